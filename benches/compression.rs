@@ -0,0 +1,62 @@
+// cargo bench --bench compression --features compression
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rust::{compress, decompress, Codec, GameInstance};
+
+const LAYERS: usize = 17;
+const CANVAS: usize = 23;
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// Stand-in for a batch of uint8 observations: mid-game boards drawn onto a 23x23 canvas with a
+// body layer per snake and a food layer, leaving the rest of the layers empty like write_obs
+fn batch(n_envs: usize) -> Vec<u8> {
+    let frame = LAYERS * CANVAS * CANVAS;
+    let mut obs = vec![0; n_envs * frame];
+    for env in 0..n_envs {
+        let mut game = GameInstance::new(11, 11, 4, 0.15);
+        for turn in 0..20 {
+            for (i, id) in game.get_player_ids().into_iter().enumerate() {
+                let m = MOVES.iter().copied().find(|&m| !game.is_move_fatal(id, m)).unwrap_or(MOVES[(i + turn) % 4]);
+                game.set_player_move(id, m);
+            }
+            game.step();
+        }
+        let out = &mut obs[env * frame..(env + 1) * frame];
+        let (_, players, food, _, _, _, _) = game.get_state();
+        let cell = |layer: usize, x: u32, y: u32| layer * CANVAS * CANVAS + (x as usize + 6) * CANVAS + y as usize + 6;
+        for (slot, id) in game.get_player_ids().iter().enumerate() {
+            let body = &players[id].body;
+            for (i, t) in body.iter().enumerate() {
+                out[cell(1 + slot.min(2), t.x, t.y)] = (body.len() - i) as u8;
+            }
+        }
+        for t in food {
+            out[cell(4, t.x, t.y)] = 1;
+        }
+    }
+    obs
+}
+
+fn codecs(c: &mut Criterion) {
+    let obs = batch(256);
+    let codecs = [("lz4", Codec::Lz4), ("zstd_1", Codec::Zstd(1)), ("zstd_3", Codec::Zstd(3))];
+    for (name, codec) in codecs {
+        let packed = compress(codec, &obs).unwrap();
+        println!("{}: {} -> {} bytes, {:.1}x", name, obs.len(), packed.len(), obs.len() as f64 / packed.len() as f64);
+    }
+    let mut group = c.benchmark_group("compress");
+    group.throughput(Throughput::Bytes(obs.len() as u64));
+    for (name, codec) in codecs {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &codec, |b, &codec| b.iter(|| compress(codec, &obs).unwrap()));
+    }
+    group.finish();
+    let mut group = c.benchmark_group("decompress");
+    group.throughput(Throughput::Bytes(obs.len() as u64));
+    for (name, codec) in codecs {
+        let packed = compress(codec, &obs).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &codec, |b, &codec| b.iter(|| decompress(codec, &packed).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, codecs);
+criterion_main!(benches);
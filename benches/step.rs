@@ -0,0 +1,69 @@
+// cargo bench --bench step
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use pyo3::Python;
+use rust::{GameInstance, GameWrapper, RayonScheduler, Scheduler, SerialScheduler};
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// Random legal-ish moves keep games running, finished games are reset like GameWrapper does
+fn advance(game: &mut GameInstance) {
+    for (i, id) in game.get_player_ids().into_iter().enumerate() {
+        let m = MOVES.iter().copied().find(|&m| !game.is_move_fatal(id, m)).unwrap_or(MOVES[i % 4]);
+        game.set_player_move(id, m);
+    }
+    game.step();
+    if game.is_over() {
+        game.reset_in_place(&mut rand::thread_rng());
+    }
+}
+
+fn game_instance_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("GameInstance::step");
+    for num_players in [1, 2, 4, 8] {
+        let mut game = GameInstance::new(11, 11, num_players, 0.15);
+        group.bench_with_input(BenchmarkId::from_parameter(num_players), &num_players, |b, _| b.iter(|| advance(&mut game)));
+    }
+    group.finish();
+}
+
+// The per-env part of GameWrapper::step, without observation writing
+fn envs_step(c: &mut Criterion) {
+    let schedulers: [(&str, Box<dyn Scheduler>); 2] = [("rayon", Box::new(RayonScheduler)), ("serial", Box::new(SerialScheduler))];
+    for (name, scheduler) in schedulers {
+        let mut group = c.benchmark_group(format!("envs_step/{}", name));
+        for n_envs in [1, 16, 256, 1024] {
+            let mut envs: Vec<_> = (0..n_envs).map(|_| Some(GameInstance::new(11, 11, 4, 0.15))).collect();
+            let mut errors = vec![None; n_envs];
+            group.bench_with_input(BenchmarkId::from_parameter(n_envs), &n_envs, |b, _| {
+                b.iter(|| {
+                    scheduler.for_each_env(&mut envs, &mut errors, &|_, gi| {
+                        advance(gi.as_mut().unwrap());
+                        Ok(())
+                    })
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+// GameWrapper::step of 4-snake envs, mostly observation writing. The board-wide variant adds
+// the turn, health, longest and length rank layers, which fill every board tile of a frame
+fn wrapper_step(c: &mut Criterion) {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let mut group = c.benchmark_group("GameWrapper::step");
+        for (name, board_wide) in [("base", false), ("board_wide", true)] {
+            let mut wrapper = GameWrapper::new(py, 64, 4, false, false, 0, 1, false, false, 11, 11, None, 0, false, false, false, false, board_wide, board_wide, false, false, false, false, false, false, false, board_wide, board_wide, None, true, false, "egocentric", Some(0)).unwrap();
+            for m in 0..4 {
+                wrapper.set_scripted_policy(m, Some("greedy_food")).unwrap();
+            }
+            wrapper.reset().unwrap();
+            group.bench_function(name, |b| b.iter(|| wrapper.step(py).unwrap()));
+        }
+        group.finish();
+    });
+}
+
+criterion_group!(benches, game_instance_step, envs_step, wrapper_step);
+criterion_main!(benches);
@@ -2,6 +2,7 @@ import numpy as np
 import ctypes
 import pathlib
 import random
+from enum import IntEnum
 from time import sleep
 from gym import spaces
 import torch
@@ -14,9 +15,23 @@ def wrap_function(lib, funcname, restype, argtypes):
     func.argtypes = argtypes
     return func
 
+class DeathReason(IntEnum):
+    """Mirrors DeathReason in gameinstance.rs"""
+    NONE = 0
+    WALL = 1
+    SELF_COLLISION = 2
+    ENEMY_BODY = 3
+    HEAD_TO_HEAD = 4
+    STARVATION = 5
+    HAZARD = 6
+    SQUAD = 7
+
 class info(ctypes.Structure):
     _fields_ = [('health', ctypes.c_uint), ('length', ctypes.c_uint), ('turn', ctypes.c_uint), ('alive_count', ctypes.c_uint), ('death_reason', ctypes.c_uint),
-        ('alive', ctypes.c_bool), ('ate', ctypes.c_bool), ('over', ctypes.c_bool)]
+        ('winner_id', ctypes.c_uint), ('rank', ctypes.c_uint), ('kills', ctypes.c_uint), ('death_turn', ctypes.c_uint), ('food_eaten', ctypes.c_uint),
+        ('squad', ctypes.c_uint), ('squad_alive', ctypes.c_uint), ('game_id', ctypes.c_uint),
+        ('alive', ctypes.c_bool), ('ate', ctypes.c_bool), ('over', ctypes.c_bool), ('draw', ctypes.c_bool), ('won', ctypes.c_bool),
+        ('max_turn_reached', ctypes.c_bool)]
 
 gamelib = None
 try:
@@ -88,7 +103,17 @@ class ParallelBattlesnakeEnv(VecEnv):
             if infoptr[i].over:
                 dones[i] = True
                 info[i]['episode'] = {}
-                if infoptr[i].alive:
+                info[i]['draw'] = infoptr[i].draw
+                info[i]['death_reason'] = DeathReason(infoptr[i].death_reason)
+                info[i]['won'] = infoptr[i].won
+                info[i]['rank'] = infoptr[i].rank
+                info[i]['kills'] = infoptr[i].kills
+                info[i]['squad'] = infoptr[i].squad
+                info[i]['max_turn_reached'] = infoptr[i].max_turn_reached
+                info[i]['TimeLimit.truncated'] = infoptr[i].max_turn_reached
+                if infoptr[i].draw or infoptr[i].max_turn_reached:
+                    info[i]['episode']['r'] = rews[i]
+                elif infoptr[i].alive:
                     rews[i] += 1.0
                     info[i]['episode']['r'] = rews[i]
                 else:
@@ -172,7 +197,17 @@ class BattlesnakeEnv(VecEnv):
             if infoptr[i].over:
                 dones[i] = True
                 info[i]['episode'] = {}
-                if infoptr[i].alive:
+                info[i]['draw'] = infoptr[i].draw
+                info[i]['death_reason'] = DeathReason(infoptr[i].death_reason)
+                info[i]['won'] = infoptr[i].won
+                info[i]['rank'] = infoptr[i].rank
+                info[i]['kills'] = infoptr[i].kills
+                info[i]['squad'] = infoptr[i].squad
+                info[i]['max_turn_reached'] = infoptr[i].max_turn_reached
+                info[i]['TimeLimit.truncated'] = infoptr[i].max_turn_reached
+                if infoptr[i].draw or infoptr[i].max_turn_reached:
+                    info[i]['episode']['r'] = rews[i]
+                elif infoptr[i].alive:
                     rews[i] += 1.0
                     info[i]['episode']['r'] = rews[i]
                 else:
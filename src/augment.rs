@@ -0,0 +1,51 @@
+use rayon::prelude::*;
+
+use crate::symmetry::D4;
+
+// Data augmentation over the dihedral group of the square canvas. Transform t is D4::ALL[t],
+// so transform 0 is the identity
+pub const NUM_TRANSFORMS: usize = 8;
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// [t][a] is the action that does under transform t what action a does on the original
+pub fn action_permutations() -> [[usize; 4]; NUM_TRANSFORMS] {
+    let mut perms = [[0; 4]; NUM_TRANSFORMS];
+    for (perm, d4) in perms.iter_mut().zip(D4::ALL) {
+        for (a, &m) in MOVES.iter().enumerate() {
+            perm[a] = MOVES.iter().position(|&n| n == d4.transform().map_action(m)).unwrap();
+        }
+    }
+    perms
+}
+
+// For one side x side layer laid out x major like the observations, the source index of every
+// destination index under transform t. Coordinates are doubled around the center so even sides
+// map onto the grid too
+fn index_map(t: usize, side: usize) -> Vec<usize> {
+    let transform = D4::ALL[t].transform();
+    let c = side as i32 - 1;
+    let mut map = vec![0; side * side];
+    for x in 0..side as i32 {
+        for y in 0..side as i32 {
+            let (dx, dy) = transform.map_offset((2 * x - c, 2 * y - c));
+            map[((dx + c) / 2 * side as i32 + (dy + c) / 2) as usize] = (x * side as i32 + y) as usize;
+        }
+    }
+    map
+}
+
+// The first k transforms of a batch of side x side layers, one after the other
+pub fn augment<T: Copy + Default + Send + Sync>(obs: &[T], side: usize, k: usize) -> Vec<T> {
+    let plane = side * side;
+    let mut out = vec![T::default(); obs.len() * k];
+    for (t, chunk) in out.chunks_mut(obs.len()).enumerate() {
+        let map = index_map(t, side);
+        chunk.par_chunks_mut(plane).zip(obs.par_chunks(plane)).for_each(|(dst, src)| {
+            for (d, &s) in dst.iter_mut().zip(&map) {
+                *d = src[s];
+            }
+        });
+    }
+    out
+}
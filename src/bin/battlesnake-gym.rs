@@ -0,0 +1,8 @@
+// cargo run --features cli --bin battlesnake-gym -- flood_fill greedy_food model.onnx
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = rust::cli::run(&args) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+}
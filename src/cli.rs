@@ -0,0 +1,107 @@
+use pyo3::prelude::*;
+
+use crate::policy;
+use crate::tournament::Tournament;
+
+const USAGE: &str = "usage: battlesnake-gym [options] ENTRANT ENTRANT...
+
+Plays every pair of entrants against each other in 1v1 games and prints the standings.
+An entrant is [NAME=]SPEC where SPEC is one of
+  random, greedy_food, flood_fill   a scripted baseline
+  PATH.onnx                         an exported policy, needs the onnx feature
+  http://HOST[:PORT][/PATH]         a snake server speaking the official API
+
+options:
+  --games N         games per match (16)
+  --width N         board width (11)
+  --height N        board height (11)
+  --max-turns N     turns after which a game is a draw, 0 for none (0)
+  --swiss ROUNDS    pair entrants Swiss-style for ROUNDS rounds instead of round robin
+  --timeout MS      move timeout of snake servers (500)
+  --replays DIR     write every game to DIR as replay JSON";
+
+struct Options {
+    games: usize,
+    width: u32,
+    height: u32,
+    max_turns: u32,
+    swiss: Option<usize>,
+    timeout_ms: u64,
+    replays: Option<String>,
+    entrants: Vec<(String, String)>,
+}
+
+fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options { games: 16, width: 11, height: 11, max_turns: 0, swiss: None, timeout_ms: 500, replays: None, entrants: Vec::new() };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name)).cloned();
+        match arg.as_str() {
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            "--games" => options.games = number(arg, value(arg)?)?,
+            "--width" => options.width = number(arg, value(arg)?)?,
+            "--height" => options.height = number(arg, value(arg)?)?,
+            "--max-turns" => options.max_turns = number(arg, value(arg)?)?,
+            "--swiss" => options.swiss = Some(number(arg, value(arg)?)?),
+            "--timeout" => options.timeout_ms = number(arg, value(arg)?)?,
+            "--replays" => options.replays = Some(value(arg)?),
+            flag if flag.starts_with("--") => return Err(format!("unknown option {}\n\n{}", flag, USAGE)),
+            entrant => {
+                // Names default to the spec with any path and .onnx dropped
+                let (name, spec) = match entrant.split_once('=') {
+                    Some((name, spec)) if !name.contains('/') => (name.to_string(), spec.to_string()),
+                    _ => {
+                        let base = entrant.trim_end_matches('/').rsplit('/').next().unwrap_or(entrant);
+                        (base.trim_end_matches(".onnx").to_string(), entrant.to_string())
+                    }
+                };
+                options.entrants.push((name, spec));
+            }
+        }
+    }
+    if options.entrants.len() < 2 {
+        return Err(format!("at least two entrants are needed\n\n{}", USAGE));
+    }
+    Ok(options)
+}
+
+fn number<T: std::str::FromStr>(name: &str, text: String) -> Result<T, String> {
+    text.parse().map_err(|_| format!("{} expects a number, got {}", name, text))
+}
+
+fn add(tournament: &mut Tournament, name: String, spec: &str, timeout_ms: u64) -> PyResult<usize> {
+    if policy::SCRIPTED.contains(&spec) {
+        return tournament.add_scripted(name, spec);
+    }
+    if spec.starts_with("http://") || spec.starts_with("https://") {
+        return tournament.add_remote(name, spec, timeout_ms);
+    }
+    if spec.ends_with(".onnx") {
+        #[cfg(feature = "onnx")]
+        return tournament.add_onnx(name, spec, true);
+        #[cfg(not(feature = "onnx"))]
+        return Err(pyo3::exceptions::PyValueError::new_err(format!("{} needs a build with the onnx feature", spec)));
+    }
+    Err(pyo3::exceptions::PyValueError::new_err(format!("unknown entrant {}, expected one of {:?}, a .onnx file or an http(s) URL", spec, policy::SCRIPTED)))
+}
+
+// The battlesnake-gym binary. Matches run through the same GameWrapper as training, so
+// libpython has to be loadable, but no Python code runs
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = parse(args)?;
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let mut tournament = Tournament::new(options.games, options.width, options.height, options.max_turns)?;
+        for (name, spec) in &options.entrants {
+            add(&mut tournament, name.clone(), spec, options.timeout_ms)?;
+        }
+        tournament.save_replays(options.replays.clone())?;
+        match options.swiss {
+            Some(rounds) => tournament.run_swiss(py, rounds)?,
+            None => tournament.run_round_robin(py)?,
+        }
+        print!("{}", tournament.table());
+        Ok(())
+    })
+    .map_err(|e: PyErr| e.to_string())
+}
@@ -0,0 +1,54 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+// Observation layers are mostly zeros, so whole batches shrink by more than 20x before they
+// go over the network or to disk. LZ4 is the fast choice, zstd the small one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    // Block format with the uncompressed size prepended as a little endian u32, as
+    // lz4.block.compress writes it
+    Lz4,
+    // A single zstd frame at this level
+    Zstd(i32),
+}
+
+impl Codec {
+    pub const NAMES: [&'static str; 2] = ["lz4", "zstd"];
+
+    pub fn parse(name: &str, level: i32) -> Option<Self> {
+        match name {
+            "lz4" => Some(Codec::Lz4),
+            "zstd" => Some(Codec::Zstd(level)),
+            _ => None,
+        }
+    }
+}
+
+pub fn compress(codec: Codec, data: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+        Codec::Zstd(level) => zstd::bulk::compress(data, level).map_err(|e| e.to_string()),
+    }
+}
+
+pub fn decompress(codec: Codec, data: &[u8]) -> Result<Vec<u8>, String> {
+    match codec {
+        Codec::Lz4 => lz4_flex::decompress_size_prepended(data).map_err(|e| e.to_string()),
+        Codec::Zstd(_) => zstd::stream::decode_all(data).map_err(|e| e.to_string()),
+    }
+}
+
+pub(crate) fn parse_codec(name: &str, level: i32) -> PyResult<Codec> {
+    Codec::parse(name, level).ok_or_else(|| PyValueError::new_err(format!("unknown codec {}, expected one of {:?}", name, Codec::NAMES)))
+}
+
+// The raw bytes behind GameWrapper.get_obs_compressed, for numpy.frombuffer with the
+// wrapper's obs dtype and reshaping to get_obs' shape
+#[pyfunction]
+pub fn decompress_obs<'py>(py: Python<'py>, data: &[u8], codec: &str) -> PyResult<&'py PyBytes> {
+    let name = codec;
+    let codec = parse_codec(name, 0)?;
+    let raw = py.allow_threads(|| decompress(codec, data)).map_err(|e| PyValueError::new_err(format!("invalid {} data: {}", name, e)))?;
+    Ok(PyBytes::new(py, &raw))
+}
@@ -0,0 +1,97 @@
+use numpy::PyArray1;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::HashMap;
+
+use crate::gameinstance::Tile;
+use crate::gamewrapper::GameWrapper;
+use crate::replay::Replay;
+
+// Plays every replay through a spectator copy of wrapper, feeding it each turn's recorded moves
+// and food, and saves the samples of GameWrapper.export_dataset to path. A replay that the
+// rules don't reproduce is an error rather than a dataset of positions that never happened
+pub(crate) fn export(wrapper: &GameWrapper, py: Python<'_>, replays: &[Replay], path: &str) -> PyResult<usize> {
+    let (mut obs, mut actions, mut outcomes, mut games) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (g, replay) in replays.iter().enumerate() {
+        let invalid = |e: String| PyValueError::new_err(format!("replay {}: {}", g, e));
+        let mut spectator = wrapper.spectator(replay)?;
+        let ids = spectator.env(0)?.get_player_ids();
+        for pair in replay.frames.windows(2) {
+            let (now, next) = (&pair[0], &pair[1]);
+            let mut moves = HashMap::new();
+            for (m, (snake, after)) in now.snakes.iter().zip(&next.snakes).enumerate().filter(|(_, (s, _))| s.alive) {
+                let board_move = move_between(snake.body[0], after.body.first().copied(), replay.width, replay.height)
+                    .ok_or_else(|| invalid(format!("snake {} does not make a move on turn {}", m, now.turn)))?;
+                obs.push(spectator.slot_obs(py, m, 0)?);
+                actions.push(spectator.slot_action(m, 0, board_move)?);
+                outcomes.push(outcome(replay, m));
+                games.push(g as u32);
+                moves.insert(ids[m], board_move);
+            }
+
+            // Food eaten this turn is still there and food spawned after it already is. The
+            // spawns land on tiles no head moves to, so the step comes out the same
+            let game = spectator.env_mut(0)?;
+            let players = game.get_state().1;
+            let bodies: Vec<Vec<Tile>> = ids.iter().map(|id| players[id].body.clone()).collect();
+            let mut food = now.food.clone();
+            food.extend(next.food.iter().filter(|t| !now.food.contains(t)));
+            game.set_layout(&bodies, &food);
+            game.set_hazards(now.hazards.iter().copied().collect());
+            spectator.step_with_moves(0, moves)?;
+
+            let players = spectator.env(0)?.get_state().1;
+            for (m, (id, after)) in ids.iter().zip(&next.snakes).enumerate() {
+                let p = &players[id];
+                if p.alive != after.alive || (p.alive && (p.body != after.body || p.health as u32 != after.health)) {
+                    return Err(invalid(format!("snake {} leaves the rules on turn {}", m, next.turn)));
+                }
+            }
+        }
+    }
+    if obs.is_empty() {
+        return Err(PyValueError::new_err("the replays hold no moves"));
+    }
+
+    let numpy = py.import("numpy")?;
+    let n = obs.len();
+    let arrays = PyDict::new(py);
+    arrays.set_item("obs", numpy.call_method1("stack", (obs,))?)?;
+    arrays.set_item("actions", PyArray1::from_vec(py, actions))?;
+    arrays.set_item("outcomes", PyArray1::from_vec(py, outcomes))?;
+    arrays.set_item("games", PyArray1::from_vec(py, games))?;
+    numpy.call_method("savez_compressed", (path,), Some(arrays))?;
+    Ok(n)
+}
+
+// The move that took a head from one frame to the next. Snakes that died on a wall have no
+// new head on the board, so any move off it will do
+fn move_between(from: Tile, to: Option<Tile>, width: u32, height: u32) -> Option<char> {
+    let (x, y) = (from.x as i64, from.y as i64);
+    if let Some(to) = to {
+        match (to.x as i64 - x, to.y as i64 - y) {
+            (0, -1) => return Some('u'),
+            (0, 1) => return Some('d'),
+            (-1, 0) => return Some('l'),
+            (1, 0) => return Some('r'),
+            _ => {}
+        }
+    }
+    [('u', y == 0), ('d', y + 1 == height as i64), ('l', x == 0), ('r', x + 1 == width as i64)]
+        .iter()
+        .find(|&&(_, off)| off)
+        .map(|&(m, _)| m)
+}
+
+// 1 for the winner, -1 for snakes eliminated by the end, 0 for draws and unfinished games
+fn outcome(replay: &Replay, slot: usize) -> f32 {
+    let last = &replay.frames[replay.frames.len() - 1].snakes[slot];
+    if replay.winner == Some(last.id) {
+        1.0
+    } else if !last.alive {
+        -1.0
+    } else {
+        0.0
+    }
+}
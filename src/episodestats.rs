@@ -0,0 +1,85 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::{json, Value};
+
+use crate::gameinstance::{field, uint, DeathReason};
+
+// Aggregate over finished episodes of the learning snake, across all envs
+#[derive(Clone, Debug, Default)]
+pub struct EpisodeStats {
+    pub episodes: u32,
+    pub turns: u64,
+    pub food: u64,
+    pub kills: u64,
+    pub wins: u32,
+    pub draws: u32,
+    // Cut off by max_turns, counted apart from losses
+    pub truncated: u32,
+    // Indexed by DeathReason as u32, None counts survivors
+    pub death_reasons: [u32; DeathReason::ALL.len()],
+}
+
+impl EpisodeStats {
+    pub fn record(&mut self, turns: u32, food: u32, kills: u32, won: bool, draw: bool, truncated: bool, death_reason: u32) {
+        self.episodes += 1;
+        self.turns += turns as u64;
+        self.food += food as u64;
+        self.kills += kills as u64;
+        self.wins += won as u32;
+        self.draws += draw as u32;
+        self.truncated += truncated as u32;
+        self.death_reasons[death_reason as usize] += 1;
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "episodes": self.episodes,
+            "turns": self.turns,
+            "food": self.food,
+            "kills": self.kills,
+            "wins": self.wins,
+            "draws": self.draws,
+            "truncated": self.truncated,
+            "death_reasons": self.death_reasons,
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let mut death_reasons = [0; DeathReason::ALL.len()];
+        let counts = field(v, "death_reasons")?.as_array().filter(|c| c.len() == death_reasons.len()).ok_or("death_reasons has the wrong length")?;
+        for (count, c) in death_reasons.iter_mut().zip(counts) {
+            *count = c.as_u64().ok_or("death_reasons are not counts")? as u32;
+        }
+        Ok(Self {
+            episodes: uint(v, "episodes")? as u32,
+            turns: uint(v, "turns")?,
+            food: uint(v, "food")?,
+            kills: uint(v, "kills")?,
+            wins: uint(v, "wins")? as u32,
+            draws: uint(v, "draws")? as u32,
+            truncated: uint(v, "truncated")? as u32,
+            death_reasons,
+        })
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let mean = |total: u64| if self.episodes > 0 { total as f64 / self.episodes as f64 } else { 0.0 };
+        let rate = |count: u32| mean(count as u64);
+        let dict = PyDict::new(py);
+        dict.set_item("episodes", self.episodes)?;
+        dict.set_item("mean_length", mean(self.turns))?;
+        dict.set_item("mean_food", mean(self.food))?;
+        dict.set_item("mean_kills", mean(self.kills))?;
+        dict.set_item("wins", self.wins)?;
+        dict.set_item("draws", self.draws)?;
+        dict.set_item("truncated", self.truncated)?;
+        dict.set_item("losses", self.episodes - self.wins - self.draws - self.truncated)?;
+        dict.set_item("win_rate", rate(self.wins))?;
+        let reasons = PyDict::new(py);
+        for reason in DeathReason::ALL {
+            reasons.set_item(reason.name(), self.death_reasons[reason as usize])?;
+        }
+        dict.set_item("death_reasons", reasons)?;
+        Ok(dict)
+    }
+}
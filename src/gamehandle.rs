@@ -0,0 +1,71 @@
+use numpy::PyArray3;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::gamewrapper::GameWrapper;
+
+// A branched copy of one env for lookahead search, see GameWrapper::clone_env and simulate.
+// Observations, info and masks read like those of a GameWrapper with a single env
+#[pyclass]
+pub struct GameHandle {
+    game: GameWrapper,
+}
+
+impl GameHandle {
+    pub(crate) fn new(game: GameWrapper) -> Self {
+        Self { game }
+    }
+
+    pub(crate) fn game(&self) -> &GameWrapper {
+        &self.game
+    }
+}
+
+#[pymethods]
+impl GameHandle {
+    #[getter]
+    fn turn(&self) -> PyResult<u32> {
+        Ok(self.game.env(0)?.get_turn())
+    }
+
+    // Over by the rules or cut off by max_turns, either way simulate won't step it further
+    fn is_over(&self) -> PyResult<bool> {
+        let game = self.game.env(0)?;
+        Ok(game.is_over() || game.max_turn_reached())
+    }
+
+    fn is_draw(&self) -> PyResult<bool> {
+        Ok(self.game.env(0)?.is_draw())
+    }
+
+    fn winner(&self) -> PyResult<Option<u32>> {
+        Ok(self.game.env(0)?.get_winner())
+    }
+
+    // Stacked observation of one model slot, shaped (channels, width, height)
+    fn get_obs(&self, py: Python<'_>, model_i: usize) -> PyResult<PyObject> {
+        if model_i >= self.game.slot_count() {
+            return Err(PyValueError::new_err(format!("model_i {} out of range for {} models", model_i, self.game.slot_count())));
+        }
+        self.game.slot_obs(py, model_i, 0)
+    }
+
+    // Arrays shaped (n_models, 1)
+    fn get_info<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.game.get_info(py)
+    }
+
+    // Shaped (n_models, 1, 4)
+    fn get_action_masks<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<bool>> {
+        self.game.get_action_masks(py)
+    }
+
+    fn get_state(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.game.get_state(py, 0)
+    }
+
+    fn get_bodies<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.game.get_bodies(py, 0)
+    }
+}
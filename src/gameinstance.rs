@@ -1,15 +1,23 @@
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::vec::Vec;
 
-pub const PLAYER_STARTING_LENGTH: usize = 5;
+pub const PLAYER_STARTING_LENGTH: usize = 3;
 const FOOD_ID: u32 = 1;
-
-// gameinstance.h
-const DEATH_NONE: u32 = 0;
-const DEATH_EATEN: u32 = 1;
-const DEATH_STARVE: u32 = 2;
-const DEATH_BODY: u32 = 2; // This is the worst -- wall collision
+pub const HAZARD_DAMAGE: usize = 14;
+pub const MINIMUM_FOOD: u32 = 1;
+pub const FOOD_SPAWN_CHANCE: f32 = 0.15;
+// Game ids start at the id every game used to share
+const FIRST_GAME_ID: u32 = 1000000;
+// Next id of an unseeded game, unique within the process until it wraps after 4 billion games
+static NEXT_GAME_ID: AtomicU32 = AtomicU32::new(FIRST_GAME_ID);
+// Words of a keyed stream set aside for each turn's draws, as a power of two
+const TURN_WORDS_LOG2: u32 = 20;
+// Sets the orientation streams' keys apart from those of the resets and turns
+const ORIENTATION_KEY: u64 = 0x6f7269656e74;
 
 type Position = (isize, isize);
 type Node = (Position, isize);
@@ -20,12 +28,46 @@ pub struct Tile {
     pub y: u32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// Values are mirrored by the DeathReason IntEnum on the Python side
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
 pub enum DeathReason {
-    None,
-    Eaten,
-    Starve,
-    Body, // This is the worst -- wall collision
+    #[default]
+    None = 0,
+    Wall = 1,
+    SelfCollision = 2,
+    EnemyBody = 3,
+    HeadToHead = 4,
+    Starvation = 5,
+    Hazard = 6,
+    // A squadmate died under shared elimination
+    Squad = 7,
+}
+
+impl DeathReason {
+    pub const ALL: [DeathReason; 8] = [
+        DeathReason::None,
+        DeathReason::Wall,
+        DeathReason::SelfCollision,
+        DeathReason::EnemyBody,
+        DeathReason::HeadToHead,
+        DeathReason::Starvation,
+        DeathReason::Hazard,
+        DeathReason::Squad,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeathReason::None => "none",
+            DeathReason::Wall => "wall",
+            DeathReason::SelfCollision => "self_collision",
+            DeathReason::EnemyBody => "enemy_body",
+            DeathReason::HeadToHead => "head_to_head",
+            DeathReason::Starvation => "starvation",
+            DeathReason::Hazard => "hazard",
+            DeathReason::Squad => "squad",
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -36,6 +78,15 @@ pub struct Player {
     pub move_dir: char,
     pub turn: usize,
     pub death_reason: DeathReason,
+    pub death_turn: u32,
+    // The snake whose head or body this one ran into, None for every other death
+    pub killed_by: Option<u32>,
+    pub kills: u32,
+    pub squad: u32,
+    // The head moved onto food on the last step
+    pub ate_this_turn: bool,
+    // Food eaten this episode, super food counting once
+    pub food_eaten: u32,
     pub body: Vec<Tile>,
 }
 
@@ -48,100 +99,548 @@ impl Player {
             move_dir: 'u',
             turn: 0,
             death_reason: DeathReason::None,
+            death_turn: 0,
+            killed_by: None,
+            kills: 0,
+            squad: 0,
+            ate_this_turn: false,
+            food_eaten: 0,
             body: Vec::new(),
         }
     }
 }
 
-pub type State = (Vec<usize>, HashMap<usize, Player>, HashSet<Tile>, usize, usize);
-pub type Parameters = (usize, usize, usize, f32);
+// The snakes of a game in a fixed-size array indexed by model slot, looked up by id like the
+// map they replace. A game has a handful of snakes, so scanning for an id beats hashing it,
+// and every rule that walks the snakes does so in slot order on every run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Players {
+    slots: Vec<Player>,
+}
+
+impl Players {
+    fn position(&self, id: u32) -> Option<usize> {
+        self.slots.iter().position(|p| p.id == id as usize)
+    }
+
+    pub fn get(&self, id: &u32) -> Option<&Player> {
+        self.position(*id).map(|i| &self.slots[i])
+    }
+
+    pub fn get_mut(&mut self, id: &u32) -> Option<&mut Player> {
+        self.position(*id).map(|i| &mut self.slots[i])
+    }
+
+    pub fn contains_key(&self, id: &u32) -> bool {
+        self.position(*id).is_some()
+    }
+
+    // The player of a model slot
+    pub fn slot(&self, m: usize) -> Option<&Player> {
+        self.slots.get(m)
+    }
+
+    // In slot order
+    pub fn values(&self) -> std::slice::Iter<'_, Player> {
+        self.slots.iter()
+    }
+
+    pub fn values_mut(&mut self) -> std::slice::IterMut<'_, Player> {
+        self.slots.iter_mut()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.slots.iter().map(|p| p.id as u32)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+impl std::ops::Index<&u32> for Players {
+    type Output = Player;
+
+    fn index(&self, id: &u32) -> &Player {
+        self.get(id).unwrap_or_else(|| panic!("no player {}", id))
+    }
+}
+
+// Squads ruleset. Player slot m plays for squad m / size, so a size of 1 is the regular
+// free-for-all and the game ends once a single squad is left
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SquadRules {
+    pub size: u32,
+    // Squadmates pass through each other's bodies and heads
+    pub allow_body_collisions: bool,
+    // When one squadmate dies the whole squad is eliminated
+    pub shared_elimination: bool,
+    // Squadmates all take the highest health in the squad every turn
+    pub shared_health: bool,
+}
 
+impl Default for SquadRules {
+    fn default() -> Self {
+        Self {
+            size: 1,
+            allow_body_collisions: true,
+            shared_elimination: true,
+            shared_health: true,
+        }
+    }
+}
+
+// A borrowed view of a game: board, players, food, width, height, turn and hazards
+pub type State<'a> = (&'a Vec<u32>, &'a Players, &'a HashSet<Tile>, u32, u32, u32, &'a HashSet<Tile>);
+
+// Everything that can change between episodes of the same GameInstance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub board_width: u32,
+    pub board_length: u32,
+    pub num_players: u32,
+    pub food_spawn_chance: f32,
+    // Food is topped up to this count every turn
+    pub minimum_food: u32,
+    // Chance each spawned food is super food, which grows the snake by super_food_growth
+    // segments instead of 1. 0 growth makes it restore health only
+    pub super_food_chance: f32,
+    pub super_food_growth: u32,
+    // Extra health lost per turn on a hazard, per stack. The official rules call it
+    // hazardDamagePerTurn and have changed it over the ladder's seasons
+    pub hazard_damage: usize,
+    // Let food spawn on hazard tiles, which the standard maps don't
+    pub food_spawns_in_hazard: bool,
+    // Cut the episode short after this many turns, 0 for no limit
+    pub max_turns: u32,
+    pub squads: SquadRules,
+    // Grow on the turn food is eaten by keeping the old tail, instead of stacking the tail so it
+    // stays one extra turn like the reference engine does. The crate's original behaviour
+    pub legacy_growth: bool,
+    // Snail mode: every snake leaves a stack of hazards as long as itself on the tile its tail
+    // just left, and each stack wears off one per turn
+    pub snail_mode: bool,
+}
+
+// The standard rules on an 11x11 duel
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            board_width: 11,
+            board_length: 11,
+            num_players: 2,
+            food_spawn_chance: FOOD_SPAWN_CHANCE,
+            minimum_food: MINIMUM_FOOD,
+            super_food_chance: 0.0,
+            super_food_growth: 1,
+            hazard_damage: HAZARD_DAMAGE,
+            food_spawns_in_hazard: false,
+            max_turns: 0,
+            squads: SquadRules::default(),
+            legacy_growth: false,
+            snail_mode: false,
+        }
+    }
+}
+
+impl Parameters {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "board_width": self.board_width,
+            "board_length": self.board_length,
+            "num_players": self.num_players,
+            "food_spawn_chance": self.food_spawn_chance,
+            "minimum_food": self.minimum_food,
+            "super_food_chance": self.super_food_chance,
+            "super_food_growth": self.super_food_growth,
+            "hazard_damage": self.hazard_damage,
+            "food_spawns_in_hazard": self.food_spawns_in_hazard,
+            "max_turns": self.max_turns,
+            "squads": {
+                "size": self.squads.size,
+                "allow_body_collisions": self.squads.allow_body_collisions,
+                "shared_elimination": self.squads.shared_elimination,
+                "shared_health": self.squads.shared_health,
+            },
+            "legacy_growth": self.legacy_growth,
+            "snail_mode": self.snail_mode,
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let squads = field(v, "squads")?;
+        Ok(Self {
+            board_width: uint(v, "board_width")? as u32,
+            board_length: uint(v, "board_length")? as u32,
+            num_players: uint(v, "num_players")? as u32,
+            food_spawn_chance: field(v, "food_spawn_chance")?.as_f64().ok_or("food_spawn_chance is not a number")? as f32,
+            minimum_food: uint(v, "minimum_food")? as u32,
+            super_food_chance: field(v, "super_food_chance")?.as_f64().ok_or("super_food_chance is not a number")? as f32,
+            super_food_growth: uint(v, "super_food_growth")? as u32,
+            hazard_damage: uint(v, "hazard_damage")? as usize,
+            food_spawns_in_hazard: boolean(v, "food_spawns_in_hazard")?,
+            max_turns: uint(v, "max_turns")? as u32,
+            squads: SquadRules {
+                size: uint(squads, "size")? as u32,
+                allow_body_collisions: boolean(squads, "allow_body_collisions")?,
+                shared_elimination: boolean(squads, "shared_elimination")?,
+                shared_health: boolean(squads, "shared_health")?,
+            },
+            legacy_growth: boolean(v, "legacy_growth")?,
+            snail_mode: boolean(v, "snail_mode")?,
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct GameInstance {
     board_width: u32,
     board_length: u32,
     num_players: u32,
     food_spawn_chance: f32,
+    minimum_food: u32,
+    super_food_chance: f32,
+    super_food_growth: u32,
+    hazard_damage: usize,
+    food_spawns_in_hazard: bool,
+    max_turns: u32,
+    squads: SquadRules,
+    legacy_growth: bool,
+    snail_mode: bool,
     game_id: u32,
     over: bool,
+    draw: bool,
     turn: u32,
     board: Vec<u32>,
-    players: HashMap<u32, Player>,
-    food: HashMap<u32, Tile>,
+    players: Players,
+    food: HashSet<Tile>,
+    // Segments gained from each super food tile, standard food (1) isn't listed
+    food_values: HashMap<Tile, u32>,
+    hazards: HashSet<Tile>,
+    // Impassable tiles of maze maps, entering one is a wall death like leaving the board
+    walls: HashSet<Tile>,
+    // Snail mode hazards left behind tails: the number of stacks on a tile, which is also the
+    // number of turns it stays hazardous
+    trails: HashMap<Tile, u32>,
+    // Healing pools, a custom variant: health a head restores on the tile every turn
+    pools: HashMap<Tile, u32>,
+    // Tiles whose contents changed during the last step: moved heads and tails, eaten and
+    // spawned food, and the bodies of snakes eliminated on that turn. May hold duplicates
+    changed: Vec<Tile>,
+    // (seed, stream) of a seeded game, which draws from ChaCha streams keyed by them, the
+    // episode and the turn instead of the thread RNG. Where a draw lands then doesn't depend
+    // on which thread ran the game or what ran before it
+    rng_key: Option<(u64, u64)>,
+    // Episodes started since the game was seeded
+    episode: u64,
 }
 
 impl GameInstance {
-    fn at(&mut self, i: usize, j: usize) -> &mut usize {
-        &mut self.board[i * self.board_length + j]
+    // The board is row major, like the observation layers
+    fn index(&self, t: Tile) -> usize {
+        (t.y * self.board_width + t.x) as usize
     }
 
-    fn at_tile(&mut self, t: Tile) -> &mut usize {
-        &mut self.board[t.x * self.board_length + t.y]
+    pub fn new(board_width: u32, board_length: u32, num_players: u32, food_spawn_chance: f32) -> Self {
+        assert!(
+            num_players as usize <= spawn_capacity(board_width, board_length),
+            "{} snakes do not fit on a {}x{} board",
+            num_players,
+            board_width,
+            board_length
+        );
+        let mut game = Self {
+            board_width,
+            board_length,
+            num_players,
+            food_spawn_chance,
+            minimum_food: MINIMUM_FOOD,
+            super_food_chance: 0.0,
+            super_food_growth: 1,
+            hazard_damage: HAZARD_DAMAGE,
+            food_spawns_in_hazard: false,
+            max_turns: 0,
+            squads: SquadRules::default(),
+            legacy_growth: false,
+            snail_mode: false,
+            game_id: FIRST_GAME_ID,
+            over: false,
+            draw: false,
+            turn: 0,
+            board: Vec::new(),
+            players: Players::default(),
+            food: HashSet::new(),
+            food_values: HashMap::new(),
+            hazards: HashSet::new(),
+            walls: HashSet::new(),
+            trails: HashMap::new(),
+            pools: HashMap::new(),
+            changed: Vec::new(),
+            rng_key: None,
+            episode: 0,
+        };
+        game.reset_in_place(&mut rand::thread_rng());
+        game
     }
 
-    pub fn new(board_width: u32, board_length: u32, num_players: u32, food_spawn_chance: f32) -> Self {
-        let mut rng = rand::thread_rng();
-        let mut game_id = 1000000;
-        let mut board = vec![0; (board_width * board_length) as usize];
-        let mut players = HashMap::new();
-        let mut food = HashMap::new();
-
-        let mut available_spawn = vec![
-            Tile { x: 1, y: 1 },
-            Tile { x: 5, y: 1 },
-            Tile { x: 9, y: 1 },
-            Tile { x: 1, y: 5 },
-            Tile { x: 9, y: 5 },
-            Tile { x: 1, y: 9 },
-            Tile { x: 5, y: 9 },
-            Tile { x: 9, y: 9 },
-        ];
-
-        available_spawn.shuffle(&mut rng);
-
-        for i in 0..num_players {
+    // Like reset_in_place, but the new episode runs with different parameters. Hazards, walls
+    // and pools that fall outside a smaller board are dropped.
+    pub fn reset_with_parameters<R: Rng>(&mut self, params: Parameters, rng: &mut R) {
+        self.board_width = params.board_width;
+        self.board_length = params.board_length;
+        self.num_players = params.num_players;
+        self.food_spawn_chance = params.food_spawn_chance;
+        self.minimum_food = params.minimum_food;
+        self.super_food_chance = params.super_food_chance;
+        self.super_food_growth = params.super_food_growth;
+        self.hazard_damage = params.hazard_damage;
+        self.food_spawns_in_hazard = params.food_spawns_in_hazard;
+        self.max_turns = params.max_turns;
+        self.squads = params.squads;
+        self.legacy_growth = params.legacy_growth;
+        self.snail_mode = params.snail_mode;
+        self.hazards.retain(|t| t.x < params.board_width && t.y < params.board_length);
+        self.walls.retain(|t| t.x < params.board_width && t.y < params.board_length);
+        self.pools.retain(|t, _| t.x < params.board_width && t.y < params.board_length);
+        self.reset_in_place(rng);
+    }
+
+    // Draws every later episode and turn from the streams of (seed, stream), starting over from
+    // the first episode. Envs of one run share the seed and take their index as the stream
+    pub fn seed_streams(&mut self, seed: u64, stream: u64) {
+        self.rng_key = Some((seed, stream));
+        self.episode = 0;
+    }
+
+    // The keyed RNG at a position of the running episode: 0 for its reset, the turn for the
+    // draws made while stepping into it. None for games without a seed
+    fn keyed_rng(&self, position: u64) -> Option<ChaCha8Rng> {
+        let (seed, stream) = self.rng_key?;
+        let mut key = [0; 32];
+        key[..8].copy_from_slice(&seed.to_le_bytes());
+        key[8..16].copy_from_slice(&self.episode.to_le_bytes());
+        let mut rng = ChaCha8Rng::from_seed(key);
+        rng.set_stream(stream);
+        rng.set_word_pos((position as u128) << TURN_WORDS_LOG2);
+        Some(rng)
+    }
+
+    // A draw for the player's orientation on the running turn, the same however often it's asked
+    // for. It comes from streams of their own keyed by the player, so it leaves the turn's other
+    // draws alone. Games without a seed key them with the game id
+    pub fn orientation_draw(&self, player_id: u32) -> u64 {
+        let (seed, stream) = self.rng_key.unwrap_or((self.game_id as u64, 0));
+        let mut key = [0; 32];
+        key[..8].copy_from_slice(&seed.to_le_bytes());
+        key[8..16].copy_from_slice(&self.episode.to_le_bytes());
+        key[16..24].copy_from_slice(&ORIENTATION_KEY.to_le_bytes());
+        key[24..28].copy_from_slice(&player_id.to_le_bytes());
+        let mut rng = ChaCha8Rng::from_seed(key);
+        rng.set_stream(stream);
+        // Each turn's draw takes two words
+        rng.set_word_pos((self.turn as u128) << 1);
+        rng.next_u64()
+    }
+
+    // reset_with_parameters drawing from the next episode's stream, or the thread RNG for games
+    // without a seed
+    pub fn reset_next_episode(&mut self, params: Parameters) {
+        match self.rng_key {
+            Some(_) => {
+                self.episode += 1;
+                let mut rng = self.keyed_rng(0).unwrap();
+                self.reset_with_parameters(params, &mut rng);
+            }
+            None => self.reset_with_parameters(params, &mut rand::thread_rng()),
+        }
+    }
+
+    // Starts the episode over from a position kept from another game, e.g. the start of an
+    // earlier episode. The game keeps its own streams, so only the position repeats
+    pub fn restart_from(&mut self, start: &GameInstance) {
+        let (rng_key, episode) = (self.rng_key, self.episode);
+        self.clone_from(start);
+        self.rng_key = rng_key;
+        self.episode = episode;
+    }
+
+    // Starts a new episode reusing the board, map and body allocations. Hazards, walls and
+    // pools are kept.
+    pub fn reset_in_place<R: Rng>(&mut self, rng: &mut R) {
+        let (board_width, board_length) = (self.board_width, self.board_length);
+        // Every episode is a new game. Seeded games draw the id so it replays with them
+        self.game_id = match self.rng_key {
+            Some(_) => rng.gen_range(FIRST_GAME_ID..=u32::MAX),
+            None => NEXT_GAME_ID.fetch_add(1, Ordering::Relaxed),
+        };
+        self.over = false;
+        self.draw = false;
+        self.turn = 0;
+        self.board.clear();
+        self.board.resize((board_width * board_length) as usize, 0);
+        self.food.clear();
+        self.food_values.clear();
+        self.trails.clear();
+        self.changed.clear();
+        let mut spare_bodies: Vec<Vec<Tile>> = self.players.slots.drain(..).map(|player| player.body).collect();
+
+        // Shuffle within each group so corners still fill before edges
+        let mut available_spawn = Vec::new();
+        for mut group in spawn_groups(board_width, board_length) {
+            group.shuffle(rng);
+            available_spawn.extend(group);
+        }
+        // Spawn points under walls are replaced by random open tiles
+        available_spawn.retain(|t| !self.walls.contains(t));
+        if available_spawn.len() < self.num_players as usize {
+            let mut open: Vec<Tile> = (0..board_length)
+                .flat_map(|y| (0..board_width).map(move |x| Tile { x, y }))
+                .filter(|t| !self.walls.contains(t) && !available_spawn.contains(t))
+                .collect();
+            open.shuffle(rng);
+            available_spawn.extend(open);
+        }
+
+        for i in 0..self.num_players {
             let mut id = rng.gen_range(1000000..9999999);
-            while players.contains_key(&id) {
+            while self.players.contains_key(&id) {
                 id = rng.gen_range(1000000..9999999);
             }
-            let mut body = Vec::new();
+            let mut body = spare_bodies.pop().unwrap_or_default();
+            body.clear();
+            // Stacked on the spawn point like the reference engine, unstacking over the first turns
             let spawn = available_spawn[i as usize];
-            body.push(spawn);
-            players.insert(id, Player { id, body });
-            board[(spawn.y * board_width + spawn.x) as usize] = id;
+            body.extend(std::iter::repeat(spawn).take(PLAYER_STARTING_LENGTH));
+            self.players.slots.push(Player { body, ..Player::new(id as usize) });
+            self.board[(spawn.y * board_width + spawn.x) as usize] = id;
+        }
+        // Squads line up with model slots
+        let squad_size = self.squads.size.max(1) as usize;
+        for (m, player) in self.players.values_mut().enumerate() {
+            player.squad = (m / squad_size) as u32;
         }
 
-        for _ in 0..num_players {
-            let mut x = rng.gen_range(0..board_width);
-            let mut y = rng.gen_range(0..board_length);
-            while board[(y * board_width + x) as usize] != 0 {
-                x = rng.gen_range(0..board_width);
-                y = rng.gen_range(0..board_length);
+        self.place_starting_food(rng);
+        for &food in &self.food {
+            self.board[(food.y * board_width + food.x) as usize] = FOOD_ID;
+        }
+    }
+
+    // Reference engine rules: top up to minimum_food, otherwise spawn a single food with
+    // food_spawn_chance
+    pub fn maybe_spawn_food<R: Rng>(&mut self, rng: &mut R) {
+        let current = self.food.len();
+        let minimum = self.minimum_food as usize;
+        if current < minimum {
+            self.place_food_randomly(minimum - current, rng);
+            return;
+        }
+        // Same integer draw as the reference engine, which makes a 15% chance spawn 14% of the time
+        let chance = (self.food_spawn_chance * 100.0).round() as u32;
+        if chance > 0 && 100 - rng.gen_range(0..100) < chance {
+            self.place_food_randomly(1, rng);
+        }
+    }
+
+    // Stops early once the board has no free tile left
+    fn place_food_randomly<R: Rng>(&mut self, n: usize, rng: &mut R) {
+        for _ in 0..n {
+            match self.unoccupied_points(false).choose(rng) {
+                Some(&t) => self.add_food(t, rng),
+                None => break,
             }
-            board[(y * board_width + x) as usize] = FOOD_ID;
-            food.insert(FOOD_ID, Tile { x, y });
         }
+    }
 
-        Self {
-            board_width,
-            board_length,
-            num_players,
-            food_spawn_chance,
-            game_id,
-            over: false,
-            turn: 0,
-            board,
-            players,
-            food,
+    fn add_food<R: Rng>(&mut self, t: Tile, rng: &mut R) {
+        self.food.insert(t);
+        if self.super_food_chance > 0.0 && rng.gen::<f32>() < self.super_food_chance {
+            self.food_values.insert(t, self.super_food_growth);
+        }
+        self.changed.push(t);
+    }
+
+    // The reference engine's starting food on its standard board sizes, where our spawn points
+    // are its own: one food diagonal to each snake, away from the center on some axis and
+    // never in a corner, then one in the center. Small boards with more than 4 snakes only get
+    // the center one. Other sizes and maze maps get one random food per snake like its
+    // fallback, and so does every snake without room next to it
+    fn place_starting_food<R: Rng>(&mut self, rng: &mut R) {
+        let standard = self.board_width == self.board_length && [7, 11, 19].contains(&self.board_width);
+        if !standard || !self.walls.is_empty() {
+            self.place_food_randomly(self.num_players as usize, rng);
+            return;
+        }
+        let (width, height) = (self.board_width as i64, self.board_length as i64);
+        let (center_x, center_y) = ((width - 1) / 2, (height - 1) / 2);
+        let free = |game: &Self, t: &Tile| {
+            !game.food.contains(t) && (game.food_spawns_in_hazard || !game.hazards.contains(t)) && game.players.values().all(|p| !p.body.contains(t))
+        };
+        let mut missing = 0;
+        if self.players.len() <= 4 || width * height >= 11 * 11 {
+            let heads: Vec<(i64, i64)> = self.players.values().map(|p| (p.body[0].x as i64, p.body[0].y as i64)).collect();
+            for (head_x, head_y) in heads {
+                let candidates: Vec<Tile> = [(head_x - 1, head_y - 1), (head_x - 1, head_y + 1), (head_x + 1, head_y - 1), (head_x + 1, head_y + 1)]
+                    .into_iter()
+                    .filter(|&(x, y)| (x, y) != (center_x, center_y))
+                    .filter(|&(x, y)| (x < head_x && head_x < center_x) || (center_x < head_x && head_x < x) || (y < head_y && head_y < center_y) || (center_y < head_y && head_y < y))
+                    .filter(|&(x, y)| !((x == 0 || x == width - 1) && (y == 0 || y == height - 1)))
+                    .map(|(x, y)| Tile { x: x as u32, y: y as u32 })
+                    .filter(|t| free(self, t))
+                    .collect();
+                match candidates.choose(rng) {
+                    Some(&t) => self.add_food(t, rng),
+                    None => missing += 1,
+                }
+            }
+        }
+        let center = Tile { x: center_x as u32, y: center_y as u32 };
+        if free(self, &center) {
+            self.add_food(center, rng);
+        }
+        self.place_food_randomly(missing, rng);
+    }
+
+    // Tiles without a body, food or wall, in row order. Hazards count as occupied unless
+    // food_spawns_in_hazard is set, and unless include_possible_moves is set, tiles a living
+    // head could move onto next turn count as occupied too
+    pub fn unoccupied_points(&self, include_possible_moves: bool) -> Vec<Tile> {
+        let mut occupied: HashSet<Tile> = self.food.iter().chain(self.walls.iter()).copied().collect();
+        if !self.food_spawns_in_hazard {
+            occupied.extend(self.hazards.iter().chain(self.trails.keys()));
         }
+        for player in self.players.values().filter(|p| p.alive) {
+            occupied.extend(player.body.iter().copied());
+            if !include_possible_moves {
+                occupied.extend(['u', 'd', 'l', 'r'].iter().filter_map(|&m| self.next_tile(player.body[0], m)));
+            }
+        }
+        let mut points = Vec::new();
+        for y in 0..self.board_length {
+            for x in 0..self.board_width {
+                if !occupied.contains(&Tile { x, y }) {
+                    points.push(Tile { x, y });
+                }
+            }
+        }
+        points
     }
 
     pub fn step(&mut self) {
         self.turn += 1;
+        self.changed.clear();
         let mut players_to_kill = Vec::new();
         let mut food_to_delete = Vec::new();
+        // Where snail mode lays this turn's trails: tails that are about to move
+        let mut trail_tiles = Vec::new();
 
         // Move players, check for out of bounds, self collisions, and food
         for player in self.players.values_mut() {
+            player.ate_this_turn = false;
             if !player.alive {
                 continue;
             }
@@ -149,148 +648,266 @@ impl GameInstance {
             // Subtract health
             player.health -= 1;
 
-            // Next head location
-            let curr_head = player.body[0];
-            let move_dir = player.move_dir;
-            let mut next_head = curr_head;
-            match move_dir {
-                'u' => next_head.y -= 1,
-                'd' => next_head.y += 1,
-                'l' => next_head.x -= 1,
-                'r' => next_head.x += 1,
-                _ => (),
+            let len = player.body.len();
+            if self.snail_mode && len >= 2 && player.body[len - 1] != player.body[len - 2] {
+                trail_tiles.push((player.body[len - 1], player.id as u32));
             }
 
-            // Check out of bounds, then check food
-            if next_head.x < 0 || next_head.x >= self.board_width || next_head.y < 0 || next_head.y >= self.board_length {
-                players_to_kill.push(player.id);
-                player.body.pop();
-            } else if self.at_tile(next_head) == FOOD_ID {
-                player.health = 100;
-                player.body.insert(0, next_head);
-                food_to_delete.push(next_head);
-            } else {
-                player.body.pop();
-                player.body.insert(0, next_head);
+            // Check out of bounds and walls, then check food
+            match next_tile(player.body[0], player.move_dir, self.board_width, self.board_length).filter(|t| !self.walls.contains(t)) {
+                // The head never makes it onto the board, so the body stays where it was. Losing
+                // the tail would leave a snake of one segment without a body
+                None => {
+                    players_to_kill.push(player.id);
+                    player.death_reason = DeathReason::Wall;
+                }
+                Some(next_head) => {
+                    let eats = self.food.contains(&next_head);
+                    let growth = if eats { self.food_values.get(&next_head).copied().unwrap_or(1) } else { 0 };
+                    let keeps_tail = self.legacy_growth && growth > 0;
+                    if !keeps_tail {
+                        self.changed.extend(player.body.pop());
+                    }
+                    player.body.insert(0, next_head);
+                    self.changed.push(next_head);
+                    if eats {
+                        player.health = 100;
+                        player.ate_this_turn = true;
+                        player.food_eaten += 1;
+                        food_to_delete.push(next_head);
+                        // The tail is stacked once per segment gained and stays put that many
+                        // more turns. Legacy growth kept the old tail for the first one
+                        let tail = *player.body.last().unwrap();
+                        let stacked = if keeps_tail { growth - 1 } else { growth };
+                        player.body.extend(std::iter::repeat(tail).take(stacked as usize));
+                    } else {
+                        // Hazards drain extra health per stack and pools restore it, unless the
+                        // snake eats there
+                        let damage = tile_damage(&self.hazards, &self.trails, &self.pools, self.hazard_damage, next_head);
+                        player.health = (player.health as i64 - damage).clamp(0, 100) as usize;
+                    }
+                }
             }
+        }
+
+        if self.squads.shared_health {
+            self.share_health(&players_to_kill);
+        }
 
-            // Starvation
-            if player.health == 0 {
-                players_to_kill.push(player.id);
-                player.death_reason = DeathReason::Starve;
+        // Running out of health takes priority over every other reason
+        for player in self.players.values_mut() {
+            if !player.alive || player.health != 0 {
+                continue;
             }
+            players_to_kill.push(player.id);
+            let head = player.body[0];
+            let on_hazard = player.death_reason != DeathReason::Wall && (self.hazards.contains(&head) || self.trails.contains_key(&head));
+            player.death_reason = if on_hazard { DeathReason::Hazard } else { DeathReason::Starvation };
         }
 
         for p in &food_to_delete {
             self.food.remove(p);
+            self.food_values.remove(p);
         }
 
-        // Reset board, add player bodies, map heads
-        self.board = vec![0; (self.board_width * self.board_length) as usize];
-        let mut heads = HashMap::new();
-        for player in self.players.values() {
-            if !player.alive {
-                continue;
-            }
-
-            let head = player.body[0];
-            heads.insert(head, player.id);
+        // The board is retained across turns and doubles as the collision grid: it is refilled
+        // with the moved bodies minus their heads, then the heads are looked up in it. Every
+        // snake has moved by now, so collisions are resolved all at once against where the
+        // snakes ended up. Like in the official rules, snakes already out this turn (off the
+        // board or out of health) are no obstacle and have no head to meet
+        let width = self.board_width;
+        let index = |t: Tile| (t.y * width + t.x) as usize;
+        self.board.fill(0);
+        let mut heads = Vec::with_capacity(self.players.len());
+        for player in self.players.values().filter(|p| p.alive && !players_to_kill.contains(&p.id)) {
             for &body_part in &player.body[1..] {
-                *self.at_tile(body_part) = player.id;
+                self.board[index(body_part)] = player.id as u32;
             }
+            heads.push((index(player.body[0]), player.id as u32));
         }
+        // Snakes sharing a head tile end up next to each other. The sort is stable, so within a
+        // tile they stay in slot order and the first longer rival is the one credited, which
+        // keeps restored snapshots playing out the same
+        heads.sort_by_key(|&(tile, _)| tile);
 
-        // Check head on head collisions
-        for player in self.players.values_mut() {
-            if !player.alive {
-                continue;
-            }
+        let mut collisions = Vec::new();
+        let mut start = 0;
+        while start < heads.len() {
+            let tile = heads[start].0;
+            let end = start + heads[start..].iter().take_while(|&&(t, _)| t == tile).count();
+            for &(_, id) in &heads[start..end] {
+                let player = &self.players[&id];
+                let passes_through = |other: &Player| self.squads.allow_body_collisions && other.id != player.id && other.squad == player.squad;
 
-            for other in self.players.values() {
-                if !other.alive || player.id == other.id {
+                // In the official order: its own body, other bodies, then heads. The own body is
+                // checked on the snake, the grid holds one snake per tile and squadmates passing
+                // through each other can share one
+                if player.body[1..].contains(&player.body[0]) {
+                    collisions.push((id, DeathReason::SelfCollision, None));
+                    continue;
+                }
+                let occupant = self.board[tile];
+                if occupant >= 1000000 && occupant != id && !passes_through(&self.players[&occupant]) {
+                    collisions.push((id, DeathReason::EnemyBody, Some(occupant)));
                     continue;
                 }
 
-                let head_1 = player.body[0];
-                let head_2 = other.body[0];
-                if head_1 == head_2 {
-                    if other.body.len() >= player.body.len() {
-                        players_to_kill.push(player.id);
-                        player.death_reason = DeathReason::Eaten;
-                    }
+                // Head on head, against every snake that is at least as long
+                let rival = heads[start..end]
+                    .iter()
+                    .map(|&(_, other)| &self.players[&other])
+                    .find(|other| other.id != player.id && !passes_through(other) && other.body.len() >= player.body.len());
+                if let Some(other) = rival {
+                    collisions.push((id, DeathReason::HeadToHead, Some(other.id as u32)));
                 }
             }
+            start = end;
+        }
+        for (id, reason, killed_by) in collisions {
+            let player = self.players.get_mut(&id).unwrap();
+            players_to_kill.push(player.id);
+            if player.death_reason == DeathReason::None {
+                player.death_reason = reason;
+                player.killed_by = killed_by;
+            }
         }
 
-        // Check for collisions with bodies
-        for player in self.players.values_mut() {
+        // Kill players, crediting whoever caused the death
+        let mut killers = Vec::new();
+        for &id in &players_to_kill {
+            let player = self.players.get_mut(&(id as u32)).unwrap();
             if !player.alive {
                 continue;
             }
-
-            let head = player.body[0];
-            if self.at_tile(head) >= &mut 1000000 {
-                players_to_kill.push(player.id);
-                player.death_reason = DeathReason::Body;
+            player.alive = false;
+            player.death_turn = self.turn;
+            killers.extend(player.killed_by);
+            self.changed.extend_from_slice(&player.body);
+        }
+        for killer in killers {
+            if let Some(player) = self.players.get_mut(&killer) {
+                player.kills += 1;
             }
         }
 
-        // Kill players
-        for &id in &players_to_kill {
-            self.players.get_mut(&id).unwrap().alive = false;
+        if self.squads.shared_elimination {
+            let fallen: HashSet<u32> = self.players.values().filter(|p| !p.alive && p.death_turn == self.turn).map(|p| p.squad).collect();
+            for player in self.players.values_mut() {
+                if player.alive && fallen.contains(&player.squad) {
+                    player.alive = false;
+                    player.death_turn = self.turn;
+                    player.death_reason = DeathReason::Squad;
+                    self.changed.extend_from_slice(&player.body);
+                }
+            }
         }
 
-        // Add new food
-        let mut rng = rand::thread_rng();
-        let mut loopiter = 0;
-
-        // GET A CHANCE TO SPAWN FOOD
-        let chance: f32 = rng.gen();
+        if self.snail_mode {
+            self.lay_trails(&trail_tiles);
+        }
 
-        // If there are no food, set chance to 0 --> Force a food spawn
-        let chance = if self.food.is_empty() { 0.0 } else { chance };
+        match self.keyed_rng(self.turn as u64) {
+            Some(mut rng) => self.maybe_spawn_food(&mut rng),
+            None => self.maybe_spawn_food(&mut rand::thread_rng()),
+        }
 
-        // If we are meant to spawn a food, then do it!
-        if chance < self.food_spawn_chance {
-            let mut x = rng.gen_range(0..self.board_width);
-            let mut y = rng.gen_range(0..self.board_length);
-            loop {
-                if self.at_tile(Tile { x, y }) == 0 {
-                    break;
-                }
-                x = rng.gen_range(0..self.board_width);
-                y = rng.gen_range(0..self.board_length);
-                loopiter += 1;
-                if loopiter >= 1000 {
-                    break;
+        // Patch the collision grid into the final board: clear the snakes that died this turn,
+        // then add the survivors' heads and the food. Squadmates passing through each other can
+        // share tiles, in which case the survivors are written in full again
+        let overlapping = self.squads.allow_body_collisions && self.squads.size > 1;
+        for player in self.players.values().filter(|p| !p.alive && p.death_turn == self.turn) {
+            for &body_part in &player.body[1..] {
+                let i = index(body_part);
+                if self.board[i] == player.id as u32 {
+                    self.board[i] = 0;
                 }
             }
-            self.at_tile(Tile { x, y }) = FOOD_ID;
-            self.food.insert(FOOD_ID, Tile { x, y });
         }
-
-        // Reset board, set players, and food
-        self.board = vec![0; (self.board_width * self.board_length) as usize];
         let mut players_alive = 0;
-        for player in self.players.values() {
-            if !player.alive {
-                continue;
-            }
+        let mut squads_alive = HashSet::new();
+        for player in self.players.values().filter(|p| p.alive) {
             players_alive += 1;
-            for &body_part in &player.body {
-                *self.at_tile(body_part) = player.id;
+            squads_alive.insert(player.squad);
+            let written = if overlapping { player.body.len() } else { 1 };
+            for &body_part in &player.body[..written] {
+                self.board[index(body_part)] = player.id as u32;
             }
         }
 
-        self.over = (players_alive <= 1 && self.num_players > 1) || (players_alive == 0 && self.num_players == 1);
+        // Solo games (a single squad) only end when every snake is dead
+        let num_squads = self.num_squads();
+        self.over = (squads_alive.len() <= 1 && num_squads > 1) || (players_alive == 0 && num_squads == 1);
+        // Everyone left died on the same turn: the game is over with no winner
+        self.draw = players_alive == 0 && num_squads > 1;
 
-        for &food in self.food.values() {
-            *self.at_tile(food) = FOOD_ID;
+        for &food in &self.food {
+            self.board[index(food)] = FOOD_ID;
         }
     }
 
-    pub fn get_state(&self) -> (&Vec<u32>, &HashMap<u32, Player>, &HashSet<Tile>, u32, u32) {
-        (&self.board, &self.players, &self.food, self.board_width, self.board_length)
+    pub fn get_state(&self) -> State<'_> {
+        (&self.board, &self.players, &self.food, self.board_width, self.board_length, self.turn, &self.hazards)
+    }
+
+    // Snail mode, after the turn's eliminations: existing stacks wear off by one, then the
+    // survivors whose tails moved stack their length on the tile the tail left. Nothing is laid
+    // under a head that just moved onto it, or under a tail kept by legacy growth
+    fn lay_trails(&mut self, tails: &[(Tile, u32)]) {
+        let changed = &mut self.changed;
+        self.trails.retain(|&t, stacks| {
+            *stacks -= 1;
+            if *stacks == 0 {
+                changed.push(t);
+            }
+            *stacks > 0
+        });
+        for &(tail, id) in tails {
+            let player = &self.players[&id];
+            if !player.alive || player.body.last() == Some(&tail) || self.players.values().any(|p| p.alive && p.body[0] == tail) {
+                continue;
+            }
+            *self.trails.entry(tail).or_insert(0) += player.body.len() as u32;
+            self.changed.push(tail);
+        }
+    }
+
+    // Segments a snake gains eating the food on the tile
+    pub fn food_value(&self, t: Tile) -> u32 {
+        self.food_values.get(&t).copied().unwrap_or(1)
+    }
+
+    // Scripted super food on a tile that holds food, 1 makes it standard again
+    pub fn set_food_value(&mut self, t: Tile, growth: u32) {
+        assert!(self.food.contains(&t), "no food at {:?}", t);
+        if growth == 1 {
+            self.food_values.remove(&t);
+        } else {
+            self.food_values.insert(t, growth);
+        }
+    }
+
+    // Hazards on a tile counting snail trails, each dealing hazard_damage
+    pub fn hazard_stacks(&self, t: Tile) -> u32 {
+        self.hazards.contains(&t) as u32 + self.trails.get(&t).copied().unwrap_or(0)
+    }
+
+    pub fn get_trails(&self) -> &HashMap<Tile, u32> {
+        &self.trails
+    }
+
+    // Health a head moving onto the tile loses on top of the turn's 1 when it doesn't eat there,
+    // negative on healing pools
+    pub fn tile_damage(&self, t: Tile) -> i64 {
+        tile_damage(&self.hazards, &self.trails, &self.pools, self.hazard_damage, t)
+    }
+
+    // Tile -> health restored per turn. Takes effect from the next step
+    pub fn set_pools(&mut self, pools: HashMap<Tile, u32>) {
+        self.pools = pools;
+    }
+
+    pub fn get_pools(&self) -> &HashMap<Tile, u32> {
+        &self.pools
     }
 
     pub fn get_parameters(&self) -> (u32, u32, u32, f32) {
@@ -306,10 +923,360 @@ impl GameInstance {
         }
     }
 
+    // Board in the shape of the official API's game state. Only living snakes are listed and
+    // coordinates are the crate's own, with y growing downwards
+    pub fn to_json(&self) -> Value {
+        let point = |t: &Tile| json!({ "x": t.x, "y": t.y });
+        let snakes: Vec<_> = self
+            .players
+            .values()
+            .filter(|p| p.alive)
+            .map(|p| {
+                json!({
+                    "id": p.id.to_string(),
+                    "health": p.health,
+                    "body": p.body.iter().map(point).collect::<Vec<_>>(),
+                    "head": point(&p.body[0]),
+                    "length": p.body.len(),
+                })
+            })
+            .collect();
+        json!({
+            "game": { "id": self.game_id.to_string() },
+            "turn": self.turn,
+            "board": {
+                "width": self.board_width,
+                "height": self.board_length,
+                "food": self.food.iter().map(point).collect::<Vec<_>>(),
+                // Stacked hazards are repeated like the official API does
+                "hazards": self
+                    .hazards
+                    .iter()
+                    .chain(self.trails.iter().flat_map(|(t, &stacks)| std::iter::repeat(t).take(stacks as usize)))
+                    .map(point)
+                    .collect::<Vec<_>>(),
+                "walls": self.walls.iter().map(point).collect::<Vec<_>>(),
+                "snakes": snakes,
+            },
+        })
+    }
+
+    // Everything from_snapshot needs to carry on with the episode, eliminated snakes included.
+    // Unlike to_json this is not meant for snake servers
+    pub fn snapshot(&self) -> Value {
+        let tiles = |tiles: &mut dyn Iterator<Item = &Tile>| tiles.map(|t| json!([t.x, t.y])).collect::<Vec<_>>();
+        let players: Vec<_> = self
+            .players
+            .values()
+            .map(|p| {
+                json!({
+                    "id": p.id,
+                    "alive": p.alive,
+                    "health": p.health,
+                    "move": p.move_dir.to_string(),
+                    "turn": p.turn,
+                    "death_reason": p.death_reason as u32,
+                    "death_turn": p.death_turn,
+                    "killed_by": p.killed_by,
+                    "kills": p.kills,
+                    "squad": p.squad,
+                    "ate_this_turn": p.ate_this_turn,
+                    "food_eaten": p.food_eaten,
+                    "body": tiles(&mut p.body.iter()),
+                })
+            })
+            .collect();
+        json!({
+            "params": self.parameters().to_json(),
+            "game_id": self.game_id,
+            "over": self.over,
+            "draw": self.draw,
+            "turn": self.turn,
+            "players": players,
+            "food": tiles(&mut self.food.iter()),
+            "food_values": self.food_values.iter().map(|(t, growth)| json!([t.x, t.y, growth])).collect::<Vec<_>>(),
+            "hazards": tiles(&mut self.hazards.iter()),
+            "walls": tiles(&mut self.walls.iter()),
+            "trails": self.trails.iter().map(|(t, stacks)| json!([t.x, t.y, stacks])).collect::<Vec<_>>(),
+            "pools": self.pools.iter().map(|(t, heal)| json!([t.x, t.y, heal])).collect::<Vec<_>>(),
+            "rng_key": self.rng_key.map(|(seed, stream)| json!([seed, stream])),
+            "episode": self.episode,
+        })
+    }
+
+    pub fn from_snapshot(v: &Value) -> Result<Self, String> {
+        let params = Parameters::from_json(field(v, "params")?)?;
+        let mut game = Self {
+            board_width: params.board_width,
+            board_length: params.board_length,
+            num_players: params.num_players,
+            food_spawn_chance: params.food_spawn_chance,
+            minimum_food: params.minimum_food,
+            super_food_chance: params.super_food_chance,
+            super_food_growth: params.super_food_growth,
+            hazard_damage: params.hazard_damage,
+            food_spawns_in_hazard: params.food_spawns_in_hazard,
+            max_turns: params.max_turns,
+            squads: params.squads,
+            legacy_growth: params.legacy_growth,
+            snail_mode: params.snail_mode,
+            game_id: uint(v, "game_id")? as u32,
+            over: boolean(v, "over")?,
+            draw: boolean(v, "draw")?,
+            turn: uint(v, "turn")? as u32,
+            board: vec![0; (params.board_width * params.board_length) as usize],
+            players: Players::default(),
+            food: tiles(field(v, "food")?)?.into_iter().collect(),
+            food_values: weighted_tiles(field(v, "food_values")?, "growth", 0)?,
+            hazards: tiles(field(v, "hazards")?)?.into_iter().collect(),
+            walls: tiles(field(v, "walls")?)?.into_iter().collect(),
+            trails: weighted_tiles(field(v, "trails")?, "stacks", 1)?,
+            pools: weighted_tiles(field(v, "pools")?, "heal", 1)?,
+            changed: Vec::new(),
+            rng_key: match field(v, "rng_key")? {
+                Value::Null => None,
+                key => match (key.get(0).and_then(Value::as_u64), key.get(1).and_then(Value::as_u64)) {
+                    (Some(seed), Some(stream)) => Some((seed, stream)),
+                    _ => return Err(format!("rng_key {} is not [seed, stream]", key)),
+                },
+            },
+            episode: uint(v, "episode")?,
+        };
+        for p in field(v, "players")?.as_array().ok_or("players is not a list")? {
+            let death_reason = uint(p, "death_reason")? as usize;
+            let player = Player {
+                id: uint(p, "id")? as usize,
+                alive: boolean(p, "alive")?,
+                health: uint(p, "health")? as usize,
+                move_dir: field(p, "move")?.as_str().and_then(|m| m.chars().next()).ok_or("move is not a direction")?,
+                turn: uint(p, "turn")? as usize,
+                death_reason: *DeathReason::ALL.get(death_reason).ok_or_else(|| format!("unknown death reason {}", death_reason))?,
+                death_turn: uint(p, "death_turn")? as u32,
+                killed_by: field(p, "killed_by")?.as_u64().map(|id| id as u32),
+                kills: uint(p, "kills")? as u32,
+                squad: uint(p, "squad")? as u32,
+                ate_this_turn: boolean(p, "ate_this_turn")?,
+                food_eaten: uint(p, "food_eaten")? as u32,
+                body: tiles(field(p, "body")?)?,
+            };
+            if player.body.is_empty() {
+                return Err(format!("snake {} has no body", player.id));
+            }
+            // step takes a point of health from every living snake first
+            if player.alive && !(1..=100).contains(&player.health) {
+                return Err(format!("snake {} has health {}, expected 1..=100", player.id, player.health));
+            }
+            game.players.slots.push(player);
+        }
+        let out_of_bounds = |t: &Tile| t.x >= params.board_width || t.y >= params.board_length;
+        if game.players.values().flat_map(|p| &p.body).chain(&game.food).chain(&game.hazards).chain(&game.walls).chain(game.trails.keys()).chain(game.pools.keys()).any(out_of_bounds) {
+            return Err("snapshot has tiles outside the board".to_string());
+        }
+        for player in game.players.values().filter(|p| p.alive) {
+            for &body_part in &player.body {
+                let i = game.index(body_part);
+                game.board[i] = player.id as u32;
+            }
+        }
+        for &food in &game.food {
+            let i = game.index(food);
+            game.board[i] = FOOD_ID;
+        }
+        Ok(game)
+    }
+
+    pub fn parameters(&self) -> Parameters {
+        Parameters {
+            board_width: self.board_width,
+            board_length: self.board_length,
+            num_players: self.num_players,
+            food_spawn_chance: self.food_spawn_chance,
+            minimum_food: self.minimum_food,
+            super_food_chance: self.super_food_chance,
+            super_food_growth: self.super_food_growth,
+            hazard_damage: self.hazard_damage,
+            food_spawns_in_hazard: self.food_spawns_in_hazard,
+            max_turns: self.max_turns,
+            squads: self.squads,
+            legacy_growth: self.legacy_growth,
+            snail_mode: self.snail_mode,
+        }
+    }
+
+    // 1 + the number of snakes that outlasted this one, so snakes eliminated on the same
+    // turn share a rank and the survivor of a finished game is 1
+    pub fn get_rank(&self, id: u32) -> u32 {
+        let player = match self.players.get(&id) {
+            Some(player) => player,
+            None => return 0,
+        };
+        let outlasted_by = self
+            .players
+            .values()
+            .filter(|other| other.id != player.id)
+            .filter(|other| if player.alive { false } else { other.alive || other.death_turn > player.death_turn })
+            .count();
+        1 + outlasted_by as u32
+    }
+
+    // Scripted positions: snakes take these bodies in get_player_ids order and the food is
+    // replaced. Everything else about the episode is kept
+    pub fn set_layout(&mut self, bodies: &[Vec<Tile>], food: &[Tile]) {
+        assert_eq!(bodies.len(), self.players.len(), "one body per snake");
+        for (id, body) in self.get_player_ids().into_iter().zip(bodies) {
+            let player = self.players.get_mut(&id).unwrap();
+            player.body.clear();
+            player.body.extend_from_slice(body);
+        }
+        self.food = food.iter().copied().collect();
+        self.food_values.clear();
+        self.board.fill(0);
+        for player in self.players.values().filter(|p| p.alive) {
+            for &body_part in &player.body {
+                let i = self.index(body_part);
+                self.board[i] = player.id as u32;
+            }
+        }
+        for &food in &self.food {
+            let i = self.index(food);
+            self.board[i] = FOOD_ID;
+        }
+    }
+
+    // Replaces the position with a hand-made one in the shape to_json writes, coordinates
+    // included. The board must match, and snakes take the slots in get_player_ids order, one
+    // per slot, each with a health and a body of at least two segments. turn, hazards and
+    // walls are optional; hazards and walls replace the current ones when given, repeated
+    // hazards count once. Snake ids are ignored. Nothing changes unless the whole scenario is valid
+    pub fn load_scenario(&mut self, v: &Value) -> Result<(), String> {
+        let board = field(v, "board")?;
+        let (width, height) = (uint(board, "width")? as u32, uint(board, "height")? as u32);
+        if (width, height) != (self.board_width, self.board_length) {
+            return Err(format!("scenario board is {}x{}, the game's {}x{}", width, height, self.board_width, self.board_length));
+        }
+        let optional = |key: &str| board.get(key).map(points).transpose();
+        let food = points(field(board, "food")?)?;
+        let hazards = optional("hazards")?;
+        let walls = optional("walls")?;
+        let turn = match v.get("turn") {
+            Some(_) => uint(v, "turn")? as u32,
+            None => 0,
+        };
+        let snakes = field(board, "snakes")?.as_array().ok_or("snakes is not a list")?;
+        if snakes.len() != self.players.len() {
+            return Err(format!("scenario has {} snakes, the game {}", snakes.len(), self.players.len()));
+        }
+        let mut bodies = Vec::new();
+        let mut healths = Vec::new();
+        for (i, snake) in snakes.iter().enumerate() {
+            let body = points(field(snake, "body")?)?;
+            if body.len() < 2 {
+                return Err(format!("snake {} needs at least 2 segments", i));
+            }
+            let health = uint(snake, "health")?;
+            if !(1..=100).contains(&health) {
+                return Err(format!("snake {} has health {}, expected 1..=100", i, health));
+            }
+            bodies.push(body);
+            healths.push(health as usize);
+        }
+        let out_of_bounds = |t: &Tile| t.x >= width || t.y >= height;
+        let mut every_tile = bodies.iter().flatten().chain(&food).chain(hazards.iter().flatten()).chain(walls.iter().flatten());
+        if let Some(t) = every_tile.find(|t| out_of_bounds(t)) {
+            return Err(format!("scenario tile ({}, {}) is outside the board", t.x, t.y));
+        }
+
+        for (id, health) in self.get_player_ids().into_iter().zip(healths) {
+            let player = self.players.get_mut(&id).unwrap();
+            *player = Player { body: std::mem::take(&mut player.body), squad: player.squad, health, ..Player::new(player.id) };
+        }
+        if let Some(hazards) = hazards {
+            self.hazards = hazards.into_iter().collect();
+        }
+        if let Some(walls) = walls {
+            self.walls = walls.into_iter().collect();
+        }
+        self.trails.clear();
+        self.over = false;
+        self.draw = false;
+        self.turn = turn;
+        self.set_layout(&bodies, &food);
+        self.changed.clear();
+        Ok(())
+    }
+
+    pub fn set_hazards(&mut self, hazards: HashSet<Tile>) {
+        self.hazards = hazards;
+    }
+
+    // Takes effect from the next step; food and snakes already on a wall tile stay until the
+    // next reset
+    pub fn set_walls(&mut self, walls: HashSet<Tile>) {
+        self.walls = walls;
+    }
+
+    pub fn get_walls(&self) -> &HashSet<Tile> {
+        &self.walls
+    }
+
+    pub fn changed_tiles(&self) -> &[Tile] {
+        &self.changed
+    }
+
     pub fn is_over(&self) -> bool {
         self.over
     }
 
+    // The turn limit ran out before the game ended by the rules
+    pub fn max_turn_reached(&self) -> bool {
+        !self.over && self.max_turns > 0 && self.turn >= self.max_turns
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.draw
+    }
+
+    // The last snake standing. None in squad games won by several survivors, see get_winning_squad
+    pub fn get_winner(&self) -> Option<u32> {
+        if !self.over || self.draw || self.num_squads() == 1 {
+            return None;
+        }
+        let mut alive = self.players.values().filter(|p| p.alive);
+        match (alive.next(), alive.next()) {
+            (Some(p), None) => Some(p.id as u32),
+            _ => None,
+        }
+    }
+
+    pub fn get_winning_squad(&self) -> Option<u32> {
+        if !self.over || self.draw || self.num_squads() == 1 {
+            return None;
+        }
+        self.players.values().find(|p| p.alive).map(|p| p.squad)
+    }
+
+    pub fn squad_alive(&self, squad: u32) -> u32 {
+        self.players.values().filter(|p| p.alive && p.squad == squad).count() as u32
+    }
+
+    pub fn num_squads(&self) -> u32 {
+        let size = self.squads.size.max(1);
+        (self.num_players + size - 1) / size
+    }
+
+    // Every living squadmate takes the highest health in its squad. Snakes already dying this
+    // turn don't contribute
+    fn share_health(&mut self, dying: &[usize]) {
+        let mut best: HashMap<u32, usize> = HashMap::new();
+        for player in self.players.values().filter(|p| p.alive && !dying.contains(&p.id)) {
+            let health = best.entry(player.squad).or_insert(0);
+            *health = std::cmp::max(*health, player.health);
+        }
+        for player in self.players.values_mut().filter(|p| p.alive) {
+            player.health = best.get(&player.squad).copied().unwrap_or(player.health);
+        }
+    }
+
     pub fn get_turn(&self) -> u32 {
         self.turn
     }
@@ -318,19 +1285,166 @@ impl GameInstance {
         self.game_id
     }
 
-    pub fn get_tile_id(&self, i: u32, j: u32) -> u32 {
-        self.board[(i * self.board_length + j) as usize]
+    pub fn get_tile_id(&self, x: u32, y: u32) -> u32 {
+        self.board[self.index(Tile { x, y })]
     }
 
     pub fn get_tile_id_from_tile(&self, t: Tile) -> u32 {
-        self.board[(t.x * self.board_length + t.y) as usize]
+        self.board[self.index(t)]
     }
 
     pub fn get_player_ids(&self) -> Vec<u32> {
-        self.players.keys().cloned().collect()
+        self.players.ids().collect()
     }
 
     pub fn get_player_id(&self, num: usize) -> Option<u32> {
-        self.players.keys().nth(num).cloned()
+        self.players.slot(num).map(|p| p.id as u32)
+    }
+
+    pub fn next_tile(&self, t: Tile, m: char) -> Option<Tile> {
+        next_tile(t, m, self.board_width, self.board_length)
+    }
+
+    // A move is fatal if it leaves the board or lands on a body segment that is
+    // guaranteed to still be there next turn (this includes reversing into the neck)
+    pub fn is_move_fatal(&self, id: u32, m: char) -> bool {
+        let player = match self.players.get(&id) {
+            Some(player) if player.alive => player,
+            _ => return true,
+        };
+        let next_head = match self.next_tile(player.body[0], m) {
+            Some(t) if !self.walls.contains(&t) => t,
+            _ => return true,
+        };
+        for other in self.players.values() {
+            if !other.alive {
+                continue;
+            }
+            if other.id != player.id && other.squad == player.squad && self.squads.allow_body_collisions {
+                continue;
+            }
+            // Tails move out of the way unless they are stacked from eating
+            let len = other.body.len();
+            let tail_stays = len >= 2 && other.body[len - 1] == other.body[len - 2];
+            let end = if tail_stays { len } else { len - 1 };
+            if other.body[..end].contains(&next_head) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// The tile one move away, or None if the move leaves the board
+pub fn next_tile(t: Tile, m: char, board_width: u32, board_length: u32) -> Option<Tile> {
+    let (x, y) = match m {
+        'u' => (t.x as i64, t.y as i64 - 1),
+        'd' => (t.x as i64, t.y as i64 + 1),
+        'l' => (t.x as i64 - 1, t.y as i64),
+        'r' => (t.x as i64 + 1, t.y as i64),
+        _ => (t.x as i64, t.y as i64),
+    };
+    if x < 0 || y < 0 || x >= board_width as i64 || y >= board_length as i64 {
+        None
+    } else {
+        Some(Tile { x: x as u32, y: y as u32 })
     }
-}
\ No newline at end of file
+}
+
+// Signed so the same path carries hazards and healing pools: damage per hazard stack, minus
+// what a pool restores
+fn tile_damage(hazards: &HashSet<Tile>, trails: &HashMap<Tile, u32>, pools: &HashMap<Tile, u32>, hazard_damage: usize, t: Tile) -> i64 {
+    let stacks = hazards.contains(&t) as i64 + trails.get(&t).copied().unwrap_or(0) as i64;
+    hazard_damage as i64 * stacks - pools.get(&t).copied().unwrap_or(0) as i64
+}
+
+// Spawn points grouped in the order the official engine fills them: corners, edge midpoints,
+// the center, then the rest of the odd lattice. All sit one tile in from the walls, and a point
+// touching an earlier one is dropped. For 11x11 the first two groups are the classic 8 spawns.
+fn spawn_groups(board_width: u32, board_length: u32) -> Vec<Vec<Tile>> {
+    if board_width < 3 || board_length < 3 {
+        return Vec::new();
+    }
+    let (min_x, mid_x, max_x) = (1, (board_width - 1) / 2, board_width - 2);
+    let (min_y, mid_y, max_y) = (1, (board_length - 1) / 2, board_length - 2);
+    let corners = vec![(min_x, min_y), (max_x, min_y), (min_x, max_y), (max_x, max_y)];
+    let edges = vec![(mid_x, min_y), (min_x, mid_y), (max_x, mid_y), (mid_x, max_y)];
+    let center = vec![(mid_x, mid_y)];
+    let mut lattice = Vec::new();
+    for x in (1..board_width - 1).step_by(2) {
+        for y in (1..board_length - 1).step_by(2) {
+            lattice.push((x, y));
+        }
+    }
+
+    let mut taken: Vec<Tile> = Vec::new();
+    let mut groups = Vec::new();
+    for candidates in [corners, edges, center, lattice] {
+        let mut group = Vec::new();
+        for (x, y) in candidates {
+            let t = Tile { x, y };
+            if taken.iter().all(|o| o.x.abs_diff(t.x) > 1 || o.y.abs_diff(t.y) > 1) {
+                taken.push(t);
+                group.push(t);
+            }
+        }
+        groups.push(group);
+    }
+    groups
+}
+
+// How many snakes can spawn on a board of this size
+pub fn spawn_capacity(board_width: u32, board_length: u32) -> usize {
+    spawn_groups(board_width, board_length).iter().map(Vec::len).sum()
+}
+
+pub(crate) fn field<'a>(v: &'a Value, key: &str) -> Result<&'a Value, String> {
+    v.get(key).ok_or_else(|| format!("missing {}", key))
+}
+
+pub(crate) fn uint(v: &Value, key: &str) -> Result<u64, String> {
+    field(v, key)?.as_u64().ok_or_else(|| format!("{} is not an unsigned integer", key))
+}
+
+pub(crate) fn boolean(v: &Value, key: &str) -> Result<bool, String> {
+    field(v, key)?.as_bool().ok_or_else(|| format!("{} is not a bool", key))
+}
+
+// [{"x": x, "y": y}, ...] as written by GameInstance::to_json
+pub(crate) fn points(v: &Value) -> Result<Vec<Tile>, String> {
+    let malformed = || format!("{} is not a list of {{\"x\": x, \"y\": y}} points", v);
+    v.as_array()
+        .ok_or_else(malformed)?
+        .iter()
+        .map(|p| match (p.get("x").and_then(Value::as_u64), p.get("y").and_then(Value::as_u64)) {
+            (Some(x), Some(y)) => Ok(Tile { x: x as u32, y: y as u32 }),
+            _ => Err(malformed()),
+        })
+        .collect()
+}
+
+// [[x, y, value], ...] with values of at least min
+pub(crate) fn weighted_tiles(v: &Value, value: &str, min: u64) -> Result<HashMap<Tile, u32>, String> {
+    let malformed = || format!("{} is not a list of [x, y, {}] tiles", v, value);
+    v.as_array()
+        .ok_or_else(malformed)?
+        .iter()
+        .map(|t| match t.as_array().map(|a| a.iter().map(Value::as_u64).collect::<Option<Vec<_>>>()) {
+            Some(Some(a)) if a.len() == 3 && a[2] >= min => Ok((Tile { x: a[0] as u32, y: a[1] as u32 }, a[2] as u32)),
+            _ => Err(malformed()),
+        })
+        .collect()
+}
+
+// [[x, y], ...] as written by GameInstance::snapshot
+pub(crate) fn tiles(v: &Value) -> Result<Vec<Tile>, String> {
+    let malformed = || format!("{} is not a list of [x, y] tiles", v);
+    v.as_array()
+        .ok_or_else(malformed)?
+        .iter()
+        .map(|t| match t.as_array().map(|xy| (xy.first().and_then(Value::as_u64), xy.get(1).and_then(Value::as_u64))) {
+            Some((Some(x), Some(y))) => Ok(Tile { x: x as u32, y: y as u32 }),
+            _ => Err(malformed()),
+        })
+        .collect()
+}
@@ -1,14 +1,47 @@
+use numpy::{PyArray1, PyArray2, PyArray3, PyReadonlyArray2};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyUserWarning, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
 use rayon::prelude::*;
-use std::hash::{Hash, Hasher};
-use std::sync::{Arc, Mutex};
+use serde_json::{json, Value};
+use rand::Rng;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use crate::gameinstance::{GameInstance, State, PLAYER_STARTING_LENGTH, DeathReason, Tile};
-
-const NUM_LAYERS: usize = 17;
-const LAYER_WIDTH: usize = 23;
-const LAYER_HEIGHT: usize = 23;
-const OBS_SIZE: usize = NUM_LAYERS * LAYER_WIDTH * LAYER_HEIGHT;
+use crate::augment;
+#[cfg(feature = "compression")]
+use crate::compression;
+use crate::dataset;
+use crate::gameinstance::{boolean, field, spawn_capacity, tiles, uint, weighted_tiles, GameInstance, Parameters, Players, PLAYER_STARTING_LENGTH, DeathReason, Tile};
+use crate::episodestats::EpisodeStats;
+use crate::gamehandle::GameHandle;
+use crate::graphobs;
+use crate::metrics::{MetricsSink, StepMetrics};
+use crate::moverequest;
+use crate::obsspec::{canvas_size, ExtraLayer, Framing, ObsDtype, ObsSpec, MIN_MAX_SNAKES, TURN_HORIZON};
+use crate::opponentpool::{OpponentPool, PoolPolicy, Sampling};
+use crate::policy::{self, Policy};
+#[cfg(feature = "prometheus")]
+use crate::prometheus::PrometheusSink;
+use crate::ratings::Ratings;
+use crate::replay::{self, Frame, Replay};
+use crate::scheduler::{RayonScheduler, Scheduler, SerialScheduler};
+use crate::statedelta::StateDelta;
+use crate::symmetry::{OrientationMode, SymmetryTransform, D4};
+use crate::territory;
+#[cfg(feature = "tui")]
+use crate::watch::Watcher;
+use crate::throughput::{Phase, Throughput};
+#[cfg(feature = "onnx")]
+use crate::onnxpolicy::OnnxPolicy;
+#[cfg(feature = "onnx")]
+use crate::policyserver;
+#[cfg(feature = "arrow")]
+use crate::rollouts::RolloutLog;
+#[cfg(feature = "arrow")]
+use arrow::pyarrow::ToPyArrow;
 
 // impl PartialEq for Tile {
 //     fn eq(&self, other: &Self) -> bool {
@@ -25,325 +58,3670 @@ const OBS_SIZE: usize = NUM_LAYERS * LAYER_WIDTH * LAYER_HEIGHT;
 //     }
 // }
 
-struct Info {
-    health: u32,
-    length: u32,
-    turn: u32,
-    alive_count: u32,
-    death_reason: u32,
-    alive: bool,
-    ate: bool,
-    over: bool,
+#[derive(Clone, Default)]
+pub struct Info {
+    pub health: u32,
+    pub length: u32,
+    pub turn: u32,
+    pub alive_count: u32,
+    pub death_reason: u32,
+    // 0 while there is no winner
+    pub winner_id: u32,
+    // Placement of this model's snake, 1 is best
+    pub rank: u32,
+    pub kills: u32,
+    // The turn the snake was eliminated on, 0 while it is alive
+    pub death_turn: u32,
+    // Food eaten this episode
+    pub food_eaten: u32,
+    pub squad: u32,
+    // Living snakes in this one's squad, itself included
+    pub squad_alive: u32,
+    // The episode the Info is about, matching Replay::game_id
+    pub game_id: u32,
+    pub alive: bool,
+    // The snake ate on the last step, whatever its health ended up at
+    pub ate: bool,
+    pub over: bool,
+    pub draw: bool,
+    // This snake's squad won, which in free-for-all games means the snake itself
+    pub won: bool,
+    // The episode was cut off by max_turns rather than ended by the rules
+    pub max_turn_reached: bool,
+}
+
+impl Info {
+    // Terminal reward of the slot: 1 for a win, -1 for elimination, else 0
+    pub(crate) fn reward(&self) -> f32 {
+        if self.draw {
+            0.0
+        } else if !self.alive {
+            -1.0
+        } else if self.won {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "health": self.health,
+            "length": self.length,
+            "turn": self.turn,
+            "alive_count": self.alive_count,
+            "death_reason": self.death_reason,
+            "winner_id": self.winner_id,
+            "rank": self.rank,
+            "kills": self.kills,
+            "death_turn": self.death_turn,
+            "food_eaten": self.food_eaten,
+            "squad": self.squad,
+            "squad_alive": self.squad_alive,
+            "game_id": self.game_id,
+            "alive": self.alive,
+            "ate": self.ate,
+            "over": self.over,
+            "draw": self.draw,
+            "won": self.won,
+            "max_turn_reached": self.max_turn_reached,
+        })
+    }
+
+    fn from_json(v: &Value) -> Result<Self, String> {
+        Ok(Self {
+            health: uint(v, "health")? as u32,
+            length: uint(v, "length")? as u32,
+            turn: uint(v, "turn")? as u32,
+            alive_count: uint(v, "alive_count")? as u32,
+            death_reason: uint(v, "death_reason")? as u32,
+            winner_id: uint(v, "winner_id")? as u32,
+            rank: uint(v, "rank")? as u32,
+            kills: uint(v, "kills")? as u32,
+            death_turn: uint(v, "death_turn")? as u32,
+            food_eaten: uint(v, "food_eaten")? as u32,
+            squad: uint(v, "squad")? as u32,
+            squad_alive: uint(v, "squad_alive")? as u32,
+            game_id: uint(v, "game_id")? as u32,
+            alive: boolean(v, "alive")?,
+            ate: boolean(v, "ate")?,
+            over: boolean(v, "over")?,
+            draw: boolean(v, "draw")?,
+            won: boolean(v, "won")?,
+            max_turn_reached: boolean(v, "max_turn_reached")?,
+        })
+    }
+}
+
+// How a slot's newest frame was laid out, so the next one can be patched from it
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct ObsFrame {
+    head: Tile,
+    transform: SymmetryTransform,
+    alive_count: usize,
+}
+
+// One write of an observation in board coordinates, before a slot's transform places it
+#[derive(Clone, Copy)]
+struct TileWrite {
+    xy: Tile,
+    layer: usize,
+    val: u8,
+    scale: f32,
+}
+
+// What every slot of an env writes alike, see shared_obs
+struct SharedObs {
+    writes: Vec<TileWrite>,
+    // Layers holding one value over the whole board, as (layer, val, scale)
+    fills: Vec<(usize, u8, f32)>,
 }
 
-#[pyclass]
+// Partial observability for one env: canvas cells further than radius from the ego head in
+// Chebyshev distance are blanked in every layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Fog {
+    radius: u32,
+    hide_enemy_health: bool,
+}
+
+#[pyclass(module = "rust")]
 pub struct GameWrapper {
     n_envs: usize,
     n_models: usize,
     envs: Vec<Option<GameInstance>>,
     obss: Vec<u8>,
+    obss_f32: Vec<f32>,
+    // Copies of the observations step_async started from, served by get_obs until step_wait
+    front_obss: Vec<u8>,
+    front_obss_f32: Vec<f32>,
+    // The thread step_async handed the wrapper to, see start_step. It hands it back with the
+    // outcome of the step
+    pending: Option<JoinHandle<(GameWrapper, Result<(), String>)>>,
+    // Every slot's stack as it was when its env's episode ended on the last step, laid out like
+    // obss and zero for envs that didn't end. Only the buffer matching the dtype is allocated
+    final_obss: Vec<u8>,
+    final_obss_f32: Vec<f32>,
+    // Reset finished envs inside step. Without it a finished env keeps its terminal state and
+    // observations, and sits out every step until reset
+    auto_reset: bool,
+    // Per env: the episode ended on the last step, and the env waits for a reset
+    episode_ended: Vec<bool>,
+    awaiting_reset: Vec<bool>,
+    // Per env: what went wrong in it during the last step or reset, raised once it's over
+    env_errors: Vec<Option<String>>,
     acts: Vec<u8>,
+    // Indexed by model_i * n_envs + env_i, so the learner's block comes first
     info: Vec<Info>,
-    fixed_orientation: bool,
-    use_symmetry: bool,
-    game_instance: Arc<Mutex<GameInstance>>,
-    // thread_pool: ThreadPool,
+    // Learner food eaten so far in each env's running episode
+    episode_food: Vec<u32>,
+    // Per slot like info: terminal rewards collected so far in the running episode
+    episode_returns: Vec<f32>,
+    // Per slot like info: (return, length, seconds since the wrapper was created) of the
+    // episode that ended on the last step, see get_info
+    episode_records: Vec<Option<(f32, u32, f64)>>,
+    created: Instant,
+    stats: EpisodeStats,
+    // Per-env hazards, walls and healing pools, carried over into every new episode
+    hazards: Vec<HashSet<Tile>>,
+    walls: Vec<HashSet<Tile>>,
+    pools: Vec<HashMap<Tile, u32>>,
+    fog: Vec<Option<Fog>>,
+    orientation_mode: OrientationMode,
+    opponent_pool: OpponentPool,
+    // Pool entry driving each (env, model slot); slot 0 is always the learner
+    opponents: Vec<Vec<Option<usize>>>,
+    // Acts for every living opponent slot the pool doesn't drive, see set_opponent_callback
+    opponent_callback: Option<PyObject>,
+    // Per model slot in every env, takes precedence over the action buffer and the opponents
+    policies: Vec<Option<Box<dyn Policy>>>,
+    // Fills model 0's actions before every step, see set_onnx_learner
+    #[cfg(feature = "onnx")]
+    learner: Option<OnnxPolicy>,
+    // Per env, applied at its next reset, see set_params and set_env_config
+    params: Vec<Parameters>,
+    obs_spec: ObsSpec,
+    n_stack: usize,
+    // Per (model, env) like info: shaping potentials of the current state and their change
+    // over the last step, see get_shaping
+    reward_shaping: bool,
+    potentials: Vec<[f32; 3]>,
+    shaping: Vec<[f32; 3]>,
+    // Reset an env as soon as model 0's snake dies instead of when the game is over
+    end_on_learner_death: bool,
+    scheduler: Box<dyn Scheduler>,
+    single_threaded: bool,
+    // Keys every env's random streams on reset with (seed, stream of env 0 + env index), so a
+    // seeded run is reproducible however the scheduler orders the envs
+    seed: Option<(u64, u64)>,
+    throughput: Throughput,
+    // Handed the StepMetrics of every step, see add_metrics_sink and set_metrics_callback
+    metrics_sinks: Vec<Box<dyn MetricsSink>>,
+    metrics_callback: Option<PyObject>,
+    // Patch each frame from the previous one instead of rewriting it, see write_obs
+    incremental_obs: bool,
+    // Per (model, env) like info, None until the slot's first frame
+    obs_frames: Vec<Option<ObsFrame>>,
+    // Per env while record_replays is on: the episode being recorded and the last finished one
+    recording: bool,
+    replays: Vec<Option<Replay>>,
+    finished_replays: Vec<Option<Replay>>,
+    // Per env, the game id and frame get_state_delta last described
+    delta_frames: Vec<Option<(u32, Frame)>>,
+    // Fixed starting positions to evaluate policies on, see make_eval_set
+    eval_set: Vec<GameInstance>,
+    // Starts of episodes the learner lost to starvation or a head-to-head, some of which new
+    // episodes start from again, see replay_interesting. Off with a capacity of 0
+    interesting_capacity: usize,
+    interesting_fraction: f64,
+    interesting: VecDeque<GameInstance>,
+    // Per env while replay_interesting is on: the start of the running episode and of the
+    // one that just finished
+    episode_starts: Vec<Option<GameInstance>>,
+    finished_starts: Vec<Option<GameInstance>>,
+    // The terminal view started by watch
+    #[cfg(feature = "tui")]
+    watcher: Option<Watcher>,
+    // While record_rollouts is on, see take_rollouts
+    #[cfg(feature = "arrow")]
+    rollouts: Option<RolloutLog>,
 }
 
 #[pymethods]
 impl GameWrapper {
-    fn orientation(&self, game_id: u32, turn: u32, player_id: u32, fixed: bool) -> u32 {
-        if fixed {
-            0
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_envs, n_models, fixed_orientation=false, use_symmetry=false, corpse_turns=0, n_stack=1, float_obs=false, hazard_layer=false, board_width=11, board_height=11, env_configs=None, max_turns=0, ally_layer=false, reward_shaping=false, territory_layer=false, distance_layer=false, turn_layer=false, health_layer=false, single_threaded=false, incremental_obs=false, wall_layer=false, hazard_damage_layer=false, healing_layer=false, food_value_layer=false, head_to_head_layer=false, longest_layer=false, length_rank_layer=false, orientation_mode=None, auto_reset=true, alive_count_scalar=false, framing="egocentric", seed=None))]
+    pub fn new(
+        py: Python<'_>,
+        n_envs: usize,
+        n_models: usize,
+        fixed_orientation: bool,
+        use_symmetry: bool,
+        corpse_turns: u32,
+        n_stack: usize,
+        float_obs: bool,
+        hazard_layer: bool,
+        board_width: u32,
+        board_height: u32,
+        env_configs: Option<Vec<&PyDict>>,
+        max_turns: u32,
+        ally_layer: bool,
+        reward_shaping: bool,
+        territory_layer: bool,
+        distance_layer: bool,
+        turn_layer: bool,
+        health_layer: bool,
+        single_threaded: bool,
+        incremental_obs: bool,
+        wall_layer: bool,
+        hazard_damage_layer: bool,
+        healing_layer: bool,
+        food_value_layer: bool,
+        head_to_head_layer: bool,
+        longest_layer: bool,
+        length_rank_layer: bool,
+        orientation_mode: Option<&str>,
+        auto_reset: bool,
+        alive_count_scalar: bool,
+        framing: &str,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        // Caught here rather than as an index panic on a worker thread at the first step
+        if n_envs == 0 {
+            return Err(PyValueError::new_err("n_envs must be at least 1"));
+        }
+        if n_models == 0 {
+            return Err(PyValueError::new_err("n_models must be at least 1"));
+        }
+        if n_stack == 0 {
+            return Err(PyValueError::new_err("n_stack must be at least 1"));
+        }
+        let base = Parameters {
+            board_width,
+            board_length: board_height,
+            num_players: n_models as u32,
+            max_turns,
+            ..Parameters::default()
+        };
+        // One dict of set_params keys per env, unset keys fall back to the arguments above
+        let params = match env_configs {
+            Some(configs) => {
+                if configs.len() != n_envs {
+                    return Err(PyValueError::new_err(format!("expected {} env configs, got {}", n_envs, configs.len())));
+                }
+                configs.into_iter().map(|c| params_from_dict(base, c)).collect::<PyResult<Vec<_>>>()?
+            }
+            None => vec![base; n_envs],
+        };
+        let framing = Framing::parse(framing).ok_or_else(|| PyValueError::new_err(format!("unknown framing {}, expected one of {:?}", framing, Framing::NAMES)))?;
+        // fixed_orientation and use_symmetry are the older spellings of none and face_up.
+        // Absolute frames keep the board the way it is
+        let fallback = if fixed_orientation || framing == Framing::Absolute { OrientationMode::None } else { OrientationMode::Flips };
+        let orientation_mode = match orientation_mode {
+            Some(_) if fixed_orientation || use_symmetry => {
+                return Err(PyValueError::new_err("orientation_mode replaces fixed_orientation and use_symmetry, pass only one"));
+            }
+            Some(name) => OrientationMode::parse(name)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown orientation_mode {}, expected one of {:?}", name, OrientationMode::NAMES)))?,
+            None if use_symmetry => OrientationMode::FaceUp,
+            None => fallback,
+        };
+        // Facing snakes up transposes the board, which only keeps its shape when it is square
+        let orientation_mode = if orientation_mode == OrientationMode::FaceUp && params.iter().any(|p| p.board_width != p.board_length) {
+            PyErr::warn(py, py.get_type::<PyUserWarning>(), "face_up orientation is disabled on non-square boards", 1)?;
+            fallback
         } else {
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            game_id.hash(&mut hasher);
-            player_id.hash(&mut hasher);
-            turn.hash(&mut hasher);
-            hasher.finish() as u32
+            orientation_mode
+        };
+        let dtype = if float_obs { ObsDtype::F32 } else { ObsDtype::U8 };
+        // Every env is padded to the canvas of the largest board
+        let canvas_width = params.iter().map(|p| p.board_width).max().unwrap_or(board_width);
+        let canvas_height = params.iter().map(|p| p.board_length).max().unwrap_or(board_height);
+        let obs_spec = ObsSpec {
+            territory_layer,
+            distance_layer,
+            turn_layer,
+            health_layer,
+            wall_layer,
+            hazard_damage_layer,
+            healing_layer,
+            food_value_layer,
+            head_to_head_layer,
+            longest_layer,
+            length_rank_layer,
+            // The one-hot alive count layers grow past 8 snakes
+            max_snakes: std::cmp::max(n_models, MIN_MAX_SNAKES),
+            alive_count_scalar,
+            ..ObsSpec::new(canvas_width, canvas_height, corpse_turns, hazard_layer, ally_layer, dtype).framed(framing, canvas_width, canvas_height)
+        };
+        if framing == Framing::Absolute && orientation_mode != OrientationMode::None {
+            return Err(PyValueError::new_err("absolute framing needs orientation_mode none"));
+        }
+        for p in &params {
+            check_params(p, &obs_spec, n_models, orientation_mode)?;
+        }
+        // Only the buffer matching the dtype is allocated
+        let obs_len = n_models * n_envs * n_stack * obs_spec.obs_size();
+        Ok(Self {
+            n_envs,
+            n_models,
+            envs: (0..n_envs).map(|_| None).collect(),
+            obss: if float_obs { Vec::new() } else { vec![0; obs_len] },
+            obss_f32: if float_obs { vec![0.0; obs_len] } else { Vec::new() },
+            front_obss: Vec::new(),
+            front_obss_f32: Vec::new(),
+            pending: None,
+            final_obss: if float_obs { Vec::new() } else { vec![0; obs_len] },
+            final_obss_f32: if float_obs { vec![0.0; obs_len] } else { Vec::new() },
+            auto_reset,
+            episode_ended: vec![false; n_envs],
+            env_errors: vec![None; n_envs],
+            awaiting_reset: vec![false; n_envs],
+            acts: vec![0; n_models * n_envs],
+            info: (0..n_models * n_envs).map(|_| Info::default()).collect(),
+            episode_food: vec![0; n_envs],
+            episode_returns: vec![0.0; n_models * n_envs],
+            episode_records: vec![None; n_models * n_envs],
+            created: Instant::now(),
+            stats: EpisodeStats::default(),
+            hazards: vec![HashSet::new(); n_envs],
+            walls: vec![HashSet::new(); n_envs],
+            pools: vec![HashMap::new(); n_envs],
+            fog: vec![None; n_envs],
+            orientation_mode,
+            opponent_pool: OpponentPool::default(),
+            opponents: vec![vec![None; n_models]; n_envs],
+            opponent_callback: None,
+            policies: (0..n_models).map(|_| None).collect(),
+            #[cfg(feature = "onnx")]
+            learner: None,
+            params,
+            obs_spec,
+            n_stack,
+            reward_shaping,
+            potentials: vec![[0.0; 3]; if reward_shaping { n_models * n_envs } else { 0 }],
+            shaping: vec![[0.0; 3]; if reward_shaping { n_models * n_envs } else { 0 }],
+            end_on_learner_death: true,
+            scheduler: if single_threaded { Box::new(SerialScheduler) } else { Box::new(RayonScheduler) },
+            single_threaded,
+            seed: seed.map(|seed| (seed, 0)),
+            throughput: Throughput::default(),
+            metrics_sinks: Vec::new(),
+            metrics_callback: None,
+            incremental_obs,
+            obs_frames: vec![None; n_models * n_envs],
+            recording: false,
+            replays: vec![None; n_envs],
+            finished_replays: vec![None; n_envs],
+            delta_frames: vec![None; n_envs],
+            eval_set: Vec::new(),
+            interesting_capacity: 0,
+            interesting_fraction: 0.0,
+            interesting: VecDeque::new(),
+            episode_starts: vec![None; n_envs],
+            finished_starts: vec![None; n_envs],
+            #[cfg(feature = "tui")]
+            watcher: None,
+            #[cfg(feature = "arrow")]
+            rollouts: None,
+        })
+    }
+
+    // Drawn from the game's keyed streams for the snake and turn, see
+    // GameInstance::orientation_draw, so a seeded run gives every slot the same orientations
+    fn orientation(&self, game: &GameInstance, player_id: u32) -> D4 {
+        let draw = || game.orientation_draw(player_id);
+        match self.orientation_mode {
+            OrientationMode::None | OrientationMode::FaceUp => D4::Identity,
+            OrientationMode::Flips => {
+                let bits = draw();
+                D4::from_flips(bits & 1 != 0, bits & 2 != 0)
+            }
+            OrientationMode::D4 => D4::ALL[(draw() % 8) as usize],
         }
     }
 
-    fn get_action(&self, model_i: usize, env_i: usize, ori: u32, player_id: u32, game_state: State) -> char {
+    fn get_action(&self, model_i: usize, env_i: usize, ori: D4, player_id: u32, game: &GameInstance) -> Result<char, String> {
         let moves = ['u', 'd', 'l', 'r'];
         let index = self.acts[model_i * self.n_envs + env_i];
-        let mut action = moves[index];
+        let (head, neck) = head_and_neck(game.get_state().1, player_id).ok_or_else(|| format!("player {} of model {} has no snake to move", player_id, model_i))?;
+        let action = moves.get(index as usize).ok_or_else(|| format!("action {} of model {} is not in 0..4", index, model_i))?;
+        Ok(self.decode_action(*action, ori, head, neck))
+    }
+
+    // The writes every slot of an env makes alike: bodies, corpses, food, hazards, walls and the
+    // board-wide layers that don't depend on the ego snake. They're worked out once per env and
+    // step and handed to write_obs for each model, which only adds the ego layers
+    fn shared_obs(&self, env_i: usize, game: &GameInstance) -> SharedObs {
+        let game_state = game.get_state();
         let players = game_state.1;
-        let head;
-        let neck;
-        if let Some(player) = players.get(&player_id) {
-            head = player.body.front().unwrap();
-            neck = player.body.iter().nth(1).unwrap();
-        } else {
-            panic!("Player not found");
-        }
-        let flip_y = false;
-        let transpose = false;
-        let transpose_rotate = false;
-        let diff_x = head.x as i32 - neck.x as i32;
-        let diff_y = head.y as i32 - neck.y as i32;
+        let mut writes = Vec::new();
+        let mut assign = |xy: Tile, layer: usize, val: u8, scale: f32| writes.push(TileWrite { xy, layer, val, scale });
 
-        // We'll rotate the inputs such that all snakes face up
-        if self.use_symmetry {
-            // Disable orientation rotations
-            // YOU CAN ONLY DO THIS IF THE GAME BOARD IS SQUARE
-            if diff_x == 0 {
-                // Check if head is above neck
-                if diff_y == 1 {
-                    flip_y = true;
-                }
-            } else {
-                // We're going to need a transpose here
-                if diff_x == 1 {
-                    // head is on the right
-                    transpose_rotate = true;
+        for player in players.values() {
+            if !player.alive || player.body.is_empty() {
+                continue;
+            }
+            // Assign health on head
+            assign(player.body[0], 0, player.health as u8, 100.0);
+            let mut i = 0;
+            let (mut tail_1, mut tail_2) = (Tile { x: 0, y: 0 }, Tile { x: 0, y: 0 });
+            for body_part in player.body.iter().rev() {
+                if i == 0 {
+                    tail_1 = *body_part;
                 }
-                if diff_x == -1 {
-                    transpose = true;
+                if i == 1 {
+                    tail_2 = *body_part;
+
+                    // Check if the tails are the same
+                    if tail_1 == tail_2 {
+                        // Double tail
+                        assign(*body_part, 7, 1, 1.0);
+                    }
                 }
+                assign(*body_part, 1, 1, 1.0);
+                assign(*body_part, 2, std::cmp::min(i, 255) as u8, player.body.len() as f32);
+                i += 1;
             }
         }
 
-        if self.use_symmetry {
-            if transpose {
-                match action {
-                    'l' => action = 'u',
-                    'r' => action = 'd', // this is the bad move
-                    'u' => action = 'l',
-                    'd' => action = 'r',
-                    _ => (),
+        // Recently eliminated snakes fade out over corpse_turns
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Corpse) {
+            let turn = game_state.5;
+            for player in players.values() {
+                if player.alive || turn - player.death_turn >= self.obs_spec.corpse_turns {
+                    continue;
                 }
-            }
-            if transpose_rotate {
-                match action {
-                    'l' => action = 'u',
-                    'r' => action = 'd', // this is the bad move
-                    'u' => action = 'r',
-                    'd' => action = 'l',
-                    _ => (),
+                let fade = std::cmp::min(self.obs_spec.corpse_turns - (turn - player.death_turn), 255) as u8;
+                for &body_part in &player.body {
+                    assign(body_part, layer, fade, self.obs_spec.corpse_turns as f32);
                 }
             }
-            if flip_y {
-                match action {
-                    'l' => action = 'l',
-                    'r' => action = 'r',
-                    'u' => action = 'd', // this is the bad move
-                    'd' => action = 'u',
-                    _ => (),
-                }
+        }
+
+        let food = game_state.2;
+        for &xy in food {
+            assign(xy, 4, 1, 1.0);
+        }
+
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::FoodValue) {
+            for &xy in food {
+                assign(xy, layer, std::cmp::min(game.food_value(xy), 255) as u8, 1.0);
+            }
+        }
+
+        let trails = game.get_trails();
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Hazard) {
+            for &xy in game_state.6.iter().chain(trails.keys().filter(|t| !game_state.6.contains(t))) {
+                assign(xy, layer, 1, 1.0);
+            }
+        }
+
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::HazardDamage) {
+            for &xy in game_state.6.iter().chain(trails.keys().filter(|t| !game_state.6.contains(t))) {
+                assign(xy, layer, game.tile_damage(xy).clamp(0, 255) as u8, 100.0);
             }
         }
 
-        if !self.use_symmetry {
-            if (ori & 1) != 0 && (action == 'l' || action == 'r') {
-                action = if action == 'l' { 'r' } else { 'l' };
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Healing) {
+            for (&xy, &heal) in game.get_pools() {
+                assign(xy, layer, std::cmp::min(heal, 255) as u8, 100.0);
             }
-            if (ori & 2) != 0 && (action == 'u' || action == 'd') {
-                action = if action == 'd' { 'u' } else { 'd' };
+        }
+
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Wall) {
+            for &xy in game.get_walls() {
+                assign(xy, layer, 1, 1.0);
             }
         }
 
-        action
+        let mut fills = vec![(5, 1, 1.0)];
+        // Signal how many players are alive
+        fills.extend(self.obs_spec.alive_layer(players.values().filter(|p| p.alive).count()));
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Turn) {
+            let horizon = match self.params[env_i].max_turns {
+                0 => TURN_HORIZON,
+                max_turns => max_turns,
+            };
+            fills.push((layer, (std::cmp::min(game_state.5, horizon) * 255 / horizon) as u8, 255.0));
+        }
+        SharedObs { writes, fills }
     }
 
-    fn write_obs(&mut self, model_i: usize, env_i: usize, player_id: u32, game_state: State, ori: u32) {
+    // Writes the newest frame of a slot from the env's shared writes and the slot's own ego
+    // layers. With incremental observations and the dirty tiles of the last step, the previous
+    // frame is shifted along with the head and only those tiles are rewritten. Resets,
+    // orientation changes and a new alive count rewrite the whole frame
+    #[allow(clippy::too_many_arguments)]
+    fn write_obs(&mut self, model_i: usize, env_i: usize, player_id: u32, game: &GameInstance, ori: D4, dirty: Option<&[bool]>, shared: &SharedObs) {
+        let game_state = game.get_state();
         let players = game_state.1;
-        let (head, neck) = match players.get(&player_id) {
-            Some(player) => (player.body[0], player.body[1]),
-            None => panic!("Player not found"),
+        if !players.contains_key(&player_id) {
+            self.env_errors[env_i] = Some(format!("player {} of model {} is not in the game", player_id, model_i));
+            return;
+        }
+        let (head, neck) = match head_and_neck(players, player_id) {
+            Some(head_and_neck) => head_and_neck,
+            None => return,
         };
 
-        let mut flip_y = false;
-        let mut transpose = false;
-        let mut transpose_rotate = false;
-        let diff_x = head.x as i32 - neck.x as i32;
-        let diff_y = head.y as i32 - neck.y as i32;
+        // We'll rotate the inputs such that all snakes face up, which replaces the orientation
+        let transform = self.slot_transform(ori, head, neck);
+        let (layer_width, layer_height) = (self.obs_spec.layer_width as i32, self.obs_spec.layer_height as i32);
+        let absolute = self.obs_spec.framing == Framing::Absolute;
+        let canvas = |xy: Tile| if absolute { (xy.x as i32, xy.y as i32) } else { transform.map_point(xy, head, layer_width, layer_height) };
 
-        // We'll rotate the inputs such that all snakes face up
-        if self.use_symmetry {
-            // Disable orientation rotations
-            ori = 0;
-            // YOU CAN ONLY DO THIS IF THE GAME BOARD IS SQUARE
-            if diff_x == 0 {
-                // Check if head is above neck
-                if diff_y == 1 {
-                    flip_y = true;
-                }
-            } else {
-                // We're going to need a transpose here
-                if diff_x == 1 {
-                    // head is on the right
-                    transpose_rotate = true;
+        let alive_count = players.values().filter(|p| p.alive).count();
+        let frame = ObsFrame { head, transform, alive_count };
+        let k = model_i * self.n_envs + env_i;
+        let shift = match (dirty, self.obs_frames[k]) {
+            // Absolute frames stay put. Fogged frames aren't patched in either framing, the
+            // edge of the fog moves with the head
+            (Some(_), Some(prev)) if absolute && self.obs_spec.patchable() && self.fog[env_i].is_none() && prev.alive_count == alive_count => Some(0),
+            (Some(_), Some(prev)) if self.obs_spec.patchable() && self.fog[env_i].is_none() && prev.transform == transform && prev.alive_count == alive_count => {
+                let (prev_x, prev_y) = canvas(prev.head);
+                let (shift_x, shift_y) = (prev_x - layer_width / 2, prev_y - layer_height / 2);
+                // The canvas border is never on the board, so moving a whole frame by one tile
+                // only carries zeros across columns and layers
+                if shift_x.abs() <= 1 && shift_y.abs() <= 1 {
+                    Some((shift_x * layer_height + shift_y) as isize)
+                } else {
+                    None
                 }
-                if diff_x == -1 {
-                    transpose = true;
+            }
+            _ => None,
+        };
+        self.obs_frames[k] = Some(frame);
+        let mask = if shift.is_some() { dirty } else { None };
+
+        let base = self.frame_offset(model_i, env_i, self.n_stack - 1);
+        let size = self.obs_spec.obs_size();
+        match self.obs_spec.dtype {
+            ObsDtype::U8 => shift_frame(&mut self.obss[base..base + size], shift),
+            ObsDtype::F32 => shift_frame(&mut self.obss_f32[base..base + size], shift),
+        }
+        let board_width = game_state.3;
+        if let Some(mask) = mask {
+            for (i, _) in mask.iter().enumerate().filter(|&(_, &d)| d) {
+                let xy = Tile { x: i as u32 % board_width, y: i as u32 / board_width };
+                let (x, y) = canvas(xy);
+                for l in 0..self.obs_spec.num_layers() as i32 {
+                    let i = base + (l * layer_height * layer_width + x * layer_height + y) as usize;
+                    match self.obs_spec.dtype {
+                        ObsDtype::U8 => self.obss[i] = 0,
+                        ObsDtype::F32 => self.obss_f32[i] = 0.0,
+                    }
                 }
             }
         }
 
-        let get_x = |xy: Tile| {
-            let mut x = (xy.x as i32 - head.x as i32) * if ori & 1 != 0 { -1 } else { 1 };
-            let mut y = (xy.y as i32 - head.y as i32) * if ori & 2 != 0 { -1 } else { 1 };
-            x += LAYER_WIDTH / 2;
-            y += LAYER_HEIGHT / 2;
-
-            if transpose || transpose_rotate {
-                y
-            } else {
-                // Default case, return x
-                x
+        let float_obs = self.obs_spec.dtype == ObsDtype::F32;
+        let writes = self.obs_spec.writes();
+        // f32 observations are normalized here by val / scale, so no second pass is needed
+        let assign = |xy: Tile, l: usize, val: u8, scale: f32| {
+            if let Some(mask) = mask {
+                if !mask[(xy.y * board_width + xy.x) as usize] {
+                    return;
+                }
             }
-        };
-
-        let get_y = |xy: Tile| {
-            let mut x = (xy.x as i32 - head.x as i32) * if ori & 1 != 0 { -1 } else { 1 };
-            let mut y = (xy.y as i32 - head.y as i32) * if ori & 2 != 0 { -1 } else { 1 };
-            x += LAYER_WIDTH / 2;
-            y += LAYER_HEIGHT / 2;
+            let (x, y) = canvas(xy);
 
-            if transpose {
-                x
-            } else if transpose_rotate {
-                LAYER_WIDTH as i32 - x - 1
-            } else if flip_y {
-                LAYER_HEIGHT as i32 - y - 1
-            } else {
-                // Default case, return y
-                y
+            if x >= 0 && x < layer_width && y >= 0 && y < layer_height {
+                let i = base + (l as i32 * layer_height * layer_width + x * layer_height + y) as usize;
+                if float_obs {
+                    writes[l].apply_f32(&mut self.obss_f32[i], val as f32 / scale);
+                } else {
+                    let clipped = writes[l].apply_u8(&mut self.obss[i], val);
+                    // Saturated silently unless overflow checks are built in
+                    if cfg!(feature = "obs-overflow-checks") && clipped {
+                        panic!("layer {} overflowed at ({}, {})", self.obs_spec.layers()[l].0, xy.x, xy.y);
+                    }
+                }
             }
         };
 
-        let assign = |xy: Tile, l: usize, val: u8| {
-            let x = get_x(xy);
-            let y = get_y(xy);
-
-            if x >= 0 && x < LAYER_WIDTH as i32 && y >= 0 && y < LAYER_HEIGHT as i32 {
-                self.obss[model_i * self.n_envs * OBS_SIZE + env_i * OBS_SIZE + l * (LAYER_HEIGHT * LAYER_WIDTH) + x as usize * LAYER_HEIGHT + y as usize] += val;
-            }
-        };
+        for w in &shared.writes {
+            assign(w.xy, w.layer, w.val, w.scale);
+        }
 
-        let player_size = players.get(&player_id).unwrap().body.len();
+        let player_size = players[&player_id].body.len();
         // Assign head_mask
-        assign(players.get(&player_id).unwrap().body[0], 6, 1);
+        assign(head, 6, 1, 1.0);
 
-        let mut alive_count = 0;
+        // Lengths relative to the ego snake
         for player in players.values() {
-            if !player.alive {
+            if !player.alive || player.body.is_empty() || player.id == player_id {
                 continue;
             }
-            alive_count += 1;
-            // Assign health on head
-            assign(player.body[0], 0, player.health);
-            let mut i = 0;
-            let (mut tail_1, mut tail_2) = (Tile { x: 0, y: 0 }, Tile { x: 0, y: 0 });
-            for body_part in player.body.iter().rev() {
-                if i == 0 {
-                    tail_1 = *body_part;
+            for &body_part in &player.body {
+                // Store the difference, capped where a u8 ends
+                if player.body.len() >= player_size {
+                    assign(body_part, 8, std::cmp::min(1 + player.body.len() - player_size, 255) as u8, player_size as f32);
                 }
-                if i == 1 {
-                    tail_2 = *body_part;
+                if player.body.len() < player_size {
+                    assign(body_part, 9, std::cmp::min(player_size - player.body.len(), 255) as u8, player_size as f32);
+                }
+            }
+            assign(player.body[0], 3, if player.body.len() >= player_size { 1 } else { 0 }, 1.0);
+        }
 
-                    // Check if the tails are the same
-                    if tail_1 == tail_2 {
-                        // Double tail
-                        assign(*body_part, 7, 1);
-                    }
+        let walls = game.get_walls();
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Territory) {
+            let territory = territory::voronoi(players, walls, game_state.3, game_state.4);
+            for (i, &owner) in territory.owner.iter().enumerate() {
+                if owner == Some(player_id) {
+                    let xy = Tile { x: i as u32 % game_state.3, y: i as u32 / game_state.3 };
+                    assign(xy, layer, 1, 1.0);
                 }
-                assign(*body_part, 1, 1);
-                assign(*body_part, 2, std::cmp::min(i, 255) as u8);
-                if player.id != player_id {
-                    if player.body.len() >= player_size {
-                        assign(*body_part, 8, 1 + player.body.len() - player_size); // Store the difference
-                    }
-                    if player.body.len() < player_size {
-                        assign(*body_part, 9, -player.body.len() + player_size); // Store the difference
+            }
+        }
+
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Distance) {
+            let head = players[&player_id].body[0];
+            let dist = territory::distances(players, walls, game_state.3, game_state.4, head);
+            for (i, &d) in dist.iter().enumerate() {
+                let xy = Tile { x: i as u32 % game_state.3, y: i as u32 / game_state.3 };
+                assign(xy, layer, std::cmp::min(d, 255) as u8, 255.0);
+            }
+        }
+
+        // Where the ego head could meet an enemy head next turn, and whether it would survive
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::HeadToHead) {
+            let ego = &players[&player_id];
+            for enemy in players.values().filter(|p| p.alive && !p.body.is_empty() && p.squad != ego.squad) {
+                let outcome = if enemy.body.len() >= player_size { 2 } else { 1 };
+                for m in ['u', 'd', 'l', 'r'] {
+                    if let Some(xy) = game.next_tile(enemy.body[0], m).filter(|t| !walls.contains(t)) {
+                        assign(xy, layer, outcome, 2.0);
                     }
                 }
-                i += 1;
             }
-            if player.id != player_id {
-                assign(player.body[0], 3, if player.body.len() >= player_size { 1 } else { 0 });
+        }
+
+        if let Some(layer) = self.obs_spec.layer(ExtraLayer::Ally) {
+            let squad = players[&player_id].squad;
+            for player in players.values() {
+                if !player.alive || player.id == player_id || player.squad != squad {
+                    continue;
+                }
+                for &body_part in &player.body {
+                    assign(body_part, layer, 1, 1.0);
+                }
             }
         }
 
-        // Subtract 1 from alive_count to get the layer index
-        alive_count -= 2;
+        let longer = players.values().filter(|p| p.alive && p.id != player_id && p.body.len() >= player_size).collect::<Vec<_>>();
+        let ego_fills = [
+            (self.obs_spec.layer(ExtraLayer::Health), players[&player_id].health as u8, 100.0),
+            (self.obs_spec.layer(ExtraLayer::Longest), longer.is_empty() as u8, 1.0),
+            (self.obs_spec.layer(ExtraLayer::LengthRank), std::cmp::min(1 + longer.iter().filter(|p| p.body.len() > player_size).count(), 255) as u8, self.obs_spec.max_snakes as f32),
+        ];
 
-        let food = game_state.2;
-        for &xy in food {
-            assign(xy, 4, 1);
+        // Board-wide layers are filled a canvas column at a time, a column being contiguous. The
+        // board maps onto a rectangle of the canvas in any orientation, spanned by two corners.
+        // The ego head is on it, so the clipped rectangle is never empty. Nothing else writes
+        // these layers, so their cells are blank or already hold the value and patched frames
+        // can be filled over
+        let (x0, y0) = canvas(Tile { x: 0, y: 0 });
+        let (x1, y1) = canvas(Tile { x: board_width - 1, y: game_state.4 - 1 });
+        let (min_x, max_x) = (std::cmp::max(std::cmp::min(x0, x1), 0), std::cmp::min(std::cmp::max(x0, x1), layer_width - 1));
+        let (min_y, max_y) = (std::cmp::max(std::cmp::min(y0, y1), 0), std::cmp::min(std::cmp::max(y0, y1), layer_height - 1));
+        let plane = (layer_width * layer_height) as usize;
+        let fills = shared.fills.iter().copied().chain(ego_fills.into_iter().filter_map(|(layer, val, scale)| layer.map(|l| (l, val, scale))));
+        for (layer, val, scale) in fills {
+            for x in min_x..=max_x {
+                let start = base + layer * plane + (x * layer_height + min_y) as usize;
+                let end = start + (max_y - min_y + 1) as usize;
+                match self.obs_spec.dtype {
+                    ObsDtype::U8 => self.obss[start..end].fill(val),
+                    ObsDtype::F32 => self.obss_f32[start..end].fill(val as f32 / scale),
+                }
+            }
         }
 
-        for x in 0..game_state.3 {
-            for y in 0..game_state.4 {
-                assign(Tile { x, y }, 5, 1);
-                // Signal how many players are alive
-                assign(Tile { x, y }, 10 + alive_count as usize, 1);
+        // Fog of war goes over everything written above
+        if let Some(fog) = self.fog[env_i] {
+            let plane = (layer_width * layer_height) as usize;
+            let num_layers = self.obs_spec.num_layers();
+            let mut clear = |i: usize| match self.obs_spec.dtype {
+                ObsDtype::U8 => self.obss[i] = 0,
+                ObsDtype::F32 => self.obss_f32[i] = 0.0,
+            };
+            if fog.hide_enemy_health {
+                for player in players.values().filter(|p| p.alive && p.id != player_id) {
+                    let (x, y) = canvas(player.body[0]);
+                    if x >= 0 && x < layer_width && y >= 0 && y < layer_height {
+                        clear(base + (x * layer_height + y) as usize);
+                    }
+                }
+            }
+            let (center_x, center_y) = canvas(head);
+            for x in 0..layer_width {
+                for y in 0..layer_height {
+                    if std::cmp::max((x - center_x).abs(), (y - center_y).abs()) as u32 > fog.radius {
+                        for l in 0..num_layers {
+                            clear(base + l * plane + (x * layer_height + y) as usize);
+                        }
+                    }
+                }
             }
         }
     }
 
-    pub fn reset(&mut self) {
-        self.obss.par_iter_mut().for_each(|x| *x = 0.0);
-        self.envs.par_iter_mut().enumerate().for_each(|(ii, gi)| {
-            let bwidth = 11;
-            let bheight = 11;
-            let food_spawn_chance = 0.15;
-            *gi = Some(GameInstance::new(bwidth, bheight, self.n_models, food_spawn_chance));
-            let ids = gi.as_ref().unwrap().get_player_ids();
-            let state = gi.as_ref().unwrap().get_state();
-            for m in 0..self.n_models {
-                self.write_obs(m, ii, ids[m], state, self.orientation(gi.as_ref().unwrap().get_game_id(), gi.as_ref().unwrap().get_turn(), ids[m], self.fixed_orientation));
-            }
-            self.info[ii] = Info {
-                health: 100,
-                length: PLAYER_STARTING_LENGTH,
-                turn: 0,
-                alive: true,
-                ate: false,
-                over: false,
-                alive_count: self.n_models,
-                death_reason: DeathReason::None,
-            };
+    pub fn reset(&mut self) -> PyResult<()> {
+        // An in-flight step_async is finished and thrown away
+        let _ = self.join_pending();
+        self.obss.par_iter_mut().for_each(|x| *x = 0);
+        self.obss_f32.par_iter_mut().for_each(|x| *x = 0.0);
+        self.final_obss.par_iter_mut().for_each(|x| *x = 0);
+        self.final_obss_f32.par_iter_mut().for_each(|x| *x = 0.0);
+        self.episode_ended.fill(false);
+        self.awaiting_reset.fill(false);
+        // The games leave the wrapper while they are reset in parallel, which only reads the rest
+        // of it. Their first observations and Info are written afterwards, in env order
+        let mut envs = std::mem::take(&mut self.envs);
+        let mut env_errors = std::mem::take(&mut self.env_errors);
+        self.scheduler.for_each_env(&mut envs, &mut env_errors, &|ii, gi| {
+            guarded(|| {
+                let p = self.params[ii];
+                let game = gi.get_or_insert_with(|| GameInstance::new(p.board_width, p.board_length, p.num_players, p.food_spawn_chance));
+                if let Some((seed, first_stream)) = self.seed {
+                    game.seed_streams(seed, first_stream + ii as u64);
+                }
+                self.reset_game(ii, game);
+                Ok(())
+            })
         });
+        self.envs = envs;
+        self.env_errors = env_errors;
+        for env_i in 0..self.n_envs {
+            self.sample_opponents(env_i);
+            if self.env_errors[env_i].is_some() {
+                continue;
+            }
+            if let Some(mut game) = self.envs[env_i].take() {
+                let begun = guarded(|| {
+                    self.begin_episode(env_i, &mut game);
+                    self.rebase_potentials(env_i, &game);
+                    Ok(())
+                });
+                self.envs[env_i] = Some(game);
+                self.env_errors[env_i] = begun.err();
+            }
+        }
+        self.shaping.fill([0.0; 3]);
+        self.episode_food.fill(0);
+        self.episode_returns.fill(0.0);
+        self.episode_records.fill(None);
+        self.raise_env_errors()
     }
 
-    pub fn step(&mut self) {
-        self.obss.par_iter_mut().for_each(|x| *x = 0.0);
-        self.envs.par_iter_mut().enumerate().for_each(|(ii, gi)| {
-            let bwidth = 11;
-            let bheight = 11;
-            let food_spawn_chance = 0.15;
-            let ids = gi.as_ref().unwrap().get_player_ids();
-            let state = gi.as_ref().unwrap().get_state();
-            for m in 0..self.n_models {
-                let action = self.get_action(m, ii, self.orientation(gi.as_ref().unwrap().get_game_id(), gi.as_ref().unwrap().get_turn(), ids[m], self.fixed_orientation), ids[m], state.clone());
-                gi.as_mut().unwrap().set_player_move(ids[m], action);
-            }
-            let player_id = ids[0];
-            let it = state.get(&player_id).unwrap();
-            gi.as_mut().unwrap().step();
-            let done = !it.alive || gi.as_ref().unwrap().is_over();
-            let count = ids.iter().filter(|&&id| state.get(&id).unwrap().alive).count();
-            self.info[ii] = Info {
-                health: it.health,
-                length: it.body.len(),
-                turn: gi.as_ref().unwrap().get_turn(),
-                alive: it.alive,
-                ate: it.health == 100 && gi.as_ref().unwrap().get_turn() > 0,
-                over: done,
-                alive_count: count,
-                death_reason: it.death_reason,
-            };
+    // Starts a new episode in one env and leaves the others running. A seed keys the env's
+    // streams anew, which makes this episode and every later one of the env repeatable. The
+    // unfinished episode isn't counted in the stats
+    #[pyo3(signature = (env_i, seed=None))]
+    pub fn reset_env(&mut self, env_i: usize, seed: Option<u64>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let p = self.params[env_i];
+        let mut game = self.envs[env_i].take().unwrap_or_else(|| GameInstance::new(p.board_width, p.board_length, p.num_players, p.food_spawn_chance));
+        if let Some(seed) = seed {
+            game.seed_streams(seed, self.seed.map_or(0, |(_, first_stream)| first_stream) + env_i as u64);
+        }
+        self.start_episode(env_i, &mut game);
+        self.restart_env(env_i, &game);
+        self.envs[env_i] = Some(game);
+        self.raise_env_errors()
+    }
+
+    // Puts a hand-made position into one env, as JSON text or a dict shaped like get_state's,
+    // see GameInstance::load_scenario. The env needs one snake per entry, set num_players with
+    // set_params and reset_env first. Hazards and walls given here only last for this episode
+    pub fn load_scenario(&mut self, py: Python<'_>, env_i: usize, scenario: &PyAny) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.env(env_i)?;
+        let text = match scenario.extract::<String>() {
+            Ok(text) => text,
+            Err(_) => py.import("json")?.call_method1("dumps", (scenario,))?.extract()?,
+        };
+        let v: Value = serde_json::from_str(&text).map_err(|e| PyValueError::new_err(format!("invalid scenario: {}", e)))?;
+        self.load_scenario_value(env_i, &v)
+    }
+
+    // Draws n starting positions, spawns and food, from the streams of seed with env 0's params
+    // and map, replacing the previous set. Unlike seeding the run, the positions don't depend
+    // on how many envs there are or what was played before, so any policy can be scored on the
+    // same set with reset_to_eval
+    pub fn make_eval_set(&mut self, n: usize, seed: u64) -> PyResult<()> {
+        self.check_not_pending()?;
+        let p = self.params[0];
+        self.eval_set = (0..n as u64)
+            .map(|i| {
+                let mut game = GameInstance::new(p.board_width, p.board_length, p.num_players, p.food_spawn_chance);
+                game.set_hazards(self.hazards[0].clone());
+                game.set_walls(self.walls[0].clone());
+                game.set_pools(self.pools[0].clone());
+                game.seed_streams(seed, i);
+                game.reset_next_episode(p);
+                game
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn eval_set_len(&self) -> PyResult<usize> {
+        self.check_not_pending()?;
+        Ok(self.eval_set.len())
+    }
+
+    // Starts env_i on position i of the eval set. Food spawns keep drawing from the position's
+    // streams, so the same moves replay the same episode. Later episodes of the env carry on
+    // from those streams too until reset_env is given a seed
+    #[pyo3(signature = (i, env_i=0))]
+    pub fn reset_to_eval(&mut self, i: usize, env_i: usize) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let game = match self.eval_set.get(i) {
+            Some(game) => game.clone(),
+            None => return Err(PyValueError::new_err(format!("eval position {} out of range for {} positions", i, self.eval_set.len()))),
+        };
+        // Eval positions stay out of the replay_interesting buffer
+        self.episode_starts[env_i] = None;
+        if self.recording {
+            self.replays[env_i] = Some(Replay::new(&game));
+        }
+        self.write_first_obs(env_i, &game);
+        let ids = game.get_player_ids();
+        for m in 0..self.n_models {
+            self.info[m * self.n_envs + env_i] = slot_info(&game, ids.get(m).copied(), false);
+        }
+        self.restart_env(env_i, &game);
+        self.envs[env_i] = Some(game);
+        self.raise_env_errors()
+    }
+
+    // Keeps the starts of up to capacity episodes the learner lost to starvation or a
+    // head-to-head, the oldest making way for new ones, and starts that fraction of new
+    // episodes over from one of them drawn at random. A capacity of 0 turns it off and empties
+    // the buffer. Episodes started with load_scenario or reset_to_eval aren't kept
+    #[pyo3(signature = (capacity, fraction=0.25))]
+    pub fn replay_interesting(&mut self, capacity: usize, fraction: f64) -> PyResult<()> {
+        self.check_not_pending()?;
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(PyValueError::new_err(format!("fraction must be between 0 and 1, got {}", fraction)));
+        }
+        self.interesting_capacity = capacity;
+        self.interesting_fraction = fraction;
+        while self.interesting.len() > capacity {
+            self.interesting.pop_front();
+        }
+        if capacity == 0 {
+            self.episode_starts.fill(None);
+            self.finished_starts.fill(None);
+        }
+        Ok(())
+    }
+
+    // How many episode starts replay_interesting holds
+    pub fn interesting_len(&self) -> PyResult<usize> {
+        self.check_not_pending()?;
+        Ok(self.interesting.len())
+    }
+
+    pub fn step(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_initialized()?;
+        self.act_opponents(py)?;
+        #[cfg(feature = "onnx")]
+        self.act_learner(py)?;
+        #[cfg(feature = "arrow")]
+        self.begin_rollout();
+        py.allow_threads(|| self.step_envs());
+        self.finish_step()
+    }
+
+    // Spectator mode: plays one turn of env_i with the board moves ('u', 'd', 'l' or 'r') of
+    // every alive snake keyed by player id, bypassing the action buffers and policies. Drives
+    // the env as a plain rules engine, e.g. to replay logged games into observation datasets.
+    // Observations, Info and replays update like step, and the other envs are left alone
+    pub fn step_with_moves(&mut self, env_i: usize, moves: HashMap<u32, char>) -> PyResult<()> {
+        self.check_not_pending()?;
+        let game = self.env(env_i)?;
+        if self.awaiting_reset[env_i] {
+            return Err(PyValueError::new_err(format!("env {} is over and waiting for a reset", env_i)));
+        }
+        let players = game.get_state().1;
+        for (&id, &m) in &moves {
+            if !players.get(&id).map_or(false, |p| p.alive) {
+                return Err(PyValueError::new_err(format!("player {} is not alive in env {}", id, env_i)));
+            }
+            if !['u', 'd', 'l', 'r'].contains(&m) {
+                return Err(PyValueError::new_err(format!("invalid move {} for player {}, expected u, d, l or r", m, id)));
+            }
+        }
+        let ids = game.get_player_ids();
+        if let Some(id) = ids.iter().find(|id| players[id].alive && !moves.contains_key(id)) {
+            return Err(PyValueError::new_err(format!("missing a move for player {}", id)));
+        }
+
+        let mut game = self.envs[env_i].take().unwrap();
+        for (&id, &m) in &moves {
+            game.set_player_move(id, m);
+        }
+        let frame = self.obs_spec.obs_size();
+        let frozen: Vec<bool> = (0..self.n_envs).map(|e| e != env_i).collect();
+        shift_frames(&mut self.obss, frame, self.n_stack, &frozen);
+        shift_frames(&mut self.obss_f32, frame, self.n_stack, &frozen);
+        game.step();
+        let players = game.get_state().1;
+        let learner_dead = self.end_on_learner_death && !players[&ids[0]].alive;
+        let done = learner_dead || game.is_over() || game.max_turn_reached();
+        for m in 0..self.n_models {
+            self.info[m * self.n_envs + env_i] = slot_info(&game, ids.get(m).copied(), done);
+        }
+        if self.reward_shaping {
+            let now = territory::potentials(&game);
+            for (m, id) in ids.iter().enumerate() {
+                let k = m * self.n_envs + env_i;
+                self.shaping[k] = match now.get(id) {
+                    Some(p) => [p[0] - self.potentials[k][0], p[1] - self.potentials[k][1], p[2] - self.potentials[k][2]],
+                    None => [0.0; 3],
+                };
+                self.potentials[k] = now.get(id).copied().unwrap_or([0.0; 3]);
+            }
+        }
+        let dirty = if self.incremental_obs && !done { Some(self.dirty_tiles(&game)) } else { None };
+        if let Some(replay) = self.replays[env_i].as_mut() {
+            replay.push(&game);
             if done {
-                *gi = Some(GameInstance::new(bwidth, bheight, self.n_models, food_spawn_chance));
+                self.finished_replays[env_i] = self.replays[env_i].take();
             }
-            let ids = gi.as_ref().unwrap().get_player_ids();
-            let state = gi.as_ref().unwrap().get_state();
-            for m in 0..self.n_models {
-                self.write_obs(m, ii, ids[m], state.clone(), self.orientation(gi.as_ref().unwrap().get_game_id(), gi.as_ref().unwrap().get_turn(), ids[m], self.fixed_orientation));
+        }
+        if done {
+            self.finished_starts[env_i] = self.episode_starts[env_i].take();
+        }
+        self.episode_ended[env_i] = done;
+        let shared = self.shared_obs(env_i, &game);
+        for (m, &id) in ids.iter().enumerate() {
+            self.write_obs(m, env_i, id, &game, self.orientation(&game, id), dirty.as_deref(), &shared);
+        }
+        self.keep_final_obs(env_i, done);
+        self.finish_env(env_i);
+        if done {
+            if self.auto_reset {
+                game.reset_next_episode(self.params[env_i]);
+                self.pick_episode_start(env_i, &mut game);
+                self.rebase_potentials(env_i, &game);
+                if self.recording {
+                    self.replays[env_i] = Some(Replay::new(&game));
+                }
+                self.write_first_obs(env_i, &game);
+            } else {
+                self.awaiting_reset[env_i] = true;
+            }
+        }
+        self.envs[env_i] = Some(game);
+        self.raise_env_errors()
+    }
+
+    // Starts simulating the next step on a background thread and returns right away, so policy
+    // work in Python overlaps with it. Until step_wait, get_obs and the other observation
+    // getters serve the observations from before this step, reset discards the step and
+    // everything else raises
+    pub fn step_async(&mut self, py: Python<'_>, actions: PyReadonlyArray2<u8>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.set_actions(actions)?;
+        self.start_step(py)
+    }
+
+    pub fn step_wait(&mut self, py: Python<'_>) -> PyResult<()> {
+        if self.pending.is_none() {
+            return Err(PyRuntimeError::new_err("step_wait called without step_async"));
+        }
+        py.allow_threads(|| self.join_pending()).map_err(|e| PyRuntimeError::new_err(format!("simulation thread {}", e)))?;
+        self.finish_step()
+    }
+
+    // Replaces the hazards of one env. They apply to the running game right away and to
+    // every episode after it
+    pub fn set_hazards(&mut self, env_i: usize, tiles: Vec<(u32, u32)>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let hazards = self.board_tiles(env_i, tiles, "hazard")?;
+        if let Some(gi) = self.envs[env_i].as_mut() {
+            gi.set_hazards(hazards.clone());
+        }
+        // The hazard layers aren't patched, so the next observations are written out in full
+        for m in 0..self.n_models {
+            self.obs_frames[m * self.n_envs + env_i] = None;
+        }
+        self.hazards[env_i] = hazards;
+        Ok(())
+    }
+
+    // Replaces the impassable wall tiles of one env. Moving into a wall eliminates a snake
+    // like leaving the board, and no snake spawns and no food lands on one. The running game
+    // takes them from its next step, and every episode after it starts clear of them
+    pub fn set_walls(&mut self, env_i: usize, tiles: Vec<(u32, u32)>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let walls = self.board_tiles(env_i, tiles, "wall")?;
+        if let Some(gi) = self.envs[env_i].as_mut() {
+            gi.set_walls(walls.clone());
+        }
+        // The wall layer isn't patched, so the next observations are written out in full
+        for m in 0..self.n_models {
+            self.obs_frames[m * self.n_envs + env_i] = None;
+        }
+        self.walls[env_i] = walls;
+        Ok(())
+    }
+
+    // Loads a maze from an ASCII map, one line per row from y = 0 down: '#' is a wall and
+    // '.' an open tile. The map must match the env's board size, blank lines and surrounding
+    // whitespace are ignored
+    pub fn set_map(&mut self, env_i: usize, map: &str) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let (width, height) = (self.params[env_i].board_width, self.params[env_i].board_length);
+        let rows: Vec<&str> = map.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+        if rows.len() != height as usize {
+            return Err(PyValueError::new_err(format!("map has {} rows, the board {}", rows.len(), height)));
+        }
+        let mut walls = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width as usize {
+                return Err(PyValueError::new_err(format!("map row {} has {} tiles, the board {}", y, row.chars().count(), width)));
+            }
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '#' => walls.push((x as u32, y as u32)),
+                    '.' => {}
+                    _ => return Err(PyValueError::new_err(format!("unknown map tile {:?} at ({}, {}), expected '#' or '.'", c, x, y))),
+                }
             }
+        }
+        self.set_walls(env_i, walls)
+    }
+
+    // Fog of war for one env: every cell more than radius tiles from the head, counting
+    // diagonal steps as one, reads as empty in every layer, and hide_enemy_health also blanks
+    // the health of the other snakes' heads. None lifts it. Takes effect from the next
+    // observation
+    #[pyo3(signature = (env_i, radius=None, hide_enemy_health=false))]
+    pub fn set_fog(&mut self, env_i: usize, radius: Option<u32>, hide_enemy_health: bool) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        self.fog[env_i] = radius.map(|radius| Fog { radius, hide_enemy_health });
+        // A frame written under another fog can't be shifted into this one
+        for m in 0..self.n_models {
+            self.obs_frames[m * self.n_envs + env_i] = None;
+        }
+        Ok(())
+    }
+
+    // Per env (radius, hide_enemy_health), None where there is no fog
+    pub fn get_fog(&self) -> PyResult<Vec<Option<(u32, bool)>>> {
+        self.check_not_pending()?;
+        Ok(self.fog.iter().map(|f| f.map(|f| (f.radius, f.hide_enemy_health))).collect())
+    }
+
+    // Replaces the healing pools of one env, a custom variant: a head on a pool tile restores
+    // heal health a turn instead of losing it to hazards, capped at 100. Pools on hazards offset
+    // the hazard damage. They apply to the running game from its next step
+    #[pyo3(signature = (env_i, tiles, heal=10))]
+    pub fn set_healing_pools(&mut self, env_i: usize, tiles: Vec<(u32, u32)>, heal: u32) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        if heal == 0 {
+            return Err(PyValueError::new_err("heal must be positive"));
+        }
+        let pools: HashMap<Tile, u32> = self.board_tiles(env_i, tiles, "pool")?.into_iter().map(|t| (t, heal)).collect();
+        if let Some(gi) = self.envs[env_i].as_mut() {
+            gi.set_pools(pools.clone());
+        }
+        // The healing layer isn't patched, so the next observations are written out in full
+        for m in 0..self.n_models {
+            self.obs_frames[m * self.n_envs + env_i] = None;
+        }
+        self.pools[env_i] = pools;
+        Ok(())
+    }
+
+    // Changes the game parameters of every env for curricula. Each env picks them up at its next
+    // reset, so in-flight episodes finish under the old ones. board_size is an int for square
+    // boards or a (width, height) tuple and must fit the observation canvas chosen at
+    // construction. Slots past num_players sit out with zero observations.
+    // squads is a dict with any of size, allow_body_collisions, shared_elimination and
+    // shared_health, unset keys keep their current values. legacy_growth brings back the old
+    // growth timing, where eating keeps the tail in place instead of stacking it for a turn.
+    // super_food_chance makes spawned food super food that grows the snake by
+    // super_food_growth segments, 0 for health without growth.
+    // hazard_damage is the official hazardDamagePerTurn, taken per hazard stack, and
+    // food_spawns_in_hazard lets food land on hazards. snail_mode turns on decaying hazard
+    // trails behind every tail, see Parameters.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (board_size=None, num_players=None, food_spawn_chance=None, minimum_food=None, super_food_chance=None, super_food_growth=None, hazard_damage=None, food_spawns_in_hazard=None, max_turns=None, squads=None, legacy_growth=None, snail_mode=None))]
+    pub fn set_params(
+        &mut self,
+        board_size: Option<&PyAny>,
+        num_players: Option<u32>,
+        food_spawn_chance: Option<f32>,
+        minimum_food: Option<u32>,
+        super_food_chance: Option<f32>,
+        super_food_growth: Option<u32>,
+        hazard_damage: Option<usize>,
+        food_spawns_in_hazard: Option<bool>,
+        max_turns: Option<u32>,
+        squads: Option<&PyDict>,
+        legacy_growth: Option<bool>,
+        snail_mode: Option<bool>,
+    ) -> PyResult<()> {
+        self.check_not_pending()?;
+        let mut params = Vec::with_capacity(self.n_envs);
+        for p in &self.params {
+            let p = override_params(*p, board_size, num_players, food_spawn_chance, minimum_food, super_food_chance, super_food_growth, hazard_damage, food_spawns_in_hazard, max_turns, squads, legacy_growth, snail_mode)?;
+            check_params(&p, &self.obs_spec, self.n_models, self.orientation_mode)?;
+            params.push(p);
+        }
+        self.params = params;
+        Ok(())
+    }
+
+    // Same as set_params for a single env, unset fields keep that env's current values
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (env_i, board_size=None, num_players=None, food_spawn_chance=None, minimum_food=None, super_food_chance=None, super_food_growth=None, hazard_damage=None, food_spawns_in_hazard=None, max_turns=None, squads=None, legacy_growth=None, snail_mode=None))]
+    pub fn set_env_config(
+        &mut self,
+        env_i: usize,
+        board_size: Option<&PyAny>,
+        num_players: Option<u32>,
+        food_spawn_chance: Option<f32>,
+        minimum_food: Option<u32>,
+        super_food_chance: Option<f32>,
+        super_food_growth: Option<u32>,
+        hazard_damage: Option<usize>,
+        food_spawns_in_hazard: Option<bool>,
+        max_turns: Option<u32>,
+        squads: Option<&PyDict>,
+        legacy_growth: Option<bool>,
+        snail_mode: Option<bool>,
+    ) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        let p = override_params(self.params[env_i], board_size, num_players, food_spawn_chance, minimum_food, super_food_chance, super_food_growth, hazard_damage, food_spawns_in_hazard, max_turns, squads, legacy_growth, snail_mode)?;
+        check_params(&p, &self.obs_spec, self.n_models, self.orientation_mode)?;
+        self.params[env_i] = p;
+        Ok(())
+    }
+
+    // Pickling, so checkpointed vectorized envs resume mid-episode: the configuration, every
+    // game, the observation buffers and the episode bookkeeping. Pooled opponents, the opponent
+    // callback, the ONNX learner and slot policies can't be pickled, pooled ones have to be
+    // added again in the same order after loading and pick their ratings back up by name.
+    // Seeded games carry their stream keys and episode counts, so a restored seeded run draws
+    // the same food and spawns the original would have. Unseeded games keep drawing from the
+    // thread RNG. Recorded replays are left out and recording is off after loading
+    pub fn __getstate__<'py>(&self, py: Python<'py>) -> PyResult<(String, &'py PyBytes)> {
+        self.check_not_pending()?;
+        let state = json!({
+            "n_envs": self.n_envs,
+            "n_models": self.n_models,
+            "orientation_mode": self.orientation_mode.name(),
+            "obs_spec": self.obs_spec.to_json(),
+            "n_stack": self.n_stack,
+            "reward_shaping": self.reward_shaping,
+            "end_on_learner_death": self.end_on_learner_death,
+            "single_threaded": self.single_threaded,
+            "seed": self.seed.map(|(seed, first_stream)| json!([seed, first_stream])),
+            "incremental_obs": self.incremental_obs,
+            "auto_reset": self.auto_reset,
+            "awaiting_reset": self.awaiting_reset,
+            "params": self.params.iter().map(Parameters::to_json).collect::<Vec<_>>(),
+            "hazards": self.hazards.iter().map(|h| h.iter().map(|t| json!([t.x, t.y])).collect::<Vec<_>>()).collect::<Vec<_>>(),
+            "walls": self.walls.iter().map(|w| w.iter().map(|t| json!([t.x, t.y])).collect::<Vec<_>>()).collect::<Vec<_>>(),
+            "pools": self.pools.iter().map(|p| p.iter().map(|(t, heal)| json!([t.x, t.y, heal])).collect::<Vec<_>>()).collect::<Vec<_>>(),
+            "fog": self.fog.iter().map(|f| f.map(|f| json!([f.radius, f.hide_enemy_health]))).collect::<Vec<_>>(),
+            "envs": self.envs.iter().map(|gi| gi.as_ref().map(GameInstance::snapshot)).collect::<Vec<_>>(),
+            "acts": self.acts,
+            "info": self.info.iter().map(Info::to_json).collect::<Vec<_>>(),
+            "episode_food": self.episode_food,
+            "episode_returns": self.episode_returns,
+            "stats": self.stats.to_json(),
+            "opponents": self.opponents,
+            "potentials": self.potentials,
+            "shaping": self.shaping,
+            "ratings": self.opponent_pool.ratings().to_json(),
+            "eval_set": self.eval_set.iter().map(GameInstance::snapshot).collect::<Vec<_>>(),
+            "interesting_capacity": self.interesting_capacity,
+            "interesting_fraction": self.interesting_fraction,
+            "interesting": self.interesting.iter().map(GameInstance::snapshot).collect::<Vec<_>>(),
         });
+        let obs = match self.obs_spec.dtype {
+            ObsDtype::U8 => PyBytes::new(py, &self.obss),
+            ObsDtype::F32 => PyBytes::new(py, &self.obss_f32.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<_>>()),
+        };
+        Ok((state.to_string(), obs))
+    }
+
+    pub fn __setstate__(&mut self, state: (String, &PyBytes)) -> PyResult<()> {
+        let _ = self.join_pending();
+        let (state, obs) = state;
+        serde_json::from_str(&state)
+            .map_err(|e| e.to_string())
+            .and_then(|v| self.restore(&v, obs.as_bytes()))
+            .map_err(|e| PyValueError::new_err(format!("invalid GameWrapper state: {}", e)))
+    }
+
+    // Enough for pickle to create the object __setstate__ then fills in
+    pub fn __getnewargs__(&self) -> (usize, usize) {
+        (self.n_envs, self.n_models)
+    }
+
+    // Deep copy of a live env for search, with its observation stacks and info
+    pub fn clone_env(&self, env_i: usize) -> PyResult<GameHandle> {
+        self.check_not_pending()?;
+        self.env(env_i)?;
+        Ok(GameHandle::new(self.branch(env_i)))
+    }
+
+    // Steps a copy of the handle's game with one action per model slot, read like set_actions.
+    // The handle itself is left as it was and finished games are not reset
+    pub fn simulate(&self, handle: &GameHandle, moves: Vec<u8>) -> PyResult<GameHandle> {
+        self.check_not_pending()?;
+        let mut game = handle.game().branch(0);
+        game.simulate_step(&moves)?;
+        Ok(GameHandle::new(game))
+    }
+
+    // Parameters each env uses from its next reset on, as dicts with set_params' keys
+    pub fn get_env_configs<'py>(&self, py: Python<'py>) -> PyResult<Vec<&'py PyDict>> {
+        self.check_not_pending()?;
+        self.params
+            .iter()
+            .map(|p| {
+                let d = PyDict::new(py);
+                d.set_item("board_size", (p.board_width, p.board_length))?;
+                d.set_item("num_players", p.num_players)?;
+                d.set_item("food_spawn_chance", p.food_spawn_chance)?;
+                d.set_item("minimum_food", p.minimum_food)?;
+                d.set_item("super_food_chance", p.super_food_chance)?;
+                d.set_item("super_food_growth", p.super_food_growth)?;
+                d.set_item("hazard_damage", p.hazard_damage)?;
+                d.set_item("food_spawns_in_hazard", p.food_spawns_in_hazard)?;
+                d.set_item("max_turns", p.max_turns)?;
+                let squads = PyDict::new(py);
+                squads.set_item("size", p.squads.size)?;
+                squads.set_item("allow_body_collisions", p.squads.allow_body_collisions)?;
+                squads.set_item("shared_elimination", p.squads.shared_elimination)?;
+                squads.set_item("shared_health", p.squads.shared_health)?;
+                d.set_item("squads", squads)?;
+                d.set_item("legacy_growth", p.legacy_growth)?;
+                d.set_item("snail_mode", p.snail_mode)?;
+                Ok(d)
+            })
+            .collect()
+    }
+
+    // Registers a frozen policy for the non-learning slots. It is called once per step with the
+    // stacked observations of every slot it controls and must return one action per row.
+    // Envs pick up pool changes at their next episode.
+    #[pyo3(signature = (policy, weight=1.0, name=None))]
+    pub fn add_opponent(&mut self, policy: PyObject, weight: f64, name: Option<String>) -> PyResult<usize> {
+        self.check_not_pending()?;
+        if !(weight >= 0.0) {
+            return Err(PyValueError::new_err(format!("weight must be non-negative, got {}", weight)));
+        }
+        let name = name.unwrap_or_else(|| format!("opponent_{}", self.opponent_pool.entries().len()));
+        Ok(self.opponent_pool.add(name, PoolPolicy::Callback(policy), weight))
+    }
+
+    // Loads an exported ONNX snapshot as a pooled opponent whose inference stays in Rust.
+    // deterministic picks the argmax action, otherwise actions are sampled from the logits
+    #[cfg(feature = "onnx")]
+    #[pyo3(signature = (path, deterministic=true, weight=1.0, name=None))]
+    pub fn add_onnx_opponent(&mut self, path: &str, deterministic: bool, weight: f64, name: Option<String>) -> PyResult<usize> {
+        self.check_not_pending()?;
+        if !(weight >= 0.0) {
+            return Err(PyValueError::new_err(format!("weight must be non-negative, got {}", weight)));
+        }
+        let policy = OnnxPolicy::load(path, deterministic).map_err(|e| PyValueError::new_err(format!("failed to load {}: {}", path, e)))?;
+        let name = name.unwrap_or_else(|| path.to_string());
+        Ok(self.opponent_pool.add(name, PoolPolicy::Onnx(policy), weight))
+    }
+
+    // Lets an exported ONNX policy act for model 0 in every env, so rollouts run without Python
+    // picking the learner's actions. None hands model 0 back to the action buffer
+    #[cfg(feature = "onnx")]
+    #[pyo3(signature = (path, deterministic=false))]
+    pub fn set_onnx_learner(&mut self, path: Option<&str>, deterministic: bool) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.learner = match path {
+            Some(path) => Some(OnnxPolicy::load(path, deterministic).map_err(|e| PyValueError::new_err(format!("failed to load {}: {}", path, e)))?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    // Replaces the ONNX learner's model with a newer export of it given as the file's bytes,
+    // e.g. pushed by the training process to its rollout workers. Takes effect from the next
+    // step and the envs carry on where they are
+    #[cfg(feature = "onnx")]
+    pub fn update_learner_weights(&mut self, weights: &PyBytes) -> PyResult<()> {
+        self.check_not_pending()?;
+        let learner = self.learner.as_mut().ok_or_else(|| PyValueError::new_err("there is no ONNX learner, call set_onnx_learner first"))?;
+        learner.update_weights(weights.as_bytes()).map_err(|e| PyValueError::new_err(format!("failed to load the learner weights: {}", e)))
+    }
+
+    pub fn set_opponent_weight(&mut self, index: usize, weight: f64) -> PyResult<()> {
+        self.check_not_pending()?;
+        if !(weight >= 0.0) {
+            return Err(PyValueError::new_err(format!("weight must be non-negative, got {}", weight)));
+        }
+        if !self.opponent_pool.set_weight(index, weight) {
+            return Err(PyValueError::new_err(format!("no opponent at index {}", index)));
+        }
+        Ok(())
+    }
+
+    // One policy for all opponents, e.g. older checkpoints in PyTorch. Called once per step with
+    // the stacked observations of every living snake in slots 1.. that no pooled opponent
+    // drives, env by env and then by slot, and must return one action per row. None removes it
+    pub fn set_opponent_callback(&mut self, callback: Option<PyObject>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.opponent_callback = callback;
+        Ok(())
     }
-}
 
+    // Called after every step with a dict of that step's aggregates over model 0's snakes: step,
+    // n_envs, episodes_finished, wins, draws, death_reasons by name, mean_turn over the envs,
+    // mean_episode_turns over the finished episodes, env_resets and the step_secs, sim_secs,
+    // obs_write_secs and reset_secs timings. Cheaper than get_info for logging to TensorBoard
+    // or W&B every step. None removes it
+    pub fn set_metrics_callback(&mut self, callback: Option<PyObject>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.metrics_callback = callback;
+        Ok(())
+    }
+
+    // Serves steps and episodes per second, env resets and per-phase timings on
+    // http://addr/metrics for Prometheus to scrape, e.g. from a remote env server. Returns the
+    // bound address, so "0.0.0.0:0" picks a free port
+    #[cfg(feature = "prometheus")]
+    pub fn serve_metrics(&mut self, addr: &str) -> PyResult<String> {
+        self.check_not_pending()?;
+        let sink = PrometheusSink::serve(addr).map_err(|e| PyIOError::new_err(format!("failed to serve metrics on {}: {}", addr, e)))?;
+        let bound = sink.local_addr().to_string();
+        self.add_metrics_sink(Box::new(sink));
+        Ok(bound)
+    }
+
+    // Puts a model slot of every env under one of the scripted baselines: random, greedy_food
+    // or flood_fill. They pick moves from the game state and ignore the action buffer and the
+    // opponents. None hands the slot back
+    #[pyo3(signature = (model_i, name=None))]
+    pub fn set_scripted_policy(&mut self, model_i: usize, name: Option<&str>) -> PyResult<()> {
+        self.check_not_pending()?;
+        let policy = match name {
+            Some(name) => Some(policy::scripted(name).ok_or_else(|| PyValueError::new_err(format!("unknown scripted policy {}, expected one of {:?}", name, policy::SCRIPTED)))?),
+            None => None,
+        };
+        self.set_policy(model_i, policy)
+    }
+
+    // Plays n_episodes whole episodes on a copy of env 0's settings and returns their summary,
+    // leaving this wrapper's envs, stats and opponents alone. policy drives slot 0 and each of
+    // opponents one of the other slots, as the name of a scripted baseline or a callback given
+    // the slot's (1, ...) observations that returns one action. Opponents default to
+    // flood_fill. With seeds, one per episode, the same arguments replay the same games.
+    // Episodes still running after max_turns are cut off and counted as truncated
+    #[pyo3(signature = (n_episodes, policy, opponents=None, seeds=None, max_turns=1000))]
+    pub fn evaluate<'py>(&self, py: Python<'py>, n_episodes: usize, policy: &PyAny, opponents: Option<Vec<&PyAny>>, seeds: Option<Vec<u64>>, max_turns: u32) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        self.env(0)?;
+        if n_episodes == 0 {
+            return Err(PyValueError::new_err("n_episodes must be at least 1"));
+        }
+        if max_turns == 0 {
+            return Err(PyValueError::new_err("max_turns must be at least 1"));
+        }
+        if let Some(seeds) = &seeds {
+            if seeds.len() != n_episodes {
+                return Err(PyValueError::new_err(format!("expected {} seeds, got {}", n_episodes, seeds.len())));
+            }
+        }
+        let opponents = match opponents {
+            Some(opponents) if opponents.len() + 1 != self.n_models => {
+                return Err(PyValueError::new_err(format!("expected {} opponents, got {}", self.n_models - 1, opponents.len())));
+            }
+            Some(opponents) => opponents,
+            None => vec!["flood_fill".into_py(py).into_ref(py); self.n_models - 1],
+        };
+        let mut eval = self.branch(0);
+        eval.set_end_on_learner_death(self.end_on_learner_death);
+        let mut callbacks = vec![None; self.n_models];
+        for (m, slot) in std::iter::once(policy).chain(opponents).enumerate() {
+            match slot.extract::<&str>() {
+                Ok(name) => eval.set_scripted_policy(m, Some(name))?,
+                Err(_) if slot.is_callable() => callbacks[m] = Some(slot),
+                Err(_) => return Err(PyValueError::new_err(format!("slot {} needs a scripted policy name or a callable", m))),
+            }
+        }
+
+        let (mut wins, mut draws, mut truncated, mut length, mut turns) = (0, 0, 0, 0u64, 0u64);
+        let mut deaths = [0usize; DeathReason::ALL.len()];
+        for episode in 0..n_episodes {
+            eval.reset_env(0, seeds.as_ref().map(|seeds| seeds[episode]))?;
+            loop {
+                for (m, callback) in callbacks.iter().enumerate() {
+                    let callback = match callback {
+                        Some(callback) => callback,
+                        None => continue,
+                    };
+                    let actions = callback.call1((eval.get_obs(py, m)?,))?.extract::<Vec<i64>>()?;
+                    if actions.len() != 1 || !(0..4).contains(&actions[0]) {
+                        return Err(PyValueError::new_err(format!("the policy of slot {} must return 1 action in 0..4", m)));
+                    }
+                    eval.set_model_actions(m, &[actions[0] as u8]);
+                }
+                eval.step(py)?;
+                if eval.info(0, 0).over {
+                    break;
+                }
+                if eval.info(0, 0).turn >= max_turns {
+                    truncated += 1;
+                    break;
+                }
+            }
+            let info = eval.info(0, 0);
+            wins += info.won as usize;
+            draws += info.draw as usize;
+            length += u64::from(info.length);
+            turns += u64::from(info.turn);
+            deaths[info.death_reason as usize] += 1;
+        }
+
+        let n = n_episodes as f64;
+        let reasons = PyDict::new(py);
+        for (reason, &count) in DeathReason::ALL.iter().zip(&deaths) {
+            reasons.set_item(reason.name(), count)?;
+        }
+        let d = PyDict::new(py);
+        d.set_item("n_episodes", n_episodes)?;
+        d.set_item("win_rate", wins as f64 / n)?;
+        d.set_item("draw_rate", draws as f64 / n)?;
+        d.set_item("truncated", truncated)?;
+        d.set_item("mean_length", length as f64 / n)?;
+        d.set_item("mean_turns", turns as f64 / n)?;
+        d.set_item("death_reasons", reasons)?;
+        Ok(d)
+    }
+
+    // Renders an official move request the way serve_policy does and compares it with slot
+    // model_i's observation of env_i, a position believed to be the same. Returns the differing
+    // cells as {"layer", "x", "y", "request", "env"} in canvas coordinates, empty when the two
+    // paths agree. Both are rendered unturned, so random flips don't count, while fog, walls and
+    // healing pools the request can't carry do. request is JSON text or a dict
+    #[pyo3(signature = (request, env_i, model_i=0))]
+    pub fn check_obs_parity<'py>(&self, py: Python<'py>, request: &PyAny, env_i: usize, model_i: usize) -> PyResult<Vec<&'py PyDict>> {
+        self.check_not_pending()?;
+        let text = match request.extract::<String>() {
+            Ok(text) => text,
+            Err(_) => py.import("json")?.call_method1("dumps", (request,))?.extract()?,
+        };
+        let request: Value = serde_json::from_str(&text).map_err(|e| PyValueError::new_err(format!("invalid move request: {}", e)))?;
+        self.obs_parity(&request, env_i, model_i)?
+            .into_iter()
+            .map(|(layer, x, y, from_request, from_env)| {
+                let d = PyDict::new(py);
+                d.set_item("layer", layer)?;
+                d.set_item("x", x)?;
+                d.set_item("y", y)?;
+                d.set_item("request", from_request)?;
+                d.set_item("env", from_env)?;
+                Ok(d)
+            })
+            .collect()
+    }
+
+    // Deploys an exported policy as a snake server on addr, answering the official Battlesnake
+    // API (/, /start, /move and /end) until interrupted. Every move request is loaded into a
+    // copy of env 0's settings with the asking snake in slot 0, so the policy sees the same
+    // observations it was trained on
+    #[cfg(feature = "onnx")]
+    #[pyo3(signature = (onnx_path, addr="0.0.0.0:8000", deterministic=true))]
+    pub fn serve_policy(&self, py: Python<'_>, onnx_path: &str, addr: &str, deterministic: bool) -> PyResult<()> {
+        self.check_not_pending()?;
+        let policy = OnnxPolicy::load(onnx_path, deterministic).map_err(|e| PyValueError::new_err(format!("failed to load {}: {}", onnx_path, e)))?;
+        let listener = std::net::TcpListener::bind(addr).map_err(|e| PyIOError::new_err(format!("failed to listen on {}: {}", addr, e)))?;
+        listener.set_nonblocking(true).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        loop {
+            // Ctrl-C reaches Python between requests
+            py.check_signals()?;
+            match listener.accept() {
+                // A client that hangs up early only costs its own answer
+                Ok((stream, _)) => {
+                    let _ = policyserver::answer(stream, self, &policy);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => py.allow_threads(|| std::thread::sleep(Duration::from_millis(2))),
+                Err(e) => return Err(PyIOError::new_err(format!("failed to accept on {}: {}", addr, e))),
+            }
+        }
+    }
+
+    // Adds a frozen copy of the learner to the league, a pooled opponent like add_opponent whose
+    // matchup statistics start from scratch
+    #[pyo3(signature = (policy, weight=1.0, name=None))]
+    pub fn snapshot_learner(&mut self, policy: PyObject, weight: f64, name: Option<String>) -> PyResult<usize> {
+        self.check_not_pending()?;
+        let name = name.unwrap_or_else(|| format!("learner_{}", self.opponent_pool.entries().len()));
+        self.add_opponent(policy, weight, Some(name))
+    }
+
+    // How pooled opponents are drawn for each episode: "weight" by weight alone, "pfsp_hard"
+    // by weight * (1 - p)^power and "pfsp_variance" by weight * p * (1 - p), where p is the
+    // learner's win rate against the opponent so far
+    #[pyo3(signature = (mode, power=2.0))]
+    pub fn set_pool_sampling(&mut self, mode: &str, power: f64) -> PyResult<()> {
+        self.check_not_pending()?;
+        let sampling = match mode {
+            "weight" => Sampling::Weight,
+            "pfsp_hard" if power >= 0.0 => Sampling::PfspHard(power),
+            "pfsp_hard" => return Err(PyValueError::new_err(format!("power must be non-negative, got {}", power))),
+            "pfsp_variance" => Sampling::PfspVariance,
+            _ => return Err(PyValueError::new_err(format!("unknown sampling mode {}, expected weight, pfsp_hard or pfsp_variance", mode))),
+        };
+        self.opponent_pool.set_sampling(sampling);
+        Ok(())
+    }
+
+    // The learner's record against every pooled opponent, in pool order: name, weight, games,
+    // wins, draws, losses, win_rate, the current sampling priority and the opponent's Elo rating
+    pub fn opponent_stats<'py>(&self, py: Python<'py>) -> PyResult<Vec<&'py PyDict>> {
+        self.check_not_pending()?;
+        let sampling = self.opponent_pool.sampling();
+        let ratings = self.opponent_pool.ratings();
+        self.opponent_pool
+            .entries()
+            .iter()
+            .map(|entry| {
+                let m = entry.matchup;
+                let d = PyDict::new(py);
+                d.set_item("name", &entry.name)?;
+                d.set_item("weight", entry.weight)?;
+                d.set_item("games", m.games)?;
+                d.set_item("wins", m.wins)?;
+                d.set_item("draws", m.draws)?;
+                d.set_item("losses", m.games - m.wins - m.draws)?;
+                d.set_item("win_rate", m.win_rate())?;
+                d.set_item("priority", entry.priority(sampling))?;
+                d.set_item("rating", ratings.opponent(&entry.name).rating)?;
+                Ok(d)
+            })
+            .collect()
+    }
+
+    // The learner's Elo rating and rated games, updated with the opponents' after every
+    // finished episode against the pool
+    pub fn learner_rating(&self) -> PyResult<(f64, u32)> {
+        self.check_not_pending()?;
+        let learner = self.opponent_pool.ratings().learner();
+        Ok((learner.rating, learner.games))
+    }
+
+    // The Elo K factor, how far one game can move a rating. 32 by default
+    pub fn set_rating_k(&mut self, k: f64) -> PyResult<()> {
+        self.check_not_pending()?;
+        if !(k > 0.0) {
+            return Err(PyValueError::new_err(format!("k must be positive, got {}", k)));
+        }
+        self.opponent_pool.ratings_mut().set_k(k);
+        Ok(())
+    }
+
+    // Writes the learner's and every opponent's rating to path as JSON, opponents keyed by name
+    pub fn save_ratings(&self, path: &str) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.opponent_pool.ratings().save(Path::new(path)).map_err(|e| PyIOError::new_err(format!("failed to write {}: {}", path, e)))
+    }
+
+    // Replaces the ratings with those written by save_ratings. Opponents pick theirs up by name,
+    // so they can be added before or after
+    pub fn load_ratings(&mut self, path: &str) -> PyResult<()> {
+        self.check_not_pending()?;
+        let ratings = Ratings::load(Path::new(path)).map_err(|e| PyIOError::new_err(format!("failed to read {}: {}", path, e)))?;
+        *self.opponent_pool.ratings_mut() = ratings;
+        Ok(())
+    }
+
+    // Hands every slot back to the action buffer
+    pub fn clear_opponents(&mut self) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.opponent_pool.clear();
+        for slots in self.opponents.iter_mut() {
+            slots.fill(None);
+        }
+        Ok(())
+    }
+
+    // Aggregate of the learner's episodes finished since the last call, then starts over
+    pub fn pop_stats<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        let stats = std::mem::take(&mut self.stats);
+        stats.to_dict(py)
+    }
+
+    // Game state of one env as a dict shaped like the official API's game state
+    pub fn get_state(&self, py: Python<'_>, env_i: usize) -> PyResult<PyObject> {
+        self.check_not_pending()?;
+        let state = self.env(env_i)?.to_json().to_string();
+        Ok(py.import("json")?.call_method1("loads", (state,))?.into())
+    }
+
+    // What changed in one env since the last call, for live viewers that keep their own board:
+    // {"game_id", "turn", "full", "snakes", "food_added", "food_removed", "hazards_added",
+    // "hazards_removed", "deaths"}. Each entry of snakes is {"id", "heads", "tail_removed",
+    // "health"}, its body now being heads followed by the old body minus tail_removed tiles,
+    // and only snakes that changed are listed. full is set on the first call and after every
+    // new episode, when everything is listed as added
+    pub fn get_state_delta(&mut self, py: Python<'_>, env_i: usize) -> PyResult<PyObject> {
+        self.check_not_pending()?;
+        let game = self.env(env_i)?;
+        let (game_id, frame) = (game.get_game_id(), Frame::of(game));
+        let seen = self.delta_frames[env_i].as_ref().map(|(id, frame)| (*id, frame));
+        let delta = StateDelta::between(seen, game_id, &frame).to_json().to_string();
+        self.delta_frames[env_i] = Some((game_id, frame));
+        Ok(py.import("json")?.call_method1("loads", (delta,))?.into())
+    }
+
+    // Shows env_i live in the terminal from a thread of its own, updated after every step while
+    // training goes on. n and p or the arrow keys cycle envs, space pauses the view and q or Esc
+    // quits it. The terminal is taken over until then or stop_watching
+    #[cfg(feature = "tui")]
+    pub fn watch(&mut self, env_i: usize) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_env(env_i)?;
+        self.watcher = None;
+        let watcher = Watcher::start(env_i, self.n_envs).map_err(|e| PyIOError::new_err(format!("failed to start the viewer: {}", e)))?;
+        self.watcher = Some(watcher);
+        self.publish_watched();
+        Ok(())
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn stop_watching(&mut self) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.watcher = None;
+        Ok(())
+    }
+
+    // Records every turn of every env from now on, see get_replay and render_to_gif. Episodes
+    // already running are recorded from their current turn. Turning it off drops the episodes
+    // being recorded and keeps the finished ones
+    pub fn record_replays(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.recording = enabled;
+        for (replay, gi) in self.replays.iter_mut().zip(&self.envs) {
+            *replay = if enabled { gi.as_ref().map(Replay::new) } else { None };
+        }
+        Ok(())
+    }
+
+    // Logs every step from now on as an Arrow record batch, see take_rollouts. Turning it off
+    // drops the batches not taken yet
+    #[cfg(feature = "arrow")]
+    pub fn record_rollouts(&mut self, enabled: bool) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.rollouts = if enabled { Some(RolloutLog::default()) } else { None };
+        Ok(())
+    }
+
+    // The steps logged since the last call as a list of pyarrow.RecordBatch, handed over
+    // without a copy. One row per slot that acted: model, env, obs (the stacked observation
+    // acted on, flattened), action, reward (1 won, -1 eliminated, else 0), done, and the
+    // slot's Info after the step. pyarrow.Table.from_batches and pyarrow.parquet write them
+    // out for offline RL
+    #[cfg(feature = "arrow")]
+    pub fn take_rollouts(&mut self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        self.check_not_pending()?;
+        let log = self.rollouts.as_mut().ok_or_else(|| PyValueError::new_err("rollouts are not recorded, call record_rollouts(True) first"))?;
+        log.take().iter().map(|batch| batch.to_pyarrow(py)).collect()
+    }
+
+    // Last finished recorded episode of an env as JSON, for replay_to_gif or archiving
+    pub fn get_replay(&self, env_i: usize) -> PyResult<String> {
+        self.check_not_pending()?;
+        Ok(self.finished_replay(env_i)?.to_json().to_string())
+    }
+
+    // Last finished recorded episode of an env in the frame format of the official board viewer
+    pub fn export_board_replay(&self, env_i: usize) -> PyResult<String> {
+        self.check_not_pending()?;
+        Ok(self.finished_replay(env_i)?.to_board_json().to_string())
+    }
+
+    // Renders the last finished recorded episode of an env to a GIF, one frame per turn
+    #[pyo3(signature = (env_i, path, cell_size=16, frame_ms=200))]
+    pub fn render_to_gif(&self, py: Python<'_>, env_i: usize, path: &str, cell_size: u32, frame_ms: u32) -> PyResult<()> {
+        self.check_not_pending()?;
+        let replay = self.finished_replay(env_i)?;
+        py.allow_threads(|| replay.write_gif(path, cell_size, frame_ms)).map_err(PyIOError::new_err)
+    }
+
+    // Snakes of one env as player_id -> {"body", "health", "alive"} in model slot order, where
+    // body is an (N, 2) uint32 array of (x, y) from head to tail. Eliminated snakes keep the
+    // body they died with
+    pub fn get_bodies<'py>(&self, py: Python<'py>, env_i: usize) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        let game = self.env(env_i)?;
+        let players = game.get_state().1;
+        let bodies = PyDict::new(py);
+        for id in game.get_player_ids() {
+            let player = &players[&id];
+            let coords: Vec<u32> = player.body.iter().flat_map(|t| [t.x, t.y]).collect();
+            let snake = PyDict::new(py);
+            snake.set_item("body", PyArray1::from_vec(py, coords).reshape([player.body.len(), 2])?)?;
+            snake.set_item("health", player.health)?;
+            snake.set_item("alive", player.alive)?;
+            bodies.set_item(id, snake)?;
+        }
+        Ok(bodies)
+    }
+
+    // Advances as many turns as fit into the wall-clock budget and returns how many were taken.
+    // Every slot has to be driven internally: model 0 by a scripted policy or the ONNX learner,
+    // the others by a scripted policy, the opponent pool or the opponent callback. A slot left
+    // to the action buffer would replay its last actions every turn, so that's an error
+    pub fn step_for(&mut self, py: Python<'_>, duration_ms: u64) -> PyResult<usize> {
+        self.check_not_pending()?;
+        #[cfg(feature = "onnx")]
+        let learner_driven = self.policies[0].is_some() || self.learner.is_some();
+        #[cfg(not(feature = "onnx"))]
+        let learner_driven = self.policies[0].is_some();
+        if !learner_driven {
+            return Err(PyValueError::new_err("model 0 reads the action buffer, give it a scripted policy or an ONNX learner first"));
+        }
+        let pooled = self.opponent_pool.entries().iter().any(|e| e.weight > 0.0) || self.opponent_callback.is_some();
+        if let Some(m) = (1..self.n_models).find(|&m| self.policies[m].is_none() && !pooled) {
+            return Err(PyValueError::new_err(format!("model {} reads the action buffer, give it a scripted policy, pooled opponents or an opponent callback first", m)));
+        }
+        let budget = Duration::from_millis(duration_ms);
+        let start = Instant::now();
+        let mut turns = 0;
+        let mut last_turn = Duration::ZERO;
+        // Stop early rather than start a turn that would blow the budget, the first one included
+        while start.elapsed() + last_turn < budget {
+            let turn_start = Instant::now();
+            self.step(py)?;
+            last_turn = turn_start.elapsed();
+            turns += 1;
+        }
+        Ok(turns)
+    }
+
+    // Actions for the next step as a (n_models, n_envs) uint8 array of u/d/l/r indices. Slots
+    // driven by the opponent pool are overwritten when step runs
+    pub fn set_actions(&mut self, actions: PyReadonlyArray2<u8>) -> PyResult<()> {
+        self.check_not_pending()?;
+        let actions = actions.as_array();
+        if actions.shape() != [self.n_models, self.n_envs] {
+            return Err(PyValueError::new_err(format!(
+                "expected actions of shape ({}, {}), got {:?}",
+                self.n_models,
+                self.n_envs,
+                actions.shape()
+            )));
+        }
+        if let Some(((m, e), &a)) = actions.indexed_iter().find(|&(_, &a)| a >= 4) {
+            return Err(PyValueError::new_err(format!("action {} for model {} in env {} is not in 0..4", a, m, e)));
+        }
+        for (act, &a) in self.acts.iter_mut().zip(actions.iter()) {
+            *act = a;
+        }
+        Ok(())
+    }
+
+    // Shape is (n_envs, n_stack * num_layers, layer_width, layer_height), uint8 or float32 per the dtype
+    pub fn get_obs(&self, py: Python<'_>, model_i: usize) -> PyResult<PyObject> {
+        let range = self.obs_range(model_i)?;
+        let shape = [self.n_envs, self.n_stack * self.obs_spec.num_layers(), self.obs_spec.layer_width, self.obs_spec.layer_height];
+        let (obss, obss_f32) = if self.pending.is_some() { (&self.front_obss, &self.front_obss_f32) } else { (&self.obss, &self.obss_f32) };
+        Ok(match self.obs_spec.dtype {
+            ObsDtype::U8 => PyArray1::from_slice(py, &obss[range]).reshape(shape)?.to_object(py),
+            ObsDtype::F32 => PyArray1::from_slice(py, &obss_f32[range]).reshape(shape)?.to_object(py),
+        })
+    }
+
+    // get_obs as one compressed buffer for sending batches elsewhere, see decompress_obs.
+    // codec is lz4 or zstd, level only applies to zstd. float32 observations are compressed
+    // as their little endian bytes
+    #[cfg(feature = "compression")]
+    #[pyo3(signature = (model_i, codec="lz4", level=3))]
+    pub fn get_obs_compressed<'py>(&self, py: Python<'py>, model_i: usize, codec: &str, level: i32) -> PyResult<&'py PyBytes> {
+        let codec = compression::parse_codec(codec, level)?;
+        let range = self.obs_range(model_i)?;
+        let (obss, obss_f32) = if self.pending.is_some() { (&self.front_obss, &self.front_obss_f32) } else { (&self.obss, &self.obss_f32) };
+        let packed = py.allow_threads(|| match self.obs_spec.dtype {
+            ObsDtype::U8 => compression::compress(codec, &obss[range]),
+            ObsDtype::F32 => compression::compress(codec, &obss_f32[range].iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>()),
+        });
+        Ok(PyBytes::new(py, &packed.map_err(PyRuntimeError::new_err)?))
+    }
+
+    // get_obs in sparse COO form, for graph networks and transformers over board cells:
+    // (indices, values) with indices shaped (nnz, 4) as (env, channel, x, y) rows in get_obs
+    // order and values in the obs dtype. Only the non-zero cells are listed
+    pub fn get_sparse_obs<'py>(&self, py: Python<'py>, model_i: usize) -> PyResult<(&'py PyArray2<u32>, PyObject)> {
+        let range = self.obs_range(model_i)?;
+        let shape = self.slot_obs_shape();
+        let (obss, obss_f32) = if self.pending.is_some() { (&self.front_obss, &self.front_obss_f32) } else { (&self.obss, &self.obss_f32) };
+        let (indices, values) = match self.obs_spec.dtype {
+            ObsDtype::U8 => {
+                let (indices, values) = py.allow_threads(|| sparse(&obss[range], shape));
+                (indices, PyArray1::from_vec(py, values).to_object(py))
+            }
+            ObsDtype::F32 => {
+                let (indices, values) = py.allow_threads(|| sparse(&obss_f32[range], shape));
+                (indices, PyArray1::from_vec(py, values).to_object(py))
+            }
+        };
+        let nnz = indices.len() / 4;
+        Ok((PyArray1::from_vec(py, indices).reshape([nnz, 4])?, values))
+    }
+
+    // The observations shaped like get_obs as each env's episode ended on the last step, what
+    // SB3 calls terminal_observation. Envs that didn't end read as zeros. With auto_reset these
+    // are the only way to see a terminal frame, get_obs already shows the next episode
+    pub fn get_final_obs(&self, py: Python<'_>, model_i: usize) -> PyResult<PyObject> {
+        self.check_not_pending()?;
+        let range = self.obs_range(model_i)?;
+        let shape = [self.n_envs, self.n_stack * self.obs_spec.num_layers(), self.obs_spec.layer_width, self.obs_spec.layer_height];
+        Ok(match self.obs_spec.dtype {
+            ObsDtype::U8 => PyArray1::from_slice(py, &self.final_obss[range]).reshape(shape)?.to_object(py),
+            ObsDtype::F32 => PyArray1::from_slice(py, &self.final_obss_f32[range]).reshape(shape)?.to_object(py),
+        })
+    }
+
+    // Per env, whether its episode ended on the last step
+    pub fn get_episode_ended(&self) -> PyResult<Vec<bool>> {
+        self.check_not_pending()?;
+        Ok(self.episode_ended.clone())
+    }
+
+    // get_obs under the first k of the 8 rotations and reflections of the canvas, the original
+    // first: (obs, actions) with obs shaped (k, n_envs, channels, width, height) and actions
+    // (k, 4), where actions[t, a] is the action that does in obs[t] what a does in the original.
+    // Policy targets follow with target[t][..., actions[t, a]] = target[..., a]
+    #[pyo3(signature = (k=8, model_i=0))]
+    pub fn get_augmented_obs(&self, py: Python<'_>, k: usize, model_i: usize) -> PyResult<(PyObject, PyObject)> {
+        if !(1..=augment::NUM_TRANSFORMS).contains(&k) {
+            return Err(PyValueError::new_err(format!("k must be in 1..={}, got {}", augment::NUM_TRANSFORMS, k)));
+        }
+        let range = self.obs_range(model_i)?;
+        let side = self.obs_spec.layer_width;
+        if side != self.obs_spec.layer_height {
+            return Err(PyValueError::new_err("augmentation needs a square observation canvas"));
+        }
+        // Turning an absolute frame turns the board about the canvas center, so it has to fill it
+        if self.obs_spec.framing == Framing::Absolute && self.params.iter().any(|p| p.board_width as usize != side || p.board_length as usize != side) {
+            return Err(PyValueError::new_err("augmenting absolute frames needs every board to fill the canvas"));
+        }
+        let shape = [k, self.n_envs, self.n_stack * self.obs_spec.num_layers(), side, side];
+        let (obss, obss_f32) = if self.pending.is_some() { (&self.front_obss, &self.front_obss_f32) } else { (&self.obss, &self.obss_f32) };
+        let obs = match self.obs_spec.dtype {
+            ObsDtype::U8 => {
+                let obs = py.allow_threads(|| augment::augment(&obss[range], side, k));
+                PyArray1::from_vec(py, obs).reshape(shape)?.to_object(py)
+            }
+            ObsDtype::F32 => {
+                let obs = py.allow_threads(|| augment::augment(&obss_f32[range], side, k));
+                PyArray1::from_vec(py, obs).reshape(shape)?.to_object(py)
+            }
+        };
+        let actions: Vec<i64> = augment::action_permutations()[..k].iter().flatten().map(|&a| a as i64).collect();
+        Ok((obs, PyArray1::from_vec(py, actions).reshape([k, 4])?.to_object(py)))
+    }
+
+    // Shape of one slot's observation, (n_stack * num_layers, layer_width, layer_height). get_obs
+    // batches n_envs of them
+    pub fn obs_shape(&self) -> PyResult<(usize, usize, usize)> {
+        self.check_not_pending()?;
+        let [channels, width, height] = self.slot_obs_shape();
+        Ok((channels, width, height))
+    }
+
+    // "uint8" or "float32"
+    #[pyo3(name = "obs_dtype")]
+    pub fn obs_dtype_name(&self) -> PyResult<&'static str> {
+        self.check_not_pending()?;
+        Ok(self.obs_spec.dtype.name())
+    }
+
+    // none, flips, d4 or face_up, see the orientation_mode constructor argument
+    #[pyo3(name = "orientation_mode")]
+    pub fn orientation_mode_name(&self) -> PyResult<&'static str> {
+        self.check_not_pending()?;
+        Ok(self.orientation_mode.name())
+    }
+
+    // Actions index u, d, l, r
+    pub fn num_actions(&self) -> usize {
+        4
+    }
+
+    // Names of the layers of one frame in channel order. Stacked frames repeat them, oldest first
+    pub fn layer_names(&self) -> PyResult<Vec<String>> {
+        self.check_not_pending()?;
+        Ok(self.obs_spec.layers().into_iter().map(|(name, _)| name).collect())
+    }
+
+    // JSON description of the observation encoding, enough to rebuild the preprocessing
+    // outside of Python byte-for-byte
+    pub fn export_encoding_spec(&self) -> PyResult<String> {
+        self.check_not_pending()?;
+        let channels: Vec<_> = self
+            .obs_spec
+            .layers()
+            .iter()
+            .enumerate()
+            .map(|(i, (name, scale))| json!({ "index": i, "name": name, "scale": scale }))
+            .collect();
+        let (layer_width, layer_height) = (self.obs_spec.layer_width, self.obs_spec.layer_height);
+        let orientation = self.orientation_mode.name();
+        Ok(json!({
+            "version": 1,
+            "dtype": self.obs_spec.dtype.name(),
+            "normalization": if self.obs_spec.dtype == ObsDtype::F32 { "value / scale" } else { "none" },
+            "accumulate": "add",
+            "layout": ["channel", "x", "y"],
+            "shape": [self.n_stack * self.obs_spec.num_layers(), layer_width, layer_height],
+            "strides": [layer_width * layer_height, layer_height, 1],
+            "n_stack": self.n_stack,
+            "stack_order": "oldest_first",
+            "window": { "width": layer_width, "height": layer_height },
+            "boards": self.params.iter().map(|p| json!({ "width": p.board_width, "height": p.board_length })).collect::<Vec<_>>(),
+            "framing": self.obs_spec.framing.name(),
+            // Absolute frames put board tile (x, y) at canvas (x, y) instead
+            "center": if self.obs_spec.framing == Framing::Egocentric { json!([layer_width / 2, layer_height / 2]) } else { Value::Null },
+            "orientation": orientation,
+            // Chebyshev radius around the center past which every channel is zero, per env
+            "fog": self.fog.iter().map(|f| f.map(|f| json!({ "radius": f.radius, "hide_enemy_health": f.hide_enemy_health }))).collect::<Vec<_>>(),
+            "actions": ["u", "d", "l", "r"],
+            "channels": channels,
+        })
+        .to_string())
+    }
+
+    // Imitation learning dataset from finished games, given as get_replay strings or engine
+    // games in the board viewer's format. Every snake alive on a turn before the last gives a
+    // sample: its observation rendered with this wrapper's settings, the action that makes its
+    // recorded move, and its outcome, 1 for the winner, -1 once eliminated and 0 otherwise.
+    // Saved with numpy.savez_compressed as obs, actions, outcomes and games, the replay index
+    // of each sample, and returns the number of samples. Replays play by env 0's rules and
+    // must fit the observation canvas and n_models
+    pub fn export_dataset(&self, py: Python<'_>, replays: Vec<&str>, path: &str) -> PyResult<usize> {
+        self.check_not_pending()?;
+        let replays = replays.into_iter().map(replay::parse_any).collect::<PyResult<Vec<_>>>()?;
+        dataset::export(self, py, &replays, path)
+    }
+
+    // Rolling performance over the last 100 steps: steps_per_sec and env_steps_per_sec by wall
+    // clock, and mean step_ms plus per-phase sim_ms, obs_write_ms and reset_ms per step. Phase
+    // times add up over envs, so they exceed step_ms when envs run in parallel
+    pub fn throughput_stats<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        self.throughput.to_dict(py, self.n_envs)
+    }
+
+    // Every Info field of the last step as a NumPy array shaped (n_models, n_envs)
+    pub fn get_info<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        let shape = [self.n_models, self.n_envs];
+        let dict = PyDict::new(py);
+        let u32_fields: [(&str, fn(&Info) -> u32); 13] = [
+            ("health", |i| i.health),
+            ("length", |i| i.length),
+            ("turn", |i| i.turn),
+            ("alive_count", |i| i.alive_count),
+            ("death_reason", |i| i.death_reason),
+            ("winner_id", |i| i.winner_id),
+            ("rank", |i| i.rank),
+            ("kills", |i| i.kills),
+            ("death_turn", |i| i.death_turn),
+            ("food_eaten", |i| i.food_eaten),
+            ("squad", |i| i.squad),
+            ("squad_alive", |i| i.squad_alive),
+            ("game_id", |i| i.game_id),
+        ];
+        for (name, field) in u32_fields {
+            let values: Vec<u32> = self.info.iter().map(field).collect();
+            dict.set_item(name, PyArray1::from_vec(py, values).reshape(shape)?)?;
+        }
+        let bool_fields: [(&str, fn(&Info) -> bool); 6] = [
+            ("alive", |i| i.alive),
+            ("ate", |i| i.ate),
+            ("over", |i| i.over),
+            ("draw", |i| i.draw),
+            ("won", |i| i.won),
+            ("max_turn_reached", |i| i.max_turn_reached),
+        ];
+        for (name, field) in bool_fields {
+            let values: Vec<bool> = self.info.iter().map(field).collect();
+            dict.set_item(name, PyArray1::from_vec(py, values).reshape(shape)?)?;
+        }
+        // Episode records in Gymnasium's vector env layout: return, length in turns and seconds
+        // since the wrapper was created, valid where _episode is set. Auto-reset has already
+        // started the next episode, so these are the only record of the finished one
+        let records = &self.episode_records;
+        let episode = PyDict::new(py);
+        episode.set_item("r", PyArray1::from_vec(py, records.iter().map(|r| r.map_or(0.0, |r| r.0)).collect()).reshape(shape)?)?;
+        episode.set_item("l", PyArray1::from_vec(py, records.iter().map(|r| r.map_or(0, |r| r.1)).collect()).reshape(shape)?)?;
+        episode.set_item("t", PyArray1::from_vec(py, records.iter().map(|r| r.map_or(0.0, |r| r.2)).collect()).reshape(shape)?)?;
+        dict.set_item("episode", episode)?;
+        dict.set_item("_episode", PyArray1::from_vec(py, records.iter().map(Option::is_some).collect()).reshape(shape)?)?;
+        Ok(dict)
+    }
+
+    // (terminated, truncated) of the last step, each shaped (n_models, n_envs). An episode is
+    // truncated when max_turns cut it off and terminated when it ended by the rules; either way
+    // the env has already been reset
+    pub fn get_dones<'py>(&self, py: Python<'py>) -> PyResult<(&'py PyArray2<bool>, &'py PyArray2<bool>)> {
+        self.check_not_pending()?;
+        let shape = [self.n_models, self.n_envs];
+        let terminated: Vec<bool> = self.info.iter().map(|i| i.over && !i.max_turn_reached).collect();
+        let truncated: Vec<bool> = self.info.iter().map(|i| i.max_turn_reached).collect();
+        Ok((PyArray1::from_vec(py, terminated).reshape(shape)?, PyArray1::from_vec(py, truncated).reshape(shape)?))
+    }
+
+    // Potential-based shaping terms of the last step, shaped (n_models, n_envs, 3). The columns
+    // are the change in flood-fill controlled area, the change in minus the moves to the nearest
+    // food and the change in length lead over the longest opponent, all with gamma = 1. Zero for
+    // eliminated snakes and right after reset()
+    pub fn get_shaping<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<f32>> {
+        self.check_not_pending()?;
+        if !self.reward_shaping {
+            return Err(PyValueError::new_err("reward shaping is disabled, construct with reward_shaping=True"));
+        }
+        let flat: Vec<f32> = self.shaping.iter().flatten().copied().collect();
+        PyArray1::from_vec(py, flat).reshape([self.n_models, self.n_envs, 3])
+    }
+
+    // Shape is (n_models, n_envs, 4) and indexed like the action buffer, so orientation and
+    // symmetry remaps are already applied. true = allowed, false = instantly fatal.
+    pub fn get_action_masks<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<bool>> {
+        self.check_not_pending()?;
+        self.check_initialized()?;
+        let moves = ['u', 'd', 'l', 'r'];
+        let mut masks = vec![true; self.n_models * self.n_envs * moves.len()];
+        masks.par_chunks_mut(moves.len()).enumerate().for_each(|(i, mask)| {
+            let (model_i, env_i) = (i / self.n_envs, i % self.n_envs);
+            let gi = match self.envs[env_i].as_ref() {
+                Some(gi) => gi,
+                None => return,
+            };
+            let player_id = match gi.get_player_ids().get(model_i) {
+                Some(&id) => id,
+                None => return,
+            };
+            let players = gi.get_state().1;
+            if !players.get(&player_id).map_or(false, |p| p.alive) {
+                return;
+            }
+            let (head, neck) = match head_and_neck(players, player_id) {
+                Some(head_and_neck) => head_and_neck,
+                None => return,
+            };
+            let ori = self.orientation(gi, player_id);
+            for (j, &m) in moves.iter().enumerate() {
+                mask[j] = !gi.is_move_fatal(player_id, self.decode_action(m, ori, head, neck));
+            }
+            // Every move loses, leave them all open so the masked distribution stays valid
+            if mask.iter().all(|&allowed| !allowed) {
+                mask.fill(true);
+            }
+        });
+        PyArray1::from_vec(py, masks).reshape([self.n_models, self.n_envs, moves.len()])
+    }
+
+    // Scalar features for hybrid CNN + MLP policies, shaped (n_models, n_envs, 8) in
+    // feature_names order. The space_ columns follow the slot's canvas like the action masks,
+    // so space_up is the room behind action u. Zero for slots without a living snake
+    pub fn get_features<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray3<f32>> {
+        self.check_not_pending()?;
+        self.check_initialized()?;
+        let n = territory::FEATURE_NAMES.len();
+        let moves = ['u', 'd', 'l', 'r'];
+        let mut features = vec![0.0; self.n_models * self.n_envs * n];
+        features.par_chunks_mut(n).enumerate().for_each(|(i, out)| {
+            let (model_i, env_i) = (i / self.n_envs, i % self.n_envs);
+            let gi = match self.envs[env_i].as_ref() {
+                Some(gi) => gi,
+                None => return,
+            };
+            let player_id = match gi.get_player_ids().get(model_i) {
+                Some(&id) => id,
+                None => return,
+            };
+            let players = gi.get_state().1;
+            if !players.get(&player_id).map_or(false, |p| p.alive) {
+                return;
+            }
+            let (head, neck) = match head_and_neck(players, player_id) {
+                Some(head_and_neck) => head_and_neck,
+                None => return,
+            };
+            let board = territory::features(gi, player_id);
+            out.copy_from_slice(&board);
+            let ori = self.orientation(gi, player_id);
+            for (j, &m) in moves.iter().enumerate() {
+                let board_move = self.decode_action(m, ori, head, neck);
+                out[1 + j] = board[1 + moves.iter().position(|&b| b == board_move).unwrap()];
+            }
+        });
+        PyArray1::from_vec(py, features).reshape([self.n_models, self.n_envs, n])
+    }
+
+    pub fn feature_names(&self) -> Vec<&'static str> {
+        territory::FEATURE_NAMES.to_vec()
+    }
+
+    // Room around a living snake's head as a dict: reachable, the free tiles its head can
+    // reach, dead_end, whether that's fewer than its length, and chokes, the (x, y) tiles that
+    // cut part of that area off once taken
+    pub fn analyze<'py>(&self, py: Python<'py>, env_i: usize, player_id: u32) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        let space = match territory::analyze(self.env(env_i)?, player_id) {
+            Some(space) => space,
+            None => return Err(PyValueError::new_err(format!("player {} is not a living snake of env {}", player_id, env_i))),
+        };
+        let dict = PyDict::new(py);
+        dict.set_item("reachable", space.reachable)?;
+        dict.set_item("dead_end", space.dead_end)?;
+        dict.set_item("chokes", space.chokes.iter().map(|t| (t.x, t.y)).collect::<Vec<_>>())?;
+        Ok(dict)
+    }
+
+    // The boards seen by model_i as one graph per env, batched the way torch_geometric's Batch
+    // is: a dict of x, the node features shaped (nodes, 9) in graph_feature_names order,
+    // edge_index shaped (2, edges) joining orthogonal neighbours in both directions, and batch,
+    // the env of every node. Node y * width + x of an env is its board cell (x, y), in board
+    // coordinates rather than the egocentric canvas
+    pub fn get_graph_obs<'py>(&self, py: Python<'py>, model_i: usize) -> PyResult<&'py PyDict> {
+        self.check_not_pending()?;
+        self.check_initialized()?;
+        let n = graphobs::NODE_FEATURE_NAMES.len();
+        let graphs: Vec<(Vec<f32>, Vec<[u32; 2]>)> = (0..self.n_envs)
+            .into_par_iter()
+            .map(|env_i| {
+                let gi = self.env(env_i)?;
+                let id = match gi.get_player_ids().get(model_i) {
+                    Some(&id) => id,
+                    None => return Err(PyValueError::new_err(format!("model {} has no snake in env {}", model_i, env_i))),
+                };
+                let (_, _, _, width, length, _, _) = gi.get_state();
+                Ok((graphobs::node_features(gi, id), graphobs::edges(width, length)))
+            })
+            .collect::<PyResult<_>>()?;
+        let (mut x, mut sources, mut targets, mut batch) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        for (env_i, (features, edges)) in graphs.into_iter().enumerate() {
+            let offset = batch.len() as i64;
+            batch.extend(std::iter::repeat(env_i as i64).take(features.len() / n));
+            x.extend(features);
+            sources.extend(edges.iter().map(|e| offset + e[0] as i64));
+            targets.extend(edges.iter().map(|e| offset + e[1] as i64));
+        }
+        let (nodes, edges) = (batch.len(), sources.len());
+        sources.extend(targets);
+        let dict = PyDict::new(py);
+        dict.set_item("x", PyArray1::from_vec(py, x).reshape([nodes, n])?)?;
+        dict.set_item("edge_index", PyArray1::from_vec(py, sources).reshape([2, edges])?)?;
+        dict.set_item("batch", PyArray1::from_vec(py, batch))?;
+        Ok(dict)
+    }
+
+    pub fn graph_feature_names(&self) -> Vec<&'static str> {
+        graphobs::NODE_FEATURE_NAMES.to_vec()
+    }
+}
+
+impl GameWrapper {
+    // Resets one env's game with its current map and params, then writes its first
+    // observations and Info
+    fn start_episode(&mut self, env_i: usize, game: &mut GameInstance) {
+        self.reset_game(env_i, game);
+        self.begin_episode(env_i, game);
+    }
+
+    // The part of start_episode that only touches the game, safe to run from the env tasks
+    fn reset_game(&self, env_i: usize, game: &mut GameInstance) {
+        game.set_hazards(self.hazards[env_i].clone());
+        game.set_walls(self.walls[env_i].clone());
+        game.set_pools(self.pools[env_i].clone());
+        game.reset_next_episode(self.params[env_i]);
+    }
+
+    // The rest of start_episode once the game is reset
+    fn begin_episode(&mut self, env_i: usize, game: &mut GameInstance) {
+        self.pick_episode_start(env_i, game);
+        if self.recording {
+            self.replays[env_i] = Some(Replay::new(game));
+        }
+        self.write_first_obs(env_i, game);
+        let ids = game.get_player_ids();
+        for m in 0..self.n_models {
+            let squad = ids.get(m).map_or(0, |id| game.get_state().1[id].squad);
+            self.info[m * self.n_envs + env_i] = Info {
+                health: 100,
+                length: PLAYER_STARTING_LENGTH as u32,
+                turn: 0,
+                // Slots past num_players have no snake this episode
+                alive: m < ids.len(),
+                ate: false,
+                over: false,
+                draw: false,
+                won: false,
+                max_turn_reached: false,
+                alive_count: ids.len() as u32,
+                death_reason: DeathReason::None as u32,
+                winner_id: 0,
+                rank: 1,
+                kills: 0,
+                death_turn: 0,
+                food_eaten: 0,
+                squad,
+                squad_alive: if m < ids.len() { game.squad_alive(squad) } else { 0 },
+                game_id: game.get_game_id(),
+            };
+        }
+    }
+
+    // Observations of a new episode's first turn, with the history filled from them. Slots
+    // without a snake this episode start blank
+    fn write_first_obs(&mut self, env_i: usize, game: &GameInstance) {
+        let ids = game.get_player_ids();
+        let shared = self.shared_obs(env_i, game);
+        for m in 0..ids.len() {
+            self.write_obs(m, env_i, ids[m], game, self.orientation(game, ids[m]), None, &shared);
+            self.fill_stack(m, env_i);
+        }
+        for m in ids.len()..self.n_models {
+            self.clear_stack(m, env_i);
+        }
+    }
+
+    // While replay_interesting is on, starts that fraction of fresh episodes over from a kept
+    // start instead, and keeps the start of the episode about to be played
+    fn pick_episode_start(&mut self, env_i: usize, game: &mut GameInstance) {
+        if self.interesting_capacity == 0 {
+            return;
+        }
+        let mut rng = rand::thread_rng();
+        if !self.interesting.is_empty() && rng.gen::<f64>() < self.interesting_fraction {
+            game.restart_from(&self.interesting[rng.gen_range(0..self.interesting.len())]);
+        }
+        self.episode_starts[env_i] = Some(game.clone());
+    }
+
+    // Bookkeeping for an env restarted outside of step and reset: new opponents, fresh
+    // shaping and episode counters, and no pending end of episode
+    fn restart_env(&mut self, env_i: usize, game: &GameInstance) {
+        self.sample_opponents(env_i);
+        self.rebase_potentials(env_i, game);
+        if self.reward_shaping {
+            for m in 0..self.n_models {
+                self.shaping[m * self.n_envs + env_i] = [0.0; 3];
+            }
+        }
+        self.episode_food[env_i] = 0;
+        for m in 0..self.n_models {
+            self.episode_returns[m * self.n_envs + env_i] = 0.0;
+            self.episode_records[m * self.n_envs + env_i] = None;
+        }
+        self.episode_ended[env_i] = false;
+        self.awaiting_reset[env_i] = false;
+        self.keep_final_obs(env_i, false);
+    }
+
+    // Episode bookkeeping once a step's Info is in. Envs that failed during the step are parked
+    // first, so their half-written episode isn't counted, and raised once the others are booked
+    fn finish_step(&mut self) -> PyResult<()> {
+        let failed = self.raise_env_errors();
+        for env_i in 0..self.n_envs {
+            self.finish_env(env_i);
+        }
+        #[cfg(feature = "arrow")]
+        if let Some(log) = self.rollouts.as_mut() {
+            log.finish(self.n_envs, &self.acts, &self.info).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        }
+        self.report_metrics()?;
+        #[cfg(feature = "tui")]
+        self.publish_watched();
+        failed
+    }
+
+    #[cfg(feature = "tui")]
+    fn publish_watched(&mut self) {
+        let env_i = match self.watcher.as_ref().map(Watcher::watched_env) {
+            Some(Some(env_i)) => env_i,
+            // The viewer was quit, the terminal is back to normal
+            Some(None) => {
+                self.watcher = None;
+                return;
+            }
+            None => return,
+        };
+        if let (Some(watcher), Some(game)) = (&self.watcher, &self.envs[env_i]) {
+            watcher.publish(env_i, game, self.throughput.total_steps());
+        }
+    }
+
+    // Nothing is gathered while no sink or callback listens
+    fn report_metrics(&mut self) -> PyResult<()> {
+        if self.metrics_sinks.is_empty() && self.metrics_callback.is_none() {
+            return Ok(());
+        }
+        let mut metrics = StepMetrics { n_envs: self.n_envs as u32, ..StepMetrics::default() };
+        self.throughput.fill_metrics(&mut metrics);
+        let mut episode_turns = 0;
+        for env_i in 0..self.n_envs {
+            let info = &self.info[env_i];
+            metrics.mean_turn += info.turn as f64 / self.n_envs as f64;
+            // Model 0's record is only there on the step its episode finished
+            if self.episode_records[env_i].is_none() {
+                continue;
+            }
+            metrics.episodes_finished += 1;
+            metrics.wins += info.won as u32;
+            metrics.draws += info.draw as u32;
+            metrics.death_reasons[info.death_reason as usize] += 1;
+            episode_turns += info.turn as u64;
+        }
+        if metrics.episodes_finished > 0 {
+            metrics.mean_episode_turns = episode_turns as f64 / metrics.episodes_finished as f64;
+        }
+        for sink in &mut self.metrics_sinks {
+            sink.record(&metrics);
+        }
+        if let Some(callback) = &self.metrics_callback {
+            Python::with_gil(|py| callback.call1(py, (metrics.to_dict(py)?,)).map(|_| ()))?;
+        }
+        Ok(())
+    }
+
+    // Envs that went wrong since the last check sit out every step like finished envs waiting
+    // for a reset, and their errors are raised together
+    fn raise_env_errors(&mut self) -> PyResult<()> {
+        let mut failed = Vec::new();
+        for env_i in 0..self.n_envs {
+            if let Some(e) = self.env_errors[env_i].take() {
+                self.awaiting_reset[env_i] = true;
+                self.episode_ended[env_i] = false;
+                failed.push(format!("env {}: {}", env_i, e));
+            }
+        }
+        if failed.is_empty() {
+            return Ok(());
+        }
+        Err(PyRuntimeError::new_err(format!("{}; these envs sit out until they are reset", failed.join("; "))))
+    }
+
+    // Keeps the observations of every slot about to act for the step's rollout batch
+    #[cfg(feature = "arrow")]
+    fn begin_rollout(&mut self) {
+        if self.rollouts.is_none() {
+            return;
+        }
+        let slots: Vec<usize> = (0..self.n_models * self.n_envs).filter(|&k| self.info[k].alive && !self.awaiting_reset[k % self.n_envs]).collect();
+        let offsets: Vec<usize> = slots.iter().map(|&k| self.frame_offset(k / self.n_envs, k % self.n_envs, 0)).collect();
+        let len = self.n_stack * self.obs_spec.obs_size();
+        let log = self.rollouts.as_mut().unwrap();
+        match self.obs_spec.dtype {
+            ObsDtype::U8 => log.begin_u8(slots, gather(&self.obss, &offsets, len), len),
+            ObsDtype::F32 => log.begin_f32(slots, gather(&self.obss_f32, &offsets, len), len),
+        }
+    }
+
+    fn finish_env(&mut self, env_i: usize) {
+        for m in 0..self.n_models {
+            self.episode_records[m * self.n_envs + env_i] = None;
+        }
+        // Waiting for a reset since an earlier step, already counted then
+        if self.awaiting_reset[env_i] && !self.episode_ended[env_i] {
+            return;
+        }
+        let num_players = self.params[env_i].num_players as usize;
+        for m in 0..std::cmp::min(num_players, self.n_models) {
+            let k = m * self.n_envs + env_i;
+            let info = &self.info[k];
+            // Each snake is rewarded once, when it is eliminated or when it outlasts the episode
+            if (!info.alive && info.death_turn == info.turn) || (info.over && info.alive) {
+                self.episode_returns[k] += info.reward();
+            }
+            if info.over {
+                self.episode_records[k] = Some((self.episode_returns[k], info.turn, self.created.elapsed().as_secs_f64()));
+                self.episode_returns[k] = 0.0;
+            }
+        }
+        let info = &self.info[env_i];
+        self.episode_food[env_i] += info.ate as u32;
+        if info.over {
+            self.stats.record(info.turn, self.episode_food[env_i], info.kills, info.won, info.draw, info.max_turn_reached, info.death_reason);
+            let (won, draw) = (info.won, info.draw);
+            // Slots under a scripted policy only had the opponent on paper
+            let mut played: Vec<usize> = (1..self.n_models).filter(|&m| self.policies[m].is_none()).filter_map(|m| self.opponents[env_i][m]).collect();
+            played.sort_unstable();
+            played.dedup();
+            for p in played {
+                self.opponent_pool.record(p, won, draw);
+            }
+            let interesting = [DeathReason::Starvation as u32, DeathReason::HeadToHead as u32].contains(&info.death_reason);
+            if let Some(start) = self.finished_starts[env_i].take().filter(|_| interesting) {
+                if self.interesting.len() == self.interesting_capacity {
+                    self.interesting.pop_front();
+                }
+                self.interesting.push_back(start);
+            }
+            self.episode_food[env_i] = 0;
+            self.sample_opponents(env_i);
+        }
+    }
+
+    // Simulates one turn of every env, no Python involved
+    fn step_envs(&mut self) {
+        let started = Instant::now();
+        let frame = self.obs_spec.obs_size();
+        shift_frames(&mut self.obss, frame, self.n_stack, &self.awaiting_reset);
+        shift_frames(&mut self.obss_f32, frame, self.n_stack, &self.awaiting_reset);
+        // The games leave the wrapper while their turns are played in parallel, which only reads
+        // the rest of it. Info, shaping, replays and observations are written afterwards, in env
+        // order
+        let mut envs = std::mem::take(&mut self.envs);
+        let mut env_errors = std::mem::take(&mut self.env_errors);
+        self.scheduler.for_each_env(&mut envs, &mut env_errors, &|ii, gi| guarded(|| self.play_turn(ii, gi)));
+        self.envs = envs;
+        self.env_errors = env_errors;
+        for env_i in 0..self.n_envs {
+            if self.awaiting_reset[env_i] {
+                self.episode_ended[env_i] = false;
+                self.keep_final_obs(env_i, false);
+                continue;
+            }
+            // Envs whose turn went wrong are left as they are until the error is raised
+            if self.env_errors[env_i].is_some() {
+                continue;
+            }
+            if let Some(mut game) = self.envs[env_i].take() {
+                let finished = guarded(|| {
+                    self.finish_turn(env_i, &mut game);
+                    Ok(())
+                });
+                self.envs[env_i] = Some(game);
+                self.env_errors[env_i] = finished.err();
+            }
+        }
+        self.throughput.end_step(started);
+    }
+
+    // One env's moves and turn, the part of a step run from the env tasks. Envs waiting for a
+    // reset sit it out
+    fn play_turn(&self, env_i: usize, gi: &mut Option<GameInstance>) -> Result<(), String> {
+        if self.awaiting_reset[env_i] {
+            return Ok(());
+        }
+        let game = match gi.as_mut() {
+            Some(game) => game,
+            None => return Err("the env has no game".to_string()),
+        };
+        let ids = game.get_player_ids();
+        for (m, &id) in ids.iter().enumerate() {
+            // Eliminated snakes have no move to make
+            if !game.get_state().1[&id].alive {
+                continue;
+            }
+            let action = match &self.policies[m] {
+                Some(policy) => policy.act(game, id),
+                None => self.get_action(m, env_i, self.orientation(game, id), id, game)?,
+            };
+            game.set_player_move(id, action);
+        }
+        let sim = Instant::now();
+        game.step();
+        self.throughput.add(Phase::Sim, sim.elapsed());
+        Ok(())
+    }
+
+    // The rest of one env's step once its turn is played: Info, shaping, the replay and the
+    // observations, then the next episode if this one is done
+    fn finish_turn(&mut self, env_i: usize, game: &mut GameInstance) {
+        let ids = game.get_player_ids();
+        let players = game.get_state().1;
+        // The episode ends with the learning snake, which is always model 0
+        let learner_dead = self.end_on_learner_death && !players[&ids[0]].alive;
+        let done = learner_dead || game.is_over() || game.max_turn_reached();
+        for m in 0..self.n_models {
+            self.info[m * self.n_envs + env_i] = slot_info(game, ids.get(m).copied(), done);
+        }
+        if self.reward_shaping {
+            let now = territory::potentials(game);
+            for (m, id) in ids.iter().enumerate() {
+                let k = m * self.n_envs + env_i;
+                // Eliminated snakes stop receiving shaping
+                self.shaping[k] = match now.get(id) {
+                    Some(p) => [p[0] - self.potentials[k][0], p[1] - self.potentials[k][1], p[2] - self.potentials[k][2]],
+                    None => [0.0; 3],
+                };
+                self.potentials[k] = now.get(id).copied().unwrap_or([0.0; 3]);
+            }
+        }
+        let dirty = if self.incremental_obs && !done { Some(self.dirty_tiles(game)) } else { None };
+        if let Some(replay) = self.replays[env_i].as_mut() {
+            replay.push(game);
+            if done {
+                self.finished_replays[env_i] = self.replays[env_i].take();
+            }
+        }
+        if done {
+            self.finished_starts[env_i] = self.episode_starts[env_i].take();
+        }
+        self.episode_ended[env_i] = done;
+        let obs_write = Instant::now();
+        let shared = self.shared_obs(env_i, game);
+        for (m, &id) in ids.iter().enumerate() {
+            self.write_obs(m, env_i, id, game, self.orientation(game, id), dirty.as_deref(), &shared);
+        }
+        self.keep_final_obs(env_i, done);
+        self.throughput.add(Phase::ObsWrite, obs_write.elapsed());
+        if !done {
+            return;
+        }
+        if !self.auto_reset {
+            // Left on its terminal observations until reset
+            self.awaiting_reset[env_i] = true;
+            return;
+        }
+
+        let reset = Instant::now();
+        game.reset_next_episode(self.params[env_i]);
+        self.pick_episode_start(env_i, game);
+        self.rebase_potentials(env_i, game);
+        if self.recording {
+            self.replays[env_i] = Some(Replay::new(game));
+        }
+        self.throughput.add(Phase::Reset, reset.elapsed());
+        let obs_write = Instant::now();
+        self.write_first_obs(env_i, game);
+        self.throughput.add(Phase::ObsWrite, obs_write.elapsed());
+    }
+
+    // Runs every pooled policy once on the observations of the slots it controls
+    fn act_opponents(&mut self, py: Python<'_>) -> PyResult<()> {
+        for (p, entry) in self.opponent_pool.entries().iter().enumerate() {
+            let slots: Vec<(usize, usize)> = (0..self.n_envs)
+                .flat_map(|e| (1..self.n_models).map(move |m| (m, e)))
+                .filter(|&(m, e)| self.opponents[e][m] == Some(p) && self.policies[m].is_none())
+                .collect();
+            if slots.is_empty() {
+                continue;
+            }
+            let actions: Vec<i64> = match &entry.policy {
+                PoolPolicy::Callback(policy) => policy.call1(py, (self.obs_batch(py, &slots)?,))?.extract(py)?,
+                #[cfg(feature = "onnx")]
+                PoolPolicy::Onnx(policy) => {
+                    let shape = [slots.len(), self.n_stack * self.obs_spec.num_layers(), self.obs_spec.layer_width, self.obs_spec.layer_height];
+                    let obs = self.obs_batch_f32(&slots);
+                    py.allow_threads(|| policy.act(obs, shape, &mut rand::thread_rng()))
+                        .map_err(|e| PyRuntimeError::new_err(format!("opponent {} failed: {}", entry.name, e)))?
+                        .into_iter()
+                        .map(i64::from)
+                        .collect()
+                }
+            };
+            apply_opponent_actions(&mut self.acts, self.n_envs, &entry.name, &slots, &actions)?;
+        }
+        if let Some(callback) = &self.opponent_callback {
+            let slots: Vec<(usize, usize)> = (0..self.n_envs)
+                .flat_map(|e| (1..self.n_models).map(move |m| (m, e)))
+                .filter(|&(m, e)| self.opponents[e][m].is_none() && self.policies[m].is_none() && self.slot_alive(m, e))
+                .collect();
+            if !slots.is_empty() {
+                let actions: Vec<i64> = callback.call1(py, (self.obs_batch(py, &slots)?,))?.extract(py)?;
+                apply_opponent_actions(&mut self.acts, self.n_envs, "callback", &slots, &actions)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs the ONNX learner on model 0's observations of every env
+    #[cfg(feature = "onnx")]
+    fn act_learner(&mut self, py: Python<'_>) -> PyResult<()> {
+        let learner = match &self.learner {
+            Some(learner) => learner,
+            None => return Ok(()),
+        };
+        let (obs, shape) = self.model_obs_f32(0);
+        let actions = py.allow_threads(|| learner.act(obs, shape, &mut rand::thread_rng())).map_err(|e| PyRuntimeError::new_err(format!("learner failed: {}", e)))?;
+        self.set_model_actions(0, &actions);
+        Ok(())
+    }
+
+    fn slot_alive(&self, model_i: usize, env_i: usize) -> bool {
+        match self.envs[env_i].as_ref() {
+            Some(gi) => gi.get_player_ids().get(model_i).map_or(false, |id| gi.get_state().1[id].alive),
+            None => false,
+        }
+    }
+
+    fn sample_opponents(&mut self, env_i: usize) {
+        let mut rng = rand::thread_rng();
+        for m in 1..self.n_models {
+            self.opponents[env_i][m] = self.opponent_pool.sample(&mut rng);
+        }
+    }
+
+    // Stacked observations of the given (model_i, env_i) slots as one (batch, channels, width, height) array
+    fn obs_batch(&self, py: Python<'_>, slots: &[(usize, usize)]) -> PyResult<PyObject> {
+        let len = self.n_stack * self.obs_spec.obs_size();
+        let offsets: Vec<usize> = slots.iter().map(|&(m, e)| self.frame_offset(m, e, 0)).collect();
+        let shape = [slots.len(), self.n_stack * self.obs_spec.num_layers(), self.obs_spec.layer_width, self.obs_spec.layer_height];
+        Ok(match self.obs_spec.dtype {
+            ObsDtype::U8 => PyArray1::from_vec(py, gather(&self.obss, &offsets, len)).reshape(shape)?.to_object(py),
+            ObsDtype::F32 => PyArray1::from_vec(py, gather(&self.obss_f32, &offsets, len)).reshape(shape)?.to_object(py),
+        })
+    }
+
+    // Same as obs_batch but as a flat float32 buffer for in-Rust inference
+    #[cfg(feature = "onnx")]
+    fn obs_batch_f32(&self, slots: &[(usize, usize)]) -> Vec<f32> {
+        let len = self.n_stack * self.obs_spec.obs_size();
+        let offsets: Vec<usize> = slots.iter().map(|&(m, e)| self.frame_offset(m, e, 0)).collect();
+        match self.obs_spec.dtype {
+            ObsDtype::U8 => gather(&self.obss, &offsets, len).into_iter().map(f32::from).collect(),
+            ObsDtype::F32 => gather(&self.obss_f32, &offsets, len),
+        }
+    }
+
+    // Starts the potentials of a fresh episode so its first step isn't measured against the
+    // previous one
+    fn rebase_potentials(&mut self, env_i: usize, game: &GameInstance) {
+        if !self.reward_shaping {
+            return;
+        }
+        let now = territory::potentials(game);
+        let ids = game.get_player_ids();
+        for m in 0..self.n_models {
+            self.potentials[m * self.n_envs + env_i] = ids.get(m).and_then(|id| now.get(id)).copied().unwrap_or([0.0; 3]);
+        }
+    }
+
+    // A single-env wrapper holding a copy of env_i, its observations and info, with the same
+    // observation settings. It has no opponents, reward shaping or worker threads
+    pub(crate) fn branch(&self, env_i: usize) -> GameWrapper {
+        let len = self.n_stack * self.obs_spec.obs_size();
+        let offsets: Vec<usize> = (0..self.n_models).map(|m| self.frame_offset(m, env_i, 0)).collect();
+        let (obss, obss_f32) = match self.obs_spec.dtype {
+            ObsDtype::U8 => (gather(&self.obss, &offsets, len), Vec::new()),
+            ObsDtype::F32 => (Vec::new(), gather(&self.obss_f32, &offsets, len)),
+        };
+        GameWrapper {
+            n_envs: 1,
+            n_models: self.n_models,
+            envs: vec![self.envs[env_i].clone()],
+            obss,
+            obss_f32,
+            front_obss: Vec::new(),
+            front_obss_f32: Vec::new(),
+            pending: None,
+            final_obss: vec![0; self.final_obss.len() / self.n_envs],
+            final_obss_f32: vec![0.0; self.final_obss_f32.len() / self.n_envs],
+            auto_reset: false,
+            episode_ended: vec![false],
+            env_errors: vec![None],
+            awaiting_reset: vec![self.awaiting_reset[env_i]],
+            acts: vec![0; self.n_models],
+            info: (0..self.n_models).map(|m| self.info[m * self.n_envs + env_i].clone()).collect(),
+            episode_food: vec![self.episode_food[env_i]],
+            episode_returns: (0..self.n_models).map(|m| self.episode_returns[m * self.n_envs + env_i]).collect(),
+            episode_records: vec![None; self.n_models],
+            created: self.created,
+            stats: EpisodeStats::default(),
+            hazards: vec![self.hazards[env_i].clone()],
+            walls: vec![self.walls[env_i].clone()],
+            pools: vec![self.pools[env_i].clone()],
+            fog: vec![self.fog[env_i]],
+            orientation_mode: self.orientation_mode,
+            opponent_pool: OpponentPool::default(),
+            opponents: vec![vec![None; self.n_models]],
+            opponent_callback: None,
+            policies: (0..self.n_models).map(|_| None).collect(),
+            #[cfg(feature = "onnx")]
+            learner: None,
+            params: vec![self.params[env_i]],
+            obs_spec: self.obs_spec.clone(),
+            n_stack: self.n_stack,
+            reward_shaping: false,
+            potentials: Vec::new(),
+            shaping: Vec::new(),
+            end_on_learner_death: false,
+            scheduler: Box::new(SerialScheduler),
+            single_threaded: true,
+            seed: None,
+            throughput: Throughput::default(),
+            metrics_sinks: Vec::new(),
+            metrics_callback: None,
+            incremental_obs: false,
+            obs_frames: vec![None; self.n_models],
+            recording: false,
+            replays: vec![None],
+            finished_replays: vec![None],
+            delta_frames: vec![None],
+            eval_set: Vec::new(),
+            interesting_capacity: 0,
+            interesting_fraction: 0.0,
+            interesting: VecDeque::new(),
+            episode_starts: vec![None],
+            finished_starts: vec![None],
+            #[cfg(feature = "tui")]
+            watcher: None,
+            #[cfg(feature = "arrow")]
+            rollouts: None,
+        }
+    }
+
+    // One turn of env 0 with the given actions, without resetting a finished game
+    pub(crate) fn simulate_step(&mut self, moves: &[u8]) -> PyResult<()> {
+        if moves.len() != self.n_models {
+            return Err(PyValueError::new_err(format!("expected {} moves, got {}", self.n_models, moves.len())));
+        }
+        if let Some(&m) = moves.iter().find(|&&m| m >= 4) {
+            return Err(PyValueError::new_err(format!("invalid move {}", m)));
+        }
+        let game = self.env(0)?;
+        if game.is_over() || game.max_turn_reached() {
+            return Err(PyValueError::new_err("the game is over"));
+        }
+        let mut game = self.envs[0].take().unwrap();
+        self.acts.copy_from_slice(moves);
+        let ids = game.get_player_ids();
+        let actions: Result<Vec<(u32, char)>, String> = ids
+            .iter()
+            .enumerate()
+            .filter(|&(_, id)| game.get_state().1[id].alive)
+            .map(|(m, &id)| self.get_action(m, 0, self.orientation(&game, id), id, &game).map(|action| (id, action)))
+            .collect();
+        let actions = match actions {
+            Ok(actions) => actions,
+            Err(e) => {
+                self.envs[0] = Some(game);
+                return Err(PyRuntimeError::new_err(e));
+            }
+        };
+        for (id, action) in actions {
+            game.set_player_move(id, action);
+        }
+
+        let frame = self.obs_spec.obs_size();
+        shift_frames(&mut self.obss, frame, self.n_stack, &[false]);
+        shift_frames(&mut self.obss_f32, frame, self.n_stack, &[false]);
+        game.step();
+        let done = game.is_over() || game.max_turn_reached();
+        let shared = self.shared_obs(0, &game);
+        for (m, &id) in ids.iter().enumerate() {
+            self.info[m] = slot_info(&game, Some(id), done);
+            self.write_obs(m, 0, id, &game, self.orientation(&game, id), None, &shared);
+        }
+        for m in ids.len()..self.n_models {
+            self.info[m] = slot_info(&game, None, done);
+        }
+        self.envs[0] = Some(game);
+        self.raise_env_errors()
+    }
+
+    // Takes over everything __getstate__ saved. Nothing is changed unless the whole state is valid
+    fn restore(&mut self, v: &Value, obs: &[u8]) -> Result<(), String> {
+        let list = |key: &str| field(v, key)?.as_array().ok_or_else(|| format!("{} is not a list", key));
+        let n_envs = uint(v, "n_envs")? as usize;
+        let n_models = uint(v, "n_models")? as usize;
+        let n_stack = uint(v, "n_stack")? as usize;
+        let obs_spec = ObsSpec::from_json(field(v, "obs_spec")?)?;
+        let orientation_mode = field(v, "orientation_mode")?
+            .as_str()
+            .and_then(OrientationMode::parse)
+            .ok_or("orientation_mode is not an orientation mode")?;
+        let reward_shaping = boolean(v, "reward_shaping")?;
+        let end_on_learner_death = boolean(v, "end_on_learner_death")?;
+        let single_threaded = boolean(v, "single_threaded")?;
+        let seed = match field(v, "seed")? {
+            Value::Null => None,
+            seed => match (seed.get(0).and_then(Value::as_u64), seed.get(1).and_then(Value::as_u64)) {
+                (Some(seed), Some(first_stream)) => Some((seed, first_stream)),
+                _ => return Err(format!("seed {} is not [seed, first_stream]", seed)),
+            },
+        };
+        let incremental_obs = boolean(v, "incremental_obs")?;
+        let auto_reset = boolean(v, "auto_reset")?;
+        let awaiting_reset = serde_json::from_value::<Vec<bool>>(field(v, "awaiting_reset")?.clone()).map_err(|e| e.to_string())?;
+        let params = list("params")?.iter().map(Parameters::from_json).collect::<Result<Vec<_>, _>>()?;
+        let hazards = list("hazards")?.iter().map(|h| Ok(tiles(h)?.into_iter().collect())).collect::<Result<Vec<HashSet<Tile>>, String>>()?;
+        let walls = list("walls")?.iter().map(|w| Ok(tiles(w)?.into_iter().collect())).collect::<Result<Vec<HashSet<Tile>>, String>>()?;
+        let pools = list("pools")?.iter().map(|p| weighted_tiles(p, "heal", 1)).collect::<Result<Vec<_>, String>>()?;
+        let fog = list("fog")?
+            .iter()
+            .map(|f| match (f.is_null(), f.get(0).and_then(Value::as_u64), f.get(1).and_then(Value::as_bool)) {
+                (true, _, _) => Ok(None),
+                (false, Some(radius), Some(hide_enemy_health)) => Ok(Some(Fog { radius: radius as u32, hide_enemy_health })),
+                _ => Err(format!("fog {} is not [radius, hide_enemy_health]", f)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let envs = list("envs")?
+            .iter()
+            .map(|e| if e.is_null() { Ok(None) } else { GameInstance::from_snapshot(e).map(Some) })
+            .collect::<Result<Vec<_>, _>>()?;
+        let info = list("info")?.iter().map(Info::from_json).collect::<Result<Vec<_>, _>>()?;
+        let acts = serde_json::from_value::<Vec<u8>>(field(v, "acts")?.clone()).map_err(|e| e.to_string())?;
+        let episode_food = serde_json::from_value::<Vec<u32>>(field(v, "episode_food")?.clone()).map_err(|e| e.to_string())?;
+        let episode_returns = serde_json::from_value::<Vec<f32>>(field(v, "episode_returns")?.clone()).map_err(|e| e.to_string())?;
+        let opponents = serde_json::from_value::<Vec<Vec<Option<usize>>>>(field(v, "opponents")?.clone()).map_err(|e| e.to_string())?;
+        let potentials = serde_json::from_value::<Vec<[f32; 3]>>(field(v, "potentials")?.clone()).map_err(|e| e.to_string())?;
+        let shaping = serde_json::from_value::<Vec<[f32; 3]>>(field(v, "shaping")?.clone()).map_err(|e| e.to_string())?;
+        let stats = EpisodeStats::from_json(field(v, "stats")?)?;
+        let ratings = Ratings::from_json(field(v, "ratings")?)?;
+        let eval_set = list("eval_set")?.iter().map(GameInstance::from_snapshot).collect::<Result<Vec<_>, _>>()?;
+        let interesting_capacity = uint(v, "interesting_capacity")? as usize;
+        let interesting_fraction = field(v, "interesting_fraction")?.as_f64().ok_or("interesting_fraction is not a number")?;
+        let interesting = list("interesting")?.iter().map(GameInstance::from_snapshot).collect::<Result<VecDeque<_>, _>>()?;
+
+        let slots = n_models * n_envs;
+        let obs_len = slots * n_stack * obs_spec.obs_size();
+        let value_size = match obs_spec.dtype {
+            ObsDtype::U8 => 1,
+            ObsDtype::F32 => 4,
+        };
+        let sizes = [
+            ("params", params.len(), n_envs),
+            ("hazards", hazards.len(), n_envs),
+            ("walls", walls.len(), n_envs),
+            ("pools", pools.len(), n_envs),
+            ("fog", fog.len(), n_envs),
+            ("envs", envs.len(), n_envs),
+            ("info", info.len(), slots),
+            ("acts", acts.len(), slots),
+            ("episode_food", episode_food.len(), n_envs),
+            ("episode_returns", episode_returns.len(), slots),
+            ("awaiting_reset", awaiting_reset.len(), n_envs),
+            ("opponents", opponents.len(), n_envs),
+            ("potentials", potentials.len(), if reward_shaping { slots } else { 0 }),
+            ("shaping", shaping.len(), if reward_shaping { slots } else { 0 }),
+            ("observations", obs.len(), obs_len * value_size),
+        ];
+        for (name, len, expected) in sizes {
+            if len != expected {
+                return Err(format!("expected {} {}, got {}", expected, name, len));
+            }
+        }
+        if opponents.iter().any(|slots| slots.len() != n_models) || n_stack == 0 {
+            return Err("opponents or n_stack don't match n_models".to_string());
+        }
+
+        self.n_envs = n_envs;
+        self.n_models = n_models;
+        self.n_stack = n_stack;
+        (self.obss, self.obss_f32) = match obs_spec.dtype {
+            ObsDtype::U8 => (obs.to_vec(), Vec::new()),
+            ObsDtype::F32 => (Vec::new(), obs.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect()),
+        };
+        (self.final_obss, self.final_obss_f32) = match obs_spec.dtype {
+            ObsDtype::U8 => (vec![0; obs_len], Vec::new()),
+            ObsDtype::F32 => (Vec::new(), vec![0.0; obs_len]),
+        };
+        self.obs_spec = obs_spec;
+        self.front_obss = Vec::new();
+        self.front_obss_f32 = Vec::new();
+        self.auto_reset = auto_reset;
+        self.episode_ended = vec![false; n_envs];
+        self.env_errors = vec![None; n_envs];
+        self.awaiting_reset = awaiting_reset;
+        self.envs = envs;
+        self.params = params;
+        self.hazards = hazards;
+        self.walls = walls;
+        self.pools = pools;
+        self.fog = fog;
+        self.info = info;
+        self.acts = acts;
+        self.episode_food = episode_food;
+        self.episode_returns = episode_returns;
+        self.episode_records = vec![None; slots];
+        self.stats = stats;
+        self.opponents = opponents;
+        *self.opponent_pool.ratings_mut() = ratings;
+        self.orientation_mode = orientation_mode;
+        self.reward_shaping = reward_shaping;
+        self.potentials = potentials;
+        self.shaping = shaping;
+        self.end_on_learner_death = end_on_learner_death;
+        self.scheduler = if single_threaded { Box::new(SerialScheduler) } else { Box::new(RayonScheduler) };
+        self.single_threaded = single_threaded;
+        self.seed = seed;
+        self.throughput = Throughput::default();
+        self.incremental_obs = incremental_obs;
+        self.obs_frames = vec![None; slots];
+        self.policies = (0..n_models).map(|_| None).collect();
+        #[cfg(feature = "onnx")]
+        let _ = self.learner.take();
+        self.recording = false;
+        self.replays = vec![None; n_envs];
+        self.finished_replays = vec![None; n_envs];
+        self.delta_frames = vec![None; n_envs];
+        self.eval_set = eval_set;
+        self.interesting_capacity = interesting_capacity;
+        self.interesting_fraction = interesting_fraction;
+        self.interesting = interesting;
+        self.episode_starts = vec![None; n_envs];
+        self.finished_starts = vec![None; n_envs];
+        #[cfg(feature = "tui")]
+        let _ = self.watcher.take();
+        Ok(())
+    }
+
+    // Rust consumers of the per-step metrics, called in the order they were added, see
+    // set_metrics_callback
+    pub fn add_metrics_sink(&mut self, sink: Box<dyn MetricsSink>) {
+        self.metrics_sinks.push(sink);
+    }
+
+    // Rust policies for a model slot of every env, see set_scripted_policy
+    pub fn set_policy(&mut self, model_i: usize, policy: Option<Box<dyn Policy>>) -> PyResult<()> {
+        if model_i >= self.n_models {
+            return Err(PyValueError::new_err(format!("model_i {} out of range for {} models", model_i, self.n_models)));
+        }
+        self.policies[model_i] = policy;
+        Ok(())
+    }
+
+    fn finished_replay(&self, env_i: usize) -> PyResult<&Replay> {
+        self.check_env(env_i)?;
+        self.finished_replays[env_i]
+            .as_ref()
+            .ok_or_else(|| PyValueError::new_err(format!("env {} has no finished recorded episode, call record_replays(True) first", env_i)))
+    }
+
+    // load_scenario with the scenario already parsed
+    pub(crate) fn load_scenario_value(&mut self, env_i: usize, v: &Value) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.env(env_i)?;
+        let mut game = self.envs[env_i].take().unwrap();
+        if let Err(e) = game.load_scenario(v) {
+            self.envs[env_i] = Some(game);
+            return Err(PyValueError::new_err(format!("invalid scenario: {}", e)));
+        }
+        // Hand-made positions aren't kept by replay_interesting
+        self.episode_starts[env_i] = None;
+        if self.recording {
+            self.replays[env_i] = Some(Replay::new(&game));
+        }
+        self.write_first_obs(env_i, &game);
+        let ids = game.get_player_ids();
+        for m in 0..self.n_models {
+            self.info[m * self.n_envs + env_i] = slot_info(&game, ids.get(m).copied(), false);
+        }
+        self.restart_env(env_i, &game);
+        self.envs[env_i] = Some(game);
+        self.raise_env_errors()
+    }
+
+    // The board move that model_i's current action in env_i stands for
+    pub(crate) fn slot_move(&self, model_i: usize, env_i: usize) -> PyResult<char> {
+        let game = self.env(env_i)?;
+        let id = match game.get_player_ids().get(model_i) {
+            Some(&id) => id,
+            None => return Err(PyValueError::new_err(format!("model {} has no snake in env {}", model_i, env_i))),
+        };
+        self.get_action(model_i, env_i, self.orientation(game, id), id, game).map_err(|e| PyRuntimeError::new_err(format!("env {}: {}", env_i, e)))
+    }
+
+    // The action of model_i in env_i that stands for a board move, the inverse of slot_move
+    pub(crate) fn slot_action(&self, model_i: usize, env_i: usize, board_move: char) -> PyResult<u8> {
+        let game = self.env(env_i)?;
+        let id = match game.get_player_ids().get(model_i) {
+            Some(&id) => id,
+            None => return Err(PyValueError::new_err(format!("model {} has no snake in env {}", model_i, env_i))),
+        };
+        let (head, neck) = match head_and_neck(game.get_state().1, id) {
+            Some(head_and_neck) => head_and_neck,
+            None => return Err(PyValueError::new_err(format!("model {} has no body to move in env {}", model_i, env_i))),
+        };
+        let ori = self.orientation(game, id);
+        let moves = ['u', 'd', 'l', 'r'];
+        let action = (0..moves.len()).find(|&a| self.decode_action(moves[a], ori, head, neck) == board_move);
+        action.map(|a| a as u8).ok_or_else(|| PyValueError::new_err(format!("invalid move {}", board_move)))
+    }
+
+    // A single-env wrapper with this one's observation settings and rules, on the first frame
+    // of a replay with its snakes in slot order. No food spawns, so the caller places it
+    pub(crate) fn spectator(&self, replay: &Replay) -> PyResult<GameWrapper> {
+        let first = replay.frames.first().ok_or_else(|| PyValueError::new_err("replay has no frames"))?;
+        let point = |t: &Tile| json!({ "x": t.x, "y": t.y });
+        let snakes: Vec<_> = first.snakes.iter().map(|s| json!({ "health": s.health, "body": s.body.iter().map(point).collect::<Vec<_>>() })).collect();
+        let scenario = json!({
+            "turn": first.turn,
+            "board": {
+                "width": replay.width,
+                "height": replay.height,
+                "food": first.food.iter().map(point).collect::<Vec<_>>(),
+                "hazards": first.hazards.iter().map(point).collect::<Vec<_>>(),
+                "snakes": snakes,
+            },
+        });
+        self.with_position(&scenario, self.params[0].hazard_damage)
+    }
+
+    // A single-env wrapper with this one's observation settings and rules on a position in the
+    // shape of GameInstance::load_scenario, one slot per snake in the order listed. No food
+    // spawns
+    pub(crate) fn with_position(&self, scenario: &Value, hazard_damage: usize) -> PyResult<GameWrapper> {
+        let invalid = |e: String| PyValueError::new_err(format!("invalid scenario: {}", e));
+        let board = field(scenario, "board").map_err(invalid)?;
+        let (width, height) = (uint(board, "width").map_err(invalid)? as u32, uint(board, "height").map_err(invalid)? as u32);
+        let num_players = field(board, "snakes").map_err(invalid)?.as_array().map_or(0, Vec::len) as u32;
+        let params = Parameters {
+            board_width: width,
+            board_length: height,
+            num_players,
+            food_spawn_chance: 0.0,
+            minimum_food: 0,
+            super_food_chance: 0.0,
+            hazard_damage,
+            max_turns: 0,
+            ..self.params[0]
+        };
+        check_params(&params, &self.obs_spec, self.n_models, self.orientation_mode)?;
+        let mut wrapper = self.branch(0);
+        wrapper.params[0] = params;
+        wrapper.hazards[0].clear();
+        wrapper.walls[0].clear();
+        wrapper.pools[0].clear();
+        wrapper.fog[0] = None;
+        wrapper.awaiting_reset[0] = false;
+        let mut game = GameInstance::new(width, height, num_players, 0.0);
+        game.reset_with_parameters(params, &mut rand::thread_rng());
+        wrapper.envs[0] = Some(game);
+        wrapper.load_scenario_value(0, scenario)?;
+        Ok(wrapper)
+    }
+
+    // Cells where a move request rendered through with_position differs from slot model_i's
+    // newest frame of env_i, as (layer, x, y, from the request, from the env), see
+    // check_obs_parity
+    pub(crate) fn obs_parity(&self, request: &Value, env_i: usize, model_i: usize) -> PyResult<Vec<(String, usize, usize, f32, f32)>> {
+        self.env(env_i)?;
+        if model_i >= self.n_models {
+            return Err(PyValueError::new_err(format!("model_i {} out of range for {} models", model_i, self.n_models)));
+        }
+        let (scenario, hazard_damage) = moverequest::request_scenario(request).map_err(|e| PyValueError::new_err(format!("invalid move request: {}", e)))?;
+        let mut deployed = self.with_position(&scenario, hazard_damage)?;
+        let mut trained = self.branch(env_i);
+        let mut frames = Vec::new();
+        for (wrapper, m) in [(&mut deployed, 0), (&mut trained, model_i)] {
+            // Random flips would turn the two frames apart
+            if matches!(wrapper.orientation_mode, OrientationMode::Flips | OrientationMode::D4) {
+                wrapper.orientation_mode = OrientationMode::None;
+            }
+            let game = wrapper.envs[0].take().unwrap();
+            wrapper.write_first_obs(0, &game);
+            wrapper.envs[0] = Some(game);
+            wrapper.raise_env_errors()?;
+            let start = wrapper.frame_offset(m, 0, wrapper.n_stack - 1);
+            let range = start..start + wrapper.obs_spec.obs_size();
+            frames.push(match wrapper.obs_spec.dtype {
+                ObsDtype::U8 => wrapper.obss[range].iter().map(|&v| v as f32).collect::<Vec<_>>(),
+                ObsDtype::F32 => wrapper.obss_f32[range].to_vec(),
+            });
+        }
+        let layers = self.obs_spec.layers();
+        let (width, height) = (self.obs_spec.layer_width, self.obs_spec.layer_height);
+        Ok(frames[0]
+            .iter()
+            .zip(&frames[1])
+            .enumerate()
+            .filter(|&(_, (a, b))| a != b)
+            .map(|(i, (&a, &b))| (layers[i / (width * height)].0.clone(), i / height % width, i % height, a, b))
+            .collect())
+    }
+
+    pub(crate) fn env_mut(&mut self, env_i: usize) -> PyResult<&mut GameInstance> {
+        self.check_env(env_i)?;
+        self.envs[env_i].as_mut().ok_or_else(|| PyValueError::new_err("environments are not initialized, call reset() first"))
+    }
+
+    // step_async with the actions already in the buffer
+    pub(crate) fn start_step(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.check_not_pending()?;
+        self.check_initialized()?;
+        self.act_opponents(py)?;
+        #[cfg(feature = "onnx")]
+        self.act_learner(py)?;
+        #[cfg(feature = "arrow")]
+        self.begin_rollout();
+        self.front_obss.clone_from(&self.obss);
+        self.front_obss_f32.clone_from(&self.obss_f32);
+        // The thread owns the wrapper until it's joined, self stands in for it meanwhile
+        let stand_in = self.stand_in();
+        let mut wrapper = std::mem::replace(self, stand_in);
+        self.pending = Some(std::thread::spawn(move || {
+            let stepped = guarded(|| {
+                wrapper.step_envs();
+                Ok(())
+            });
+            (wrapper, stepped)
+        }));
+        Ok(())
+    }
+
+    // Takes the wrapper back from step_async's thread, if there is one. Err when the step
+    // panicked, the wrapper is back either way unless the thread itself died
+    fn join_pending(&mut self) -> Result<(), String> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let (wrapper, stepped) = pending.join().map_err(|_| "died, its envs are lost".to_string())?;
+        // The front buffers are kept for the next step_async
+        let front = (std::mem::take(&mut self.front_obss), std::mem::take(&mut self.front_obss_f32));
+        *self = wrapper;
+        (self.front_obss, self.front_obss_f32) = front;
+        stepped
+    }
+
+    // What's left in place of the wrapper while step_async's thread has it: the observations the
+    // step started from, and the shapes get_obs and the other observation getters need. Every
+    // other method raises until step_wait
+    fn stand_in(&mut self) -> GameWrapper {
+        GameWrapper {
+            n_envs: self.n_envs,
+            n_models: self.n_models,
+            envs: Vec::new(),
+            obss: Vec::new(),
+            obss_f32: Vec::new(),
+            front_obss: std::mem::take(&mut self.front_obss),
+            front_obss_f32: std::mem::take(&mut self.front_obss_f32),
+            pending: None,
+            final_obss: Vec::new(),
+            final_obss_f32: Vec::new(),
+            auto_reset: self.auto_reset,
+            episode_ended: Vec::new(),
+            env_errors: Vec::new(),
+            awaiting_reset: Vec::new(),
+            acts: Vec::new(),
+            info: Vec::new(),
+            episode_food: Vec::new(),
+            episode_returns: Vec::new(),
+            episode_records: Vec::new(),
+            created: self.created,
+            stats: EpisodeStats::default(),
+            hazards: Vec::new(),
+            walls: Vec::new(),
+            pools: Vec::new(),
+            fog: Vec::new(),
+            orientation_mode: self.orientation_mode,
+            opponent_pool: OpponentPool::default(),
+            opponents: Vec::new(),
+            opponent_callback: None,
+            policies: Vec::new(),
+            #[cfg(feature = "onnx")]
+            learner: None,
+            params: self.params.clone(),
+            obs_spec: self.obs_spec.clone(),
+            n_stack: self.n_stack,
+            reward_shaping: false,
+            potentials: Vec::new(),
+            shaping: Vec::new(),
+            end_on_learner_death: false,
+            scheduler: Box::new(SerialScheduler),
+            single_threaded: self.single_threaded,
+            seed: None,
+            throughput: Throughput::default(),
+            metrics_sinks: Vec::new(),
+            metrics_callback: None,
+            incremental_obs: false,
+            obs_frames: Vec::new(),
+            recording: false,
+            replays: Vec::new(),
+            finished_replays: Vec::new(),
+            delta_frames: Vec::new(),
+            eval_set: Vec::new(),
+            interesting_capacity: 0,
+            interesting_fraction: 0.0,
+            interesting: VecDeque::new(),
+            episode_starts: Vec::new(),
+            finished_starts: Vec::new(),
+            #[cfg(feature = "tui")]
+            watcher: None,
+            #[cfg(feature = "arrow")]
+            rollouts: None,
+        }
+    }
+
+    // Seeded envs of this wrapper take the streams from first_stream on, so wrappers sharing a
+    // seed don't replay each other's envs
+    pub(crate) fn set_first_stream(&mut self, first_stream: u64) {
+        if let Some((seed, _)) = self.seed {
+            self.seed = Some((seed, first_stream));
+        }
+    }
+
+    pub(crate) fn set_scheduler(&mut self, scheduler: Box<dyn Scheduler>) {
+        self.scheduler = scheduler;
+    }
+
+    pub(crate) fn set_end_on_learner_death(&mut self, end: bool) {
+        self.end_on_learner_death = end;
+    }
+
+    pub(crate) fn info(&self, model_i: usize, env_i: usize) -> &Info {
+        &self.info[model_i * self.n_envs + env_i]
+    }
+
+    pub(crate) fn set_action(&mut self, model_i: usize, env_i: usize, action: u8) {
+        self.acts[model_i * self.n_envs + env_i] = action;
+    }
+
+    // (channels, width, height) of a single slot's stacked observation
+    pub(crate) fn slot_obs_shape(&self) -> [usize; 3] {
+        [self.n_stack * self.obs_spec.num_layers(), self.obs_spec.layer_width, self.obs_spec.layer_height]
+    }
+
+    pub(crate) fn obs_dtype(&self) -> ObsDtype {
+        self.obs_spec.dtype
+    }
+
+    pub(crate) fn slot_obs(&self, py: Python<'_>, model_i: usize, env_i: usize) -> PyResult<PyObject> {
+        let start = self.frame_offset(model_i, env_i, 0);
+        let range = start..start + self.n_stack * self.obs_spec.obs_size();
+        Ok(match self.obs_spec.dtype {
+            ObsDtype::U8 => PyArray1::from_slice(py, &self.obss[range]).reshape(self.slot_obs_shape())?.to_object(py),
+            ObsDtype::F32 => PyArray1::from_slice(py, &self.obss_f32[range]).reshape(self.slot_obs_shape())?.to_object(py),
+        })
+    }
+
+    // The tiles as a set, what being the kind of tile named when one is off env_i's board
+    fn board_tiles(&self, env_i: usize, tiles: Vec<(u32, u32)>, what: &str) -> PyResult<HashSet<Tile>> {
+        let p = &self.params[env_i];
+        let mut set = HashSet::new();
+        for (x, y) in tiles {
+            if x >= p.board_width || y >= p.board_length {
+                return Err(PyValueError::new_err(format!("{} ({}, {}) is outside the {}x{} board", what, x, y, p.board_width, p.board_length)));
+            }
+            set.insert(Tile { x, y });
+        }
+        Ok(set)
+    }
+
+    fn check_not_pending(&self) -> PyResult<()> {
+        if self.pending.is_some() {
+            return Err(PyRuntimeError::new_err("a step_async is in flight, call step_wait() first"));
+        }
+        Ok(())
+    }
+
+    fn check_initialized(&self) -> PyResult<()> {
+        if self.envs.iter().any(Option::is_none) {
+            return Err(PyValueError::new_err("environments are not initialized, call reset() first"));
+        }
+        Ok(())
+    }
+
+    fn check_env(&self, env_i: usize) -> PyResult<()> {
+        if env_i >= self.n_envs {
+            return Err(PyValueError::new_err(format!("env_i {} out of range for {} envs", env_i, self.n_envs)));
+        }
+        Ok(())
+    }
+
+    // Actions of one model slot in every env, without the checks set_actions does
+    pub(crate) fn set_model_actions(&mut self, model_i: usize, actions: &[u8]) {
+        self.acts[model_i * self.n_envs..(model_i + 1) * self.n_envs].copy_from_slice(actions);
+    }
+
+    // Stacked observations of one model slot in every env as a flat float32 buffer and its shape
+    #[cfg(feature = "onnx")]
+    pub(crate) fn model_obs_f32(&self, model_i: usize) -> (Vec<f32>, [usize; 4]) {
+        let slots: Vec<(usize, usize)> = (0..self.n_envs).map(|e| (model_i, e)).collect();
+        let [channels, width, height] = self.slot_obs_shape();
+        (self.obs_batch_f32(&slots), [self.n_envs, channels, width, height])
+    }
+
+    pub(crate) fn slot_count(&self) -> usize {
+        self.n_models
+    }
+
+    pub(crate) fn env(&self, env_i: usize) -> PyResult<&GameInstance> {
+        self.check_env(env_i)?;
+        self.envs[env_i].as_ref().ok_or_else(|| PyValueError::new_err("environments are not initialized, call reset() first"))
+    }
+
+    // Where model_i's stacked observations of every env sit in the observation buffers
+    fn obs_range(&self, model_i: usize) -> PyResult<std::ops::Range<usize>> {
+        if model_i >= self.n_models {
+            return Err(PyValueError::new_err(format!("model_i {} out of range for {} models", model_i, self.n_models)));
+        }
+        let len = self.n_envs * self.n_stack * self.obs_spec.obs_size();
+        Ok(model_i * len..(model_i + 1) * len)
+    }
+
+    // Frames of one (model, env) pair are contiguous and oldest first, so a stack reads
+    // as n_stack * num_layers channels
+    fn frame_offset(&self, model_i: usize, env_i: usize, frame: usize) -> usize {
+        ((model_i * self.n_envs + env_i) * self.n_stack + frame) * self.obs_spec.obs_size()
+    }
+
+    // Copies the newest frame over the history so a new episode doesn't see the previous one
+    fn fill_stack(&mut self, model_i: usize, env_i: usize) {
+        let obs_size = self.obs_spec.obs_size();
+        let newest = self.frame_offset(model_i, env_i, self.n_stack - 1);
+        for frame in 0..self.n_stack - 1 {
+            let dst = self.frame_offset(model_i, env_i, frame);
+            match self.obs_spec.dtype {
+                ObsDtype::U8 => self.obss.copy_within(newest..newest + obs_size, dst),
+                ObsDtype::F32 => self.obss_f32.copy_within(newest..newest + obs_size, dst),
+            }
+        }
+    }
+
+    // Copies every slot's stack of an env whose episode just ended into the final buffers, or
+    // clears them when it didn't end
+    fn keep_final_obs(&mut self, env_i: usize, ended: bool) {
+        for m in 0..self.n_models {
+            let range = self.frame_offset(m, env_i, 0)..self.frame_offset(m, env_i, self.n_stack);
+            match self.obs_spec.dtype {
+                ObsDtype::U8 if ended => self.final_obss[range.clone()].copy_from_slice(&self.obss[range]),
+                ObsDtype::U8 => self.final_obss[range].fill(0),
+                ObsDtype::F32 if ended => self.final_obss_f32[range.clone()].copy_from_slice(&self.obss_f32[range]),
+                ObsDtype::F32 => self.final_obss_f32[range].fill(0.0),
+            }
+        }
+    }
+
+    fn clear_stack(&mut self, model_i: usize, env_i: usize) {
+        let range = self.frame_offset(model_i, env_i, 0)..self.frame_offset(model_i, env_i, self.n_stack);
+        match self.obs_spec.dtype {
+            ObsDtype::U8 => self.obss[range].fill(0),
+            ObsDtype::F32 => self.obss_f32[range].fill(0.0),
+        }
+        self.obs_frames[model_i * self.n_envs + env_i] = None;
+    }
+
+    // Board tiles an incremental frame has to rewrite after a step: whatever changed on the
+    // board, plus every body shown, since segment indices and health move along with a snake
+    fn dirty_tiles(&self, game: &GameInstance) -> Vec<bool> {
+        let (_, players, _, width, length, turn, _) = game.get_state();
+        let corpse_turns = self.obs_spec.corpse_turns;
+        let shown = players.values().filter(|p| p.alive || (corpse_turns > 0 && turn - p.death_turn <= corpse_turns));
+        let mut dirty = vec![false; (width * length) as usize];
+        for t in game.changed_tiles().iter().chain(shown.flat_map(|p| p.body.iter())) {
+            dirty[(t.y * width + t.x) as usize] = true;
+        }
+        dirty
+    }
+
+    // Maps board tiles and moves onto a slot's canvas: face up in face_up mode, otherwise the
+    // slot's orientation
+    fn slot_transform(&self, ori: D4, head: Tile, neck: Tile) -> SymmetryTransform {
+        if self.orientation_mode == OrientationMode::FaceUp {
+            // YOU CAN ONLY DO THIS IF THE GAME BOARD IS SQUARE
+            SymmetryTransform::face_up(head, neck)
+        } else {
+            ori.transform()
+        }
+    }
+
+    // An action picked on the slot's canvas, as a move on the board
+    fn decode_action(&self, action: char, ori: D4, head: Tile, neck: Tile) -> char {
+        self.slot_transform(ori, head, neck).inverse().map_action(action)
+    }
+}
+
+impl Drop for GameWrapper {
+    fn drop(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            let _ = pending.join();
+        }
+    }
+}
+
+// Drops the oldest frame of every stack outside the frozen envs. The newest slot keeps the
+// previous frame, which write_obs either patches or clears
+fn shift_frames<T: Copy + Default + Send>(obss: &mut [T], frame: usize, n_stack: usize, frozen: &[bool]) {
+    let n_envs = frozen.len();
+    obss.par_chunks_mut(n_stack * frame).enumerate().filter(|(k, _)| !frozen[k % n_envs]).for_each(|(_, frames)| {
+        frames.copy_within(frame.., 0);
+    });
+}
+
+// Moves a frame's contents by shift elements, or clears it when there is nothing to patch
+fn shift_frame<T: Copy + Default>(frame: &mut [T], shift: Option<isize>) {
+    let len = frame.len();
+    match shift {
+        None => frame.fill(T::default()),
+        Some(d) if d >= 0 => {
+            let d = d as usize;
+            frame.copy_within(..len - d, d);
+            frame[..d].fill(T::default());
+        }
+        Some(d) => {
+            let d = d.unsigned_abs();
+            frame.copy_within(d.., 0);
+            frame[len - d..].fill(T::default());
+        }
+    }
+}
+
+// Head and neck of a player, None when it isn't in the game or nothing is left of its body.
+// A snake of length 1 is its own neck
+fn head_and_neck(players: &Players, player_id: u32) -> Option<(Tile, Tile)> {
+    let body = &players.get(&player_id)?.body;
+    let head = *body.first()?;
+    Some((head, *body.get(1).unwrap_or(&head)))
+}
+
+// Runs one env's part of a step or reset, turning a panic into an error of that env, so a bug
+// in one game can't unwind out of a worker thread and abort the interpreter
+fn guarded(f: impl FnOnce() -> Result<(), String>) -> Result<(), String> {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = match (panic.downcast_ref::<&str>(), panic.downcast_ref::<String>()) {
+                (Some(message), _) => message.to_string(),
+                (_, Some(message)) => message.clone(),
+                _ => "unknown panic".to_string(),
+            };
+            Err(format!("panicked: {}", message))
+        }
+    }
+}
+
+// Info of one model slot after a step, for a slot without a snake when id is None
+fn slot_info(game: &GameInstance, id: Option<u32>, done: bool) -> Info {
+    let players = game.get_state().1;
+    let it = match id {
+        Some(id) => &players[&id],
+        None => return Info { over: done, max_turn_reached: game.max_turn_reached(), game_id: game.get_game_id(), ..Info::default() },
+    };
+    // Health is capped at 100 and bodies fit the board, so these never saturate
+    Info {
+        health: u32::try_from(it.health).unwrap_or(u32::MAX),
+        length: u32::try_from(it.body.len()).unwrap_or(u32::MAX),
+        turn: game.get_turn(),
+        alive: it.alive,
+        ate: it.ate_this_turn,
+        over: done,
+        draw: game.is_draw(),
+        won: game.get_winning_squad() == Some(it.squad),
+        max_turn_reached: game.max_turn_reached(),
+        alive_count: u32::try_from(players.values().filter(|p| p.alive).count()).unwrap_or(u32::MAX),
+        death_reason: it.death_reason as u32,
+        winner_id: game.get_winner().unwrap_or(0),
+        rank: game.get_rank(it.id as u32),
+        kills: it.kills,
+        death_turn: if it.alive { 0 } else { it.death_turn },
+        food_eaten: it.food_eaten,
+        squad: it.squad,
+        squad_alive: game.squad_alive(it.squad),
+        game_id: game.get_game_id(),
+    }
+}
+
+// Checks that a board configuration fits the observation canvas and player slots
+fn check_params(params: &Parameters, obs_spec: &ObsSpec, n_models: usize, orientation_mode: OrientationMode) -> PyResult<()> {
+    if params.board_width == 0 || params.board_length == 0 {
+        return Err(PyValueError::new_err(format!("board size must be positive, got {}x{}", params.board_width, params.board_length)));
+    }
+    let (width, height) = match obs_spec.framing {
+        Framing::Egocentric => (canvas_size(params.board_width, params.board_length), canvas_size(params.board_width, params.board_length)),
+        Framing::Absolute => (params.board_width as usize, params.board_length as usize),
+    };
+    if width > obs_spec.layer_width || height > obs_spec.layer_height {
+        return Err(PyValueError::new_err(format!(
+            "a {}x{} board does not fit the {}x{} observation canvas",
+            params.board_width, params.board_length, obs_spec.layer_width, obs_spec.layer_height
+        )));
+    }
+    if orientation_mode == OrientationMode::FaceUp && params.board_width != params.board_length {
+        return Err(PyValueError::new_err("face_up orientation needs a square board"));
+    }
+    if params.num_players == 0 || params.num_players as usize > n_models {
+        return Err(PyValueError::new_err(format!("num_players must be in 1..={}, got {}", n_models, params.num_players)));
+    }
+    let capacity = spawn_capacity(params.board_width, params.board_length);
+    if params.num_players as usize > capacity {
+        return Err(PyValueError::new_err(format!(
+            "a {}x{} board fits at most {} snakes, got {}",
+            params.board_width, params.board_length, capacity, params.num_players
+        )));
+    }
+    if !(0.0..=1.0).contains(&params.food_spawn_chance) {
+        return Err(PyValueError::new_err(format!("food_spawn_chance must be in [0, 1], got {}", params.food_spawn_chance)));
+    }
+    let tiles = params.board_width as usize * params.board_length as usize;
+    if params.minimum_food as usize >= tiles {
+        return Err(PyValueError::new_err(format!("minimum_food must leave room on a {}x{} board, got {}", params.board_width, params.board_length, params.minimum_food)));
+    }
+    if !(0.0..=1.0).contains(&params.super_food_chance) {
+        return Err(PyValueError::new_err(format!("super_food_chance must be in [0, 1], got {}", params.super_food_chance)));
+    }
+    if params.squads.size == 0 {
+        return Err(PyValueError::new_err("squad size must be at least 1"));
+    }
+    Ok(())
+}
+
+// Applies the set fields of a set_params call on top of base
+#[allow(clippy::too_many_arguments)]
+fn override_params(
+    mut params: Parameters,
+    board_size: Option<&PyAny>,
+    num_players: Option<u32>,
+    food_spawn_chance: Option<f32>,
+    minimum_food: Option<u32>,
+    super_food_chance: Option<f32>,
+    super_food_growth: Option<u32>,
+    hazard_damage: Option<usize>,
+    food_spawns_in_hazard: Option<bool>,
+    max_turns: Option<u32>,
+    squads: Option<&PyDict>,
+    legacy_growth: Option<bool>,
+    snail_mode: Option<bool>,
+) -> PyResult<Parameters> {
+    if let Some(size) = board_size {
+        (params.board_width, params.board_length) = match size.extract::<u32>() {
+            Ok(side) => (side, side),
+            Err(_) => size.extract::<(u32, u32)>()?,
+        };
+    }
+    params.num_players = num_players.unwrap_or(params.num_players);
+    params.food_spawn_chance = food_spawn_chance.unwrap_or(params.food_spawn_chance);
+    params.minimum_food = minimum_food.unwrap_or(params.minimum_food);
+    params.super_food_chance = super_food_chance.unwrap_or(params.super_food_chance);
+    params.super_food_growth = super_food_growth.unwrap_or(params.super_food_growth);
+    params.hazard_damage = hazard_damage.unwrap_or(params.hazard_damage);
+    params.food_spawns_in_hazard = food_spawns_in_hazard.unwrap_or(params.food_spawns_in_hazard);
+    params.max_turns = max_turns.unwrap_or(params.max_turns);
+    params.legacy_growth = legacy_growth.unwrap_or(params.legacy_growth);
+    params.snail_mode = snail_mode.unwrap_or(params.snail_mode);
+    if let Some(squads) = squads {
+        let rules = &mut params.squads;
+        for (key, value) in squads.iter() {
+            match key.extract::<&str>()? {
+                "size" => rules.size = value.extract()?,
+                "allow_body_collisions" => rules.allow_body_collisions = value.extract()?,
+                "shared_elimination" => rules.shared_elimination = value.extract()?,
+                "shared_health" => rules.shared_health = value.extract()?,
+                other => return Err(PyValueError::new_err(format!("unknown squads key '{}'", other))),
+            }
+        }
+    }
+    Ok(params)
+}
+
+// Same as override_params with the fields taken from a dict of set_params keys
+fn params_from_dict(base: Parameters, config: &PyDict) -> PyResult<Parameters> {
+    for key in config.keys() {
+        let key: &str = key.extract()?;
+        if !["board_size", "num_players", "food_spawn_chance", "minimum_food", "super_food_chance", "super_food_growth", "hazard_damage", "food_spawns_in_hazard", "max_turns", "squads", "legacy_growth", "snail_mode"].contains(&key) {
+            return Err(PyValueError::new_err(format!("unknown env config key '{}'", key)));
+        }
+    }
+    override_params(
+        base,
+        config.get_item("board_size"),
+        config.get_item("num_players").map(|v| v.extract()).transpose()?,
+        config.get_item("food_spawn_chance").map(|v| v.extract()).transpose()?,
+        config.get_item("minimum_food").map(|v| v.extract()).transpose()?,
+        config.get_item("super_food_chance").map(|v| v.extract()).transpose()?,
+        config.get_item("super_food_growth").map(|v| v.extract()).transpose()?,
+        config.get_item("hazard_damage").map(|v| v.extract()).transpose()?,
+        config.get_item("food_spawns_in_hazard").map(|v| v.extract()).transpose()?,
+        config.get_item("max_turns").map(|v| v.extract()).transpose()?,
+        config.get_item("squads").map(|v| v.downcast::<PyDict>()).transpose()?,
+        config.get_item("legacy_growth").map(|v| v.extract()).transpose()?,
+        config.get_item("snail_mode").map(|v| v.extract()).transpose()?,
+    )
+}
+
+// (env, channel, x, y) and the value of every non-zero cell of stacked observations laid out
+// env after env, each shaped [channels, width, height]
+fn sparse<T: Copy + Default + PartialEq + Send + Sync>(obss: &[T], [channels, width, height]: [usize; 3]) -> (Vec<u32>, Vec<T>) {
+    let per_env: Vec<(Vec<u32>, Vec<T>)> = obss
+        .par_chunks(channels * width * height)
+        .enumerate()
+        .map(|(env, obs)| {
+            let (mut indices, mut values) = (Vec::new(), Vec::new());
+            for (i, &v) in obs.iter().enumerate().filter(|&(_, &v)| v != T::default()) {
+                indices.extend([env, i / (width * height), i / height % width, i % height].map(|n| n as u32));
+                values.push(v);
+            }
+            (indices, values)
+        })
+        .collect();
+    let indices = per_env.iter().flat_map(|(indices, _)| indices.iter().copied()).collect();
+    let values = per_env.into_iter().flat_map(|(_, values)| values).collect();
+    (indices, values)
+}
+
+fn gather<T: Copy>(obss: &[T], offsets: &[usize], len: usize) -> Vec<T> {
+    offsets.iter().flat_map(|&o| obss[o..o + len].iter().copied()).collect()
+}
+
+// Writes an opponent's actions for the given (model_i, env_i) slots into the action buffer
+fn apply_opponent_actions(acts: &mut [u8], n_envs: usize, name: &str, slots: &[(usize, usize)], actions: &[i64]) -> PyResult<()> {
+    if actions.len() != slots.len() {
+        return Err(PyValueError::new_err(format!("opponent {} returned {} actions for {} observations", name, actions.len(), slots.len())));
+    }
+    for (&(m, e), &a) in slots.iter().zip(actions) {
+        if !(0..4).contains(&a) {
+            return Err(PyValueError::new_err(format!("opponent {} returned invalid action {}", name, a)));
+        }
+        acts[m * n_envs + e] = a as u8;
+    }
+    Ok(())
+}
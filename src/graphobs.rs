@@ -0,0 +1,54 @@
+use crate::gameinstance::{GameInstance, Tile};
+
+// Columns of node_features. Bodies count down from the head like the dense body layer, and
+// health sits on heads
+pub const NODE_FEATURE_NAMES: [&str; 9] = ["own_body", "own_head", "enemy_body", "enemy_head", "body_rank", "health", "food", "hazard", "wall"];
+
+// One node per board cell, indexed y * width + x, seen by snake id. Rows of
+// NODE_FEATURE_NAMES.len() values, flattened
+pub fn node_features(game: &GameInstance, id: u32) -> Vec<f32> {
+    let n = NODE_FEATURE_NAMES.len();
+    let (_, players, food, width, length, _, hazards) = game.get_state();
+    let mut x = vec![0.0; (width * length) as usize * n];
+    let mut set = |t: Tile, feature: usize, value: f32| x[(t.y * width + t.x) as usize * n + feature] = value;
+    for player in players.values().filter(|p| p.alive) {
+        let own = player.id == id;
+        let len = player.body.len() as f32;
+        for (i, &t) in player.body.iter().enumerate().rev() {
+            set(t, if own { 0 } else { 2 }, 1.0);
+            set(t, 4, (len - i as f32) / len);
+        }
+        set(player.body[0], if own { 1 } else { 3 }, 1.0);
+        set(player.body[0], 5, player.health as f32 / 100.0);
+    }
+    for &t in food {
+        set(t, 6, 1.0);
+    }
+    for &t in hazards.iter().chain(game.get_trails().keys()) {
+        set(t, 7, 1.0);
+    }
+    for &t in game.get_walls() {
+        set(t, 8, 1.0);
+    }
+    x
+}
+
+// Both directions of every pair of orthogonally adjacent cells as (source, target) node
+// indices, PyG's edge_index read column by column
+pub fn edges(width: u32, length: u32) -> Vec<[u32; 2]> {
+    let mut edges = Vec::new();
+    for y in 0..length {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                edges.push([i, i + 1]);
+                edges.push([i + 1, i]);
+            }
+            if y + 1 < length {
+                edges.push([i, i + width]);
+                edges.push([i + width, i]);
+            }
+        }
+    }
+    edges
+}
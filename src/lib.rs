@@ -1,13 +1,80 @@
+mod augment;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "compression")]
+mod compression;
+mod dataset;
+mod episodestats;
+mod gamehandle;
 mod gameinstance;
 mod gamewrapper;
+mod graphobs;
+mod metrics;
+mod moverequest;
+mod obsspec;
+#[cfg(feature = "onnx")]
+mod onnxpolicy;
+mod opponentpool;
+mod pettingzoo;
+mod policy;
+#[cfg(feature = "onnx")]
+mod policyserver;
+#[cfg(feature = "prometheus")]
+mod prometheus;
+mod ratings;
+mod replay;
+#[cfg(feature = "cli")]
+mod remotesnake;
+#[cfg(feature = "arrow")]
+mod rollouts;
+mod scenariosuite;
+mod scheduler;
+mod shardedwrapper;
+mod statedelta;
+mod symmetry;
+mod territory;
+mod throughput;
+mod tournament;
+#[cfg(feature = "tui")]
+mod watch;
 
+#[cfg(feature = "compression")]
+pub use compression::{compress, decompress, Codec};
+pub use gameinstance::{DeathReason, GameInstance, Parameters, Player, Players, SquadRules, Tile};
+pub use gamehandle::GameHandle;
 pub use gamewrapper::GameWrapper;
+pub use metrics::{MetricsSink, StepMetrics};
+pub use obsspec::{CellWrite, ObsDtype, ObsSpec};
+pub use pettingzoo::PettingZooWrapper;
+pub use policy::{FloodFillPolicy, GreedyFoodPolicy, Policy, RandomPolicy};
+#[cfg(feature = "prometheus")]
+pub use prometheus::PrometheusSink;
+pub use ratings::{Rating, Ratings};
+#[cfg(feature = "cli")]
+pub use remotesnake::RemoteSnake;
+pub use replay::{Frame, Replay, SnakeFrame};
+pub use scenariosuite::ScenarioSuite;
+pub use scheduler::{PoolScheduler, RayonScheduler, Scheduler, SerialScheduler};
+pub use shardedwrapper::ShardedGameWrapper;
+pub use statedelta::{SnakeDelta, StateDelta};
+pub use symmetry::{OrientationMode, SymmetryTransform, D4};
+pub use territory::{analyze, Space};
+pub use tournament::Tournament;
 
-use pyo3::prelude::{pymodule, PyModule, PyResult, Python};
+use pyo3::prelude::{pymodule, wrap_pyfunction, PyModule, PyResult, Python};
 
 // The name of the module must be the same as the rust package name
 #[pymodule]
 fn rust(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<GameWrapper>()?;
+    m.add_class::<GameHandle>()?;
+    m.add_class::<PettingZooWrapper>()?;
+    m.add_class::<Tournament>()?;
+    m.add_class::<ScenarioSuite>()?;
+    m.add_class::<ShardedGameWrapper>()?;
+    m.add_function(wrap_pyfunction!(replay::replay_to_gif, m)?)?;
+    m.add_function(wrap_pyfunction!(replay::replay_to_board, m)?)?;
+    #[cfg(feature = "compression")]
+    m.add_function(wrap_pyfunction!(compression::decompress_obs, m)?)?;
     Ok(())
 }
@@ -0,0 +1,59 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::gameinstance::DeathReason;
+
+// Aggregates over the learning snake of every env after one step, cheap enough to hand out
+// every step instead of fetching the info arrays
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepMetrics {
+    // Steps taken so far, this one included
+    pub step: u64,
+    pub n_envs: u32,
+    pub episodes_finished: u32,
+    pub wins: u32,
+    pub draws: u32,
+    // Episodes finished this step by DeathReason as u32, None counts survivors
+    pub death_reasons: [u32; DeathReason::ALL.len()],
+    // Over every env, a finished episode at its last turn
+    pub mean_turn: f64,
+    // Over the episodes finished this step, 0 without any
+    pub mean_episode_turns: f64,
+    // Envs that started a new episode within the step
+    pub env_resets: u32,
+    // Wall time of the step, and the time of each phase summed over envs
+    pub step_secs: f64,
+    pub sim_secs: f64,
+    pub obs_write_secs: f64,
+    pub reset_secs: f64,
+}
+
+impl StepMetrics {
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("step", self.step)?;
+        dict.set_item("n_envs", self.n_envs)?;
+        dict.set_item("episodes_finished", self.episodes_finished)?;
+        dict.set_item("wins", self.wins)?;
+        dict.set_item("draws", self.draws)?;
+        let reasons = PyDict::new(py);
+        for reason in DeathReason::ALL {
+            reasons.set_item(reason.name(), self.death_reasons[reason as usize])?;
+        }
+        dict.set_item("death_reasons", reasons)?;
+        dict.set_item("mean_turn", self.mean_turn)?;
+        dict.set_item("mean_episode_turns", self.mean_episode_turns)?;
+        dict.set_item("env_resets", self.env_resets)?;
+        dict.set_item("step_secs", self.step_secs)?;
+        dict.set_item("sim_secs", self.sim_secs)?;
+        dict.set_item("obs_write_secs", self.obs_write_secs)?;
+        dict.set_item("reset_secs", self.reset_secs)?;
+        Ok(dict)
+    }
+}
+
+// Receives the StepMetrics of every step, e.g. to forward them to TensorBoard or W&B. It runs
+// on the stepping thread once the step is finished, so it should return quickly
+pub trait MetricsSink: Send {
+    fn record(&mut self, metrics: &StepMetrics);
+}
@@ -0,0 +1,35 @@
+use serde_json::{json, Value};
+
+use crate::gameinstance::{field, uint, HAZARD_DAMAGE};
+
+// An official move request as a GameInstance::load_scenario position and its hazard damage.
+// The asking snake comes first, then the others in request order, and rows are flipped since
+// the engine's y axis points up
+pub fn request_scenario(request: &Value) -> Result<(Value, usize), String> {
+    let board = field(request, "board")?;
+    let height = uint(board, "height")?;
+    let flip = |v: &Value| -> Result<Value, String> {
+        let points = v.as_array().ok_or("points are not a list")?;
+        let flipped = points.iter().map(|p| Ok(json!({ "x": uint(p, "x")?, "y": height as i64 - 1 - uint(p, "y")? as i64 }))).collect::<Result<Vec<_>, String>>()?;
+        Ok(Value::from(flipped))
+    };
+    let you = field(field(request, "you")?, "id")?;
+    let snakes = field(board, "snakes")?.as_array().ok_or("snakes is not a list")?;
+    let ordered: Vec<&Value> = snakes.iter().filter(|s| s.get("id") == Some(you)).chain(snakes.iter().filter(|s| s.get("id") != Some(you))).collect();
+    if ordered.first().map_or(true, |s| s.get("id") != Some(you)) {
+        return Err("you is not one of the board's snakes".to_string());
+    }
+    let snakes = ordered.into_iter().map(|s| Ok(json!({ "health": field(s, "health")?, "body": flip(field(s, "body")?)? }))).collect::<Result<Vec<_>, String>>()?;
+    let scenario = json!({
+        "turn": uint(request, "turn")?,
+        "board": {
+            "width": uint(board, "width")?,
+            "height": height,
+            "food": flip(field(board, "food")?)?,
+            "hazards": flip(board.get("hazards").unwrap_or(&json!([])))?,
+            "snakes": snakes,
+        },
+    });
+    let hazard_damage = request.pointer("/game/ruleset/settings/hazardDamagePerTurn").and_then(Value::as_u64).map_or(HAZARD_DAMAGE, |d| d as usize);
+    Ok((scenario, hazard_damage))
+}
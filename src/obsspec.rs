@@ -0,0 +1,437 @@
+use serde_json::{json, Value};
+
+use crate::gameinstance::{boolean, field, uint};
+
+// One-hot alive count layers cover at least this many snakes, so games of up to 8 keep the
+// original 17 base layers
+pub const MIN_MAX_SNAKES: usize = 8;
+// Turns the turn layer saturates at when episodes have no max_turns
+pub const TURN_HORIZON: u32 = 500;
+
+// Base layers as (name, f32 normalization scale). The alive count layers follow them
+const BASE_LAYERS: [(&str, &str); 10] = [
+    ("health", "100"),
+    ("body", "1"),
+    ("segment_index", "snake_length"),
+    ("head_larger", "1"),
+    ("food", "1"),
+    ("board", "1"),
+    ("ego_head", "1"),
+    ("double_tail", "1"),
+    ("longer_diff", "ego_length"),
+    ("shorter_diff", "ego_length"),
+];
+
+// Base layers summing what is written to a tile: segment counts and the values carried by
+// every segment. The other base layers mark one thing per tile
+const ADDED_BASE_LAYERS: [&str; 4] = ["body", "segment_index", "longer_diff", "shorter_diff"];
+
+// How a layer combines the values written to one tile within a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellWrite {
+    // Sums over stacked segments and corpses, saturating at 255 in u8 observations
+    Add,
+    // The largest value written wins, so stacked snakes don't depend on iteration order
+    Set,
+}
+
+impl CellWrite {
+    // Returns whether the u8 sum was clipped at 255
+    pub fn apply_u8(self, cell: &mut u8, val: u8) -> bool {
+        match self {
+            CellWrite::Add => {
+                let (sum, overflowed) = cell.overflowing_add(val);
+                *cell = if overflowed { u8::MAX } else { sum };
+                overflowed
+            }
+            CellWrite::Set => {
+                *cell = std::cmp::max(*cell, val);
+                false
+            }
+        }
+    }
+
+    pub fn apply_f32(self, cell: &mut f32, val: f32) {
+        match self {
+            CellWrite::Add => *cell += val,
+            CellWrite::Set => *cell = cell.max(val),
+        }
+    }
+}
+
+// Optional layers, appended after the base and alive count layers in declaration order
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtraLayer {
+    Corpse,
+    Hazard,
+    Ally,
+    Territory,
+    Distance,
+    Turn,
+    Health,
+    Wall,
+    HazardDamage,
+    Healing,
+    FoodValue,
+    HeadToHead,
+    Longest,
+    LengthRank,
+}
+
+impl ExtraLayer {
+    fn name(&self) -> &'static str {
+        match self {
+            ExtraLayer::Corpse => "corpse",
+            ExtraLayer::Hazard => "hazard",
+            ExtraLayer::Ally => "ally",
+            ExtraLayer::Territory => "territory",
+            ExtraLayer::Distance => "distance",
+            ExtraLayer::Turn => "turn",
+            ExtraLayer::Health => "ego_health",
+            ExtraLayer::Wall => "wall",
+            ExtraLayer::HazardDamage => "hazard_damage",
+            ExtraLayer::Healing => "healing",
+            ExtraLayer::FoodValue => "food_value",
+            ExtraLayer::HeadToHead => "head_to_head",
+            ExtraLayer::Longest => "longest",
+            ExtraLayer::LengthRank => "length_rank",
+        }
+    }
+
+    fn write(&self) -> CellWrite {
+        match self {
+            ExtraLayer::Corpse | ExtraLayer::Ally => CellWrite::Add,
+            _ => CellWrite::Set,
+        }
+    }
+
+    fn scale(&self) -> &'static str {
+        match self {
+            ExtraLayer::Corpse => "corpse_turns",
+            ExtraLayer::Hazard => "1",
+            ExtraLayer::Ally => "1",
+            ExtraLayer::Territory => "1",
+            ExtraLayer::Distance => "255",
+            ExtraLayer::Turn => "255",
+            ExtraLayer::Health => "100",
+            ExtraLayer::Wall => "1",
+            ExtraLayer::HazardDamage => "100",
+            ExtraLayer::Healing => "100",
+            ExtraLayer::FoodValue => "1",
+            ExtraLayer::HeadToHead => "2",
+            ExtraLayer::Longest => "1",
+            ExtraLayer::LengthRank => "max_snakes",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ObsDtype {
+    // Raw counts and values
+    #[default]
+    U8,
+    // Per-layer normalized values
+    F32,
+}
+
+impl ObsDtype {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObsDtype::U8 => "uint8",
+            ObsDtype::F32 => "float32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "uint8" => Some(ObsDtype::U8),
+            "float32" => Some(ObsDtype::F32),
+            _ => None,
+        }
+    }
+}
+
+// Where the board sits on the canvas
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Framing {
+    // Centered on the ego head, on a square canvas that fits the board from any head position
+    #[default]
+    Egocentric,
+    // The board as it is from the canvas' top left corner, on a canvas the size of the largest
+    // board. The ego head is only marked on its layer
+    Absolute,
+}
+
+impl Framing {
+    pub const NAMES: [&'static str; 2] = ["egocentric", "absolute"];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Framing::Egocentric => "egocentric",
+            Framing::Absolute => "absolute",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "egocentric" => Some(Framing::Egocentric),
+            "absolute" => Some(Framing::Absolute),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObsSpec {
+    // Canvas size, see Framing
+    pub layer_width: usize,
+    pub layer_height: usize,
+    // Keep eliminated snakes visible on the corpse layer for this many turns (0 disables it)
+    pub corpse_turns: u32,
+    // Alive snake counts as one-hot layers over 2..=max_snakes, or with alive_count_scalar a
+    // single layer of the count scaled by max_snakes
+    pub max_snakes: usize,
+    pub alive_count_scalar: bool,
+    pub hazard_layer: bool,
+    // Marks the bodies of squadmates, which the base layers don't tell apart from enemies
+    pub ally_layer: bool,
+    // Tiles the ego snake reaches strictly before every other snake
+    pub territory_layer: bool,
+    // BFS moves from the ego head around bodies, capped at 255 and 255 where unreachable
+    pub distance_layer: bool,
+    // Broadcast over the whole board: the turn as a 0..=255 fraction of max_turns (or
+    // TURN_HORIZON without a limit) and the ego snake's health
+    pub turn_layer: bool,
+    pub health_layer: bool,
+    // Impassable tiles of maze maps
+    pub wall_layer: bool,
+    // Damage a head takes on each tile, stacked snail trails included
+    pub hazard_damage_layer: bool,
+    // Health restored on healing pool tiles
+    pub healing_layer: bool,
+    // Segments each food grows a snake by, 0 on super food that only restores health
+    pub food_value_layer: bool,
+    // Tiles next to enemy heads: 1 where the ego snake would win a head-to-head there next turn,
+    // 2 where it would lose or trade, which wins over 1 next to several heads
+    pub head_to_head_layer: bool,
+    // Broadcast over the whole board: whether the ego snake is strictly longer than every other
+    // living snake, and 1 + the number of living snakes longer than it
+    pub longest_layer: bool,
+    pub length_rank_layer: bool,
+    pub dtype: ObsDtype,
+    pub framing: Framing,
+}
+
+impl ObsSpec {
+    pub fn new(board_width: u32, board_height: u32, corpse_turns: u32, hazard_layer: bool, ally_layer: bool, dtype: ObsDtype) -> Self {
+        let canvas = canvas_size(board_width, board_height);
+        Self {
+            layer_width: canvas,
+            layer_height: canvas,
+            corpse_turns,
+            max_snakes: MIN_MAX_SNAKES,
+            alive_count_scalar: false,
+            hazard_layer,
+            ally_layer,
+            territory_layer: false,
+            distance_layer: false,
+            turn_layer: false,
+            health_layer: false,
+            wall_layer: false,
+            hazard_damage_layer: false,
+            healing_layer: false,
+            food_value_layer: false,
+            head_to_head_layer: false,
+            longest_layer: false,
+            length_rank_layer: false,
+            dtype,
+            framing: Framing::Egocentric,
+        }
+    }
+
+    // A spec with the given framing, sized to boards up to board_width x board_height
+    pub fn framed(self, framing: Framing, board_width: u32, board_height: u32) -> Self {
+        let (layer_width, layer_height) = match framing {
+            Framing::Egocentric => (canvas_size(board_width, board_height), canvas_size(board_width, board_height)),
+            Framing::Absolute => (board_width as usize, board_height as usize),
+        };
+        Self { layer_width, layer_height, framing, ..self }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "layer_width": self.layer_width,
+            "layer_height": self.layer_height,
+            "corpse_turns": self.corpse_turns,
+            "max_snakes": self.max_snakes,
+            "alive_count_scalar": self.alive_count_scalar,
+            "hazard_layer": self.hazard_layer,
+            "ally_layer": self.ally_layer,
+            "territory_layer": self.territory_layer,
+            "distance_layer": self.distance_layer,
+            "turn_layer": self.turn_layer,
+            "health_layer": self.health_layer,
+            "wall_layer": self.wall_layer,
+            "hazard_damage_layer": self.hazard_damage_layer,
+            "healing_layer": self.healing_layer,
+            "food_value_layer": self.food_value_layer,
+            "head_to_head_layer": self.head_to_head_layer,
+            "longest_layer": self.longest_layer,
+            "length_rank_layer": self.length_rank_layer,
+            "dtype": self.dtype.name(),
+            "framing": self.framing.name(),
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let dtype = field(v, "dtype")?.as_str().and_then(ObsDtype::from_name).ok_or("unknown dtype")?;
+        Ok(Self {
+            layer_width: uint(v, "layer_width")? as usize,
+            layer_height: uint(v, "layer_height")? as usize,
+            corpse_turns: uint(v, "corpse_turns")? as u32,
+            max_snakes: uint(v, "max_snakes")? as usize,
+            alive_count_scalar: boolean(v, "alive_count_scalar")?,
+            hazard_layer: boolean(v, "hazard_layer")?,
+            ally_layer: boolean(v, "ally_layer")?,
+            territory_layer: boolean(v, "territory_layer")?,
+            distance_layer: boolean(v, "distance_layer")?,
+            turn_layer: boolean(v, "turn_layer")?,
+            health_layer: boolean(v, "health_layer")?,
+            wall_layer: boolean(v, "wall_layer")?,
+            hazard_damage_layer: boolean(v, "hazard_damage_layer")?,
+            healing_layer: boolean(v, "healing_layer")?,
+            food_value_layer: boolean(v, "food_value_layer")?,
+            head_to_head_layer: boolean(v, "head_to_head_layer")?,
+            longest_layer: boolean(v, "longest_layer")?,
+            length_rank_layer: boolean(v, "length_rank_layer")?,
+            dtype,
+            framing: field(v, "framing")?.as_str().and_then(Framing::parse).ok_or("unknown framing")?,
+        })
+    }
+
+    fn extra_layers(&self) -> Vec<ExtraLayer> {
+        let mut layers = Vec::new();
+        if self.corpse_turns > 0 {
+            layers.push(ExtraLayer::Corpse);
+        }
+        if self.hazard_layer {
+            layers.push(ExtraLayer::Hazard);
+        }
+        if self.ally_layer {
+            layers.push(ExtraLayer::Ally);
+        }
+        if self.territory_layer {
+            layers.push(ExtraLayer::Territory);
+        }
+        if self.distance_layer {
+            layers.push(ExtraLayer::Distance);
+        }
+        if self.turn_layer {
+            layers.push(ExtraLayer::Turn);
+        }
+        if self.health_layer {
+            layers.push(ExtraLayer::Health);
+        }
+        if self.wall_layer {
+            layers.push(ExtraLayer::Wall);
+        }
+        if self.hazard_damage_layer {
+            layers.push(ExtraLayer::HazardDamage);
+        }
+        if self.healing_layer {
+            layers.push(ExtraLayer::Healing);
+        }
+        if self.food_value_layer {
+            layers.push(ExtraLayer::FoodValue);
+        }
+        if self.head_to_head_layer {
+            layers.push(ExtraLayer::HeadToHead);
+        }
+        if self.longest_layer {
+            layers.push(ExtraLayer::Longest);
+        }
+        if self.length_rank_layer {
+            layers.push(ExtraLayer::LengthRank);
+        }
+        layers
+    }
+
+    pub fn layer(&self, layer: ExtraLayer) -> Option<usize> {
+        self.extra_layers().iter().position(|&l| l == layer).map(|i| self.base_layers() + i)
+    }
+
+    fn alive_layers(&self) -> usize {
+        if self.alive_count_scalar {
+            1
+        } else {
+            self.max_snakes - 1
+        }
+    }
+
+    // Base layers, alive count layers included
+    fn base_layers(&self) -> usize {
+        BASE_LAYERS.len() + self.alive_layers()
+    }
+
+    // The layer showing how many snakes are alive, with its value and scale. None for the one-hot
+    // layers of solo games and lone survivors, which stay empty
+    pub fn alive_layer(&self, alive: usize) -> Option<(usize, u8, f32)> {
+        if self.alive_count_scalar {
+            Some((BASE_LAYERS.len(), std::cmp::min(alive, 255) as u8, self.max_snakes as f32))
+        } else if alive >= 2 {
+            Some((BASE_LAYERS.len() + alive - 2, 1, 1.0))
+        } else {
+            None
+        }
+    }
+
+    // (name, scale) of every layer in tensor order
+    pub fn layers(&self) -> Vec<(String, String)> {
+        let mut layers: Vec<_> = BASE_LAYERS.iter().map(|&(name, scale)| (name.to_string(), scale.to_string())).collect();
+        if self.alive_count_scalar {
+            layers.push(("alive_count".to_string(), self.max_snakes.to_string()));
+        } else {
+            for alive in 2..=self.max_snakes {
+                layers.push((format!("alive_count_{}", alive), "1".to_string()));
+            }
+        }
+        for layer in self.extra_layers() {
+            layers.push((layer.name().to_string(), layer.scale().to_string()));
+        }
+        layers
+    }
+
+    // Write semantics of every layer in tensor order
+    pub fn writes(&self) -> Vec<CellWrite> {
+        let base = BASE_LAYERS.iter().map(|(name, _)| if ADDED_BASE_LAYERS.contains(name) { CellWrite::Add } else { CellWrite::Set });
+        let alive = std::iter::repeat(CellWrite::Set).take(self.alive_layers());
+        base.chain(alive).chain(self.extra_layers().iter().map(ExtraLayer::write)).collect()
+    }
+
+    // Every layer only changes on the tiles that changed on the board, so a frame can be shifted
+    // with the head and patched. Territory, distance and the broadcast layers change all over the
+    // board, snail trails wear off everywhere at once, and head-to-head tiles move around the heads
+    pub fn patchable(&self) -> bool {
+        !(self.territory_layer
+            || self.distance_layer
+            || self.turn_layer
+            || self.health_layer
+            || self.hazard_damage_layer
+            || self.head_to_head_layer
+            || self.longest_layer
+            || self.length_rank_layer)
+    }
+
+    pub fn num_layers(&self) -> usize {
+        self.base_layers() + self.extra_layers().len()
+    }
+
+    pub fn obs_size(&self) -> usize {
+        self.num_layers() * self.layer_width * self.layer_height
+    }
+}
+
+// Side of the square egocentric canvas that fits a board from any head position
+pub fn canvas_size(board_width: u32, board_height: u32) -> usize {
+    2 * std::cmp::max(board_width, board_height) as usize + 1
+}
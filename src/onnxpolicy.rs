@@ -0,0 +1,46 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use tract_onnx::prelude::*;
+
+// Exported policy snapshot. Takes a (batch, channels, width, height) float32 input and
+// returns (batch, 4) action logits
+pub struct OnnxPolicy {
+    model: TypedRunnableModel<TypedModel>,
+    deterministic: bool,
+}
+
+impl OnnxPolicy {
+    pub fn load(path: &str, deterministic: bool) -> TractResult<Self> {
+        let model = tract_onnx::onnx().model_for_path(path)?.into_optimized()?.into_runnable()?;
+        Ok(Self { model, deterministic })
+    }
+
+    // An exported snapshot already in memory, e.g. sent over by the training process
+    pub fn from_bytes(bytes: &[u8], deterministic: bool) -> TractResult<Self> {
+        let model = tract_onnx::onnx().model_for_read(&mut &bytes[..])?.into_optimized()?.into_runnable()?;
+        Ok(Self { model, deterministic })
+    }
+
+    // Swaps in a newer export of the same policy. The old model is kept if the bytes don't load
+    pub fn update_weights(&mut self, bytes: &[u8]) -> TractResult<()> {
+        self.model = Self::from_bytes(bytes, self.deterministic)?.model;
+        Ok(())
+    }
+
+    pub fn act<R: Rng>(&self, obs: Vec<f32>, shape: [usize; 4], rng: &mut R) -> TractResult<Vec<u8>> {
+        let input: Tensor = tract_ndarray::Array4::from_shape_vec(shape, obs)?.into();
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let logits = outputs[0].to_array_view::<f32>()?;
+        let mut actions = Vec::with_capacity(shape[0]);
+        for row in logits.outer_iter() {
+            let action = if self.deterministic {
+                row.iter().enumerate().fold(0, |best, (i, &l)| if l > row[best] { i } else { best })
+            } else {
+                let max = row.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                WeightedIndex::new(row.iter().map(|&l| (l - max).exp()))?.sample(rng)
+            };
+            actions.push(action as u8);
+        }
+        Ok(actions)
+    }
+}
@@ -0,0 +1,137 @@
+use pyo3::prelude::*;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+#[cfg(feature = "onnx")]
+use crate::onnxpolicy::OnnxPolicy;
+use crate::ratings::Ratings;
+
+pub enum PoolPolicy {
+    // Called with a (batch, channels, width, height) observation array, returns one action per row
+    Callback(PyObject),
+    // Runs entirely in Rust, see add_onnx_opponent
+    #[cfg(feature = "onnx")]
+    Onnx(OnnxPolicy),
+}
+
+// The learner's record against one opponent, counting each episode the opponent played in once
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Matchup {
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+}
+
+impl Matchup {
+    // Draws count half, and an opponent that hasn't played yet is an even match
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            0.5
+        } else {
+            (self.wins as f64 + 0.5 * self.draws as f64) / self.games as f64
+        }
+    }
+}
+
+// How entries are picked, always scaled by their weight. The PFSP modes (prioritized fictitious
+// self-play) favor opponents by the learner's win rate p against them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Sampling {
+    #[default]
+    Weight,
+    // (1 - p)^power, mostly the opponents the learner still loses to
+    PfspHard(f64),
+    // p * (1 - p), the opponents closest to an even match
+    PfspVariance,
+}
+
+pub struct PoolEntry {
+    pub name: String,
+    pub policy: PoolPolicy,
+    pub weight: f64,
+    pub matchup: Matchup,
+}
+
+impl PoolEntry {
+    pub fn priority(&self, sampling: Sampling) -> f64 {
+        let p = self.matchup.win_rate();
+        match sampling {
+            Sampling::Weight => self.weight,
+            Sampling::PfspHard(power) => self.weight * (1.0 - p).powf(power),
+            Sampling::PfspVariance => self.weight * p * (1.0 - p),
+        }
+    }
+}
+
+// Frozen policies that take over the non-learning player slots, sampled per episode
+#[derive(Default)]
+pub struct OpponentPool {
+    entries: Vec<PoolEntry>,
+    sampling: Sampling,
+    // Kept when the pool is cleared, an opponent added again under its name keeps its rating
+    ratings: Ratings,
+}
+
+impl OpponentPool {
+    pub fn add(&mut self, name: String, policy: PoolPolicy, weight: f64) -> usize {
+        self.entries.push(PoolEntry { name, policy, weight, matchup: Matchup::default() });
+        self.entries.len() - 1
+    }
+
+    pub fn set_weight(&mut self, index: usize, weight: f64) -> bool {
+        match self.entries.get_mut(index) {
+            Some(entry) => {
+                entry.weight = weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_sampling(&mut self, sampling: Sampling) {
+        self.sampling = sampling;
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        self.sampling
+    }
+
+    // One finished episode of the learner against the entry
+    pub fn record(&mut self, index: usize, won: bool, draw: bool) {
+        let entry = &mut self.entries[index];
+        entry.matchup.games += 1;
+        entry.matchup.wins += won as u32;
+        entry.matchup.draws += draw as u32;
+        let score = if won { 1.0 } else if draw { 0.5 } else { 0.0 };
+        self.ratings.record(&entry.name, score);
+    }
+
+    pub fn ratings(&self) -> &Ratings {
+        &self.ratings
+    }
+
+    pub fn ratings_mut(&mut self) -> &mut Ratings {
+        &mut self.ratings
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[PoolEntry] {
+        &self.entries
+    }
+
+    // None if the pool is empty or every weight is zero. Falls back to the weights alone when
+    // PFSP zeroes every priority, e.g. once the learner beats the whole pool
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        let dist = WeightedIndex::new(self.entries.iter().map(|e| e.priority(self.sampling)))
+            .or_else(|_| WeightedIndex::new(self.entries.iter().map(|e| e.weight)))
+            .ok()?;
+        Some(dist.sample(rng))
+    }
+}
@@ -0,0 +1,165 @@
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::gameinstance::DeathReason;
+use crate::gamewrapper::GameWrapper;
+use crate::obsspec::ObsDtype;
+
+type StepResult<'py> = (&'py PyDict, &'py PyDict, &'py PyDict, &'py PyDict, &'py PyDict);
+
+// PettingZoo parallel API over a single game with one agent per snake. Agents are named
+// snake_<slot> and leave `agents` once eliminated; every snake plays until the game is over
+#[pyclass]
+pub struct PettingZooWrapper {
+    game: GameWrapper,
+    n_agents: usize,
+    // Slots still in play this episode, empty until reset
+    live: Vec<usize>,
+}
+
+fn agent_name(slot: usize) -> String {
+    format!("snake_{}", slot)
+}
+
+#[pymethods]
+impl PettingZooWrapper {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (n_agents, fixed_orientation=false, n_stack=1, float_obs=false, board_width=11, board_height=11, max_turns=0))]
+    pub fn new(
+        py: Python<'_>,
+        n_agents: usize,
+        fixed_orientation: bool,
+        n_stack: usize,
+        float_obs: bool,
+        board_width: u32,
+        board_height: u32,
+        max_turns: u32,
+    ) -> PyResult<Self> {
+        let mut game = GameWrapper::new(py, 1, n_agents, fixed_orientation, false, 0, n_stack, float_obs, false, board_width, board_height, None, max_turns, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, None, false, false, "egocentric", None)?;
+        game.set_end_on_learner_death(false);
+        Ok(Self { game, n_agents, live: Vec::new() })
+    }
+
+    #[getter]
+    fn metadata<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let metadata = PyDict::new(py);
+        metadata.set_item("name", "battlesnake_v0")?;
+        metadata.set_item("render_modes", Vec::<String>::new())?;
+        metadata.set_item("is_parallelizable", true)?;
+        Ok(metadata)
+    }
+
+    #[getter]
+    fn possible_agents(&self) -> Vec<String> {
+        (0..self.n_agents).map(agent_name).collect()
+    }
+
+    #[getter]
+    fn agents(&self) -> Vec<String> {
+        self.live.iter().map(|&slot| agent_name(slot)).collect()
+    }
+
+    #[getter]
+    fn num_agents(&self) -> usize {
+        self.live.len()
+    }
+
+    #[getter]
+    fn max_num_agents(&self) -> usize {
+        self.n_agents
+    }
+
+    fn observation_space(&self, py: Python<'_>, agent: &str) -> PyResult<PyObject> {
+        self.slot(agent)?;
+        let numpy = py.import("numpy")?;
+        let (high, dtype) = match self.game.obs_dtype() {
+            ObsDtype::U8 => (255.0, numpy.getattr("uint8")?),
+            ObsDtype::F32 => (f64::INFINITY, numpy.getattr("float32")?),
+        };
+        let shape = self.game.slot_obs_shape();
+        Ok(py.import("gymnasium.spaces")?.getattr("Box")?.call1((0.0, high, (shape[0], shape[1], shape[2]), dtype))?.into())
+    }
+
+    fn action_space(&self, py: Python<'_>, agent: &str) -> PyResult<PyObject> {
+        self.slot(agent)?;
+        Ok(py.import("gymnasium.spaces")?.getattr("Discrete")?.call1((4,))?.into())
+    }
+
+    // Returns (observations, infos). seed and options are accepted for API compatibility and
+    // currently ignored
+    #[pyo3(signature = (seed=None, options=None))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: Option<u64>, options: Option<&PyDict>) -> PyResult<(&'py PyDict, &'py PyDict)> {
+        let _ = (seed, options);
+        self.game.reset()?;
+        self.live = (0..self.n_agents).filter(|&slot| self.game.info(slot, 0).alive).collect();
+        let observations = PyDict::new(py);
+        let infos = PyDict::new(py);
+        for &slot in &self.live {
+            observations.set_item(agent_name(slot), self.game.slot_obs(py, slot, 0)?)?;
+            infos.set_item(agent_name(slot), PyDict::new(py))?;
+        }
+        Ok((observations, infos))
+    }
+
+    // Takes {agent: action} for every live agent and returns (observations, rewards,
+    // terminations, truncations, infos) for the agents that were live before the step. Rewards
+    // are +1 for winning, -1 for being eliminated and 0 otherwise, draws included. When the game
+    // ends the observations are the terminal ones, the next episode starts on reset
+    fn step<'py>(&mut self, py: Python<'py>, actions: &PyDict) -> PyResult<StepResult<'py>> {
+        if self.live.is_empty() {
+            return Err(PyValueError::new_err("no live agents, call reset() first"));
+        }
+        for &slot in &self.live {
+            let action: u8 = match actions.get_item(agent_name(slot)) {
+                Some(action) => action.extract()?,
+                None => return Err(PyValueError::new_err(format!("missing action for {}", agent_name(slot)))),
+            };
+            if action >= 4 {
+                return Err(PyValueError::new_err(format!("action {} for {} is not in 0..4", action, agent_name(slot))));
+            }
+            self.game.set_action(slot, 0, action);
+        }
+        self.game.step(py)?;
+
+        let (observations, rewards, terminations, truncations, infos) = (PyDict::new(py), PyDict::new(py), PyDict::new(py), PyDict::new(py), PyDict::new(py));
+        let mut still_live = Vec::new();
+        for &slot in &self.live {
+            let info = self.game.info(slot, 0);
+            let name = agent_name(slot);
+            let truncated = info.max_turn_reached;
+            let terminated = !truncated && (!info.alive || info.over);
+            let reward = info.reward();
+            observations.set_item(&name, self.game.slot_obs(py, slot, 0)?)?;
+            rewards.set_item(&name, reward)?;
+            terminations.set_item(&name, terminated)?;
+            truncations.set_item(&name, truncated)?;
+            let agent_info = PyDict::new(py);
+            agent_info.set_item("length", info.length)?;
+            agent_info.set_item("turn", info.turn)?;
+            agent_info.set_item("rank", info.rank)?;
+            agent_info.set_item("kills", info.kills)?;
+            agent_info.set_item("food_eaten", info.food_eaten)?;
+            agent_info.set_item("game_id", info.game_id)?;
+            if !info.alive {
+                agent_info.set_item("death_turn", info.death_turn)?;
+            }
+            agent_info.set_item("death_reason", DeathReason::ALL[info.death_reason as usize].name())?;
+            infos.set_item(&name, agent_info)?;
+            if !terminated && !truncated {
+                still_live.push(slot);
+            }
+        }
+        self.live = still_live;
+        Ok((observations, rewards, terminations, truncations, infos))
+    }
+}
+
+impl PettingZooWrapper {
+    fn slot(&self, agent: &str) -> PyResult<usize> {
+        (0..self.n_agents)
+            .find(|&slot| agent_name(slot) == agent)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown agent '{}'", agent)))
+    }
+}
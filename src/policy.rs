@@ -0,0 +1,81 @@
+use rand::seq::SliceRandom;
+
+use crate::gameinstance::GameInstance;
+use crate::territory;
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// Controls one snake from the full game state and returns u, d, l or r in board coordinates.
+// One instance serves every env a GameWrapper steps in parallel, so implementations shouldn't
+// keep per-game state
+pub trait Policy: Send + Sync {
+    fn act(&self, game: &GameInstance, player_id: u32) -> char;
+}
+
+// Moves is_move_fatal allows, every move if none is left
+fn safe_moves(game: &GameInstance, player_id: u32) -> Vec<char> {
+    let safe: Vec<char> = MOVES.into_iter().filter(|&m| !game.is_move_fatal(player_id, m)).collect();
+    if safe.is_empty() {
+        MOVES.to_vec()
+    } else {
+        safe
+    }
+}
+
+// (tiles reachable, moves to the closest reachable food) from where the move puts the head
+fn outlook(game: &GameInstance, player_id: u32, m: char) -> (usize, u32) {
+    let (_, players, food, width, length, _, _) = game.get_state();
+    let next = match game.next_tile(players[&player_id].body[0], m) {
+        Some(next) => next,
+        None => return (0, u32::MAX),
+    };
+    let dist = territory::distances(players, game.get_walls(), width, length, next);
+    let area = dist.iter().filter(|&&d| d != u32::MAX).count();
+    let food = food.iter().map(|t| dist[(t.y * width + t.x) as usize]).min().unwrap_or(u32::MAX);
+    (area, food)
+}
+
+// Uniform over the moves that don't lose right away
+pub struct RandomPolicy;
+
+impl Policy for RandomPolicy {
+    fn act(&self, game: &GameInstance, player_id: u32) -> char {
+        *safe_moves(game, player_id).choose(&mut rand::thread_rng()).unwrap()
+    }
+}
+
+// Takes the safe move with the shortest path to food
+pub struct GreedyFoodPolicy;
+
+impl Policy for GreedyFoodPolicy {
+    fn act(&self, game: &GameInstance, player_id: u32) -> char {
+        safe_moves(game, player_id).into_iter().min_by_key(|&m| outlook(game, player_id, m).1).unwrap()
+    }
+}
+
+// Takes the safe move that keeps the most of the board reachable, heading for food on ties
+pub struct FloodFillPolicy;
+
+impl Policy for FloodFillPolicy {
+    fn act(&self, game: &GameInstance, player_id: u32) -> char {
+        safe_moves(game, player_id)
+            .into_iter()
+            .max_by_key(|&m| {
+                let (area, food) = outlook(game, player_id, m);
+                (area, std::cmp::Reverse(food))
+            })
+            .unwrap()
+    }
+}
+
+// The built-in baselines by the names Python uses for them
+pub fn scripted(name: &str) -> Option<Box<dyn Policy>> {
+    match name {
+        "random" => Some(Box::new(RandomPolicy)),
+        "greedy_food" => Some(Box::new(GreedyFoodPolicy)),
+        "flood_fill" => Some(Box::new(FloodFillPolicy)),
+        _ => None,
+    }
+}
+
+pub const SCRIPTED: [&str; 3] = ["random", "greedy_food", "flood_fill"];
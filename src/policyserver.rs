@@ -0,0 +1,67 @@
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::gamewrapper::GameWrapper;
+use crate::moverequest::request_scenario;
+use crate::onnxpolicy::OnnxPolicy;
+
+// Snake servers get 500ms per move by default, reading a request shouldn't take a fraction of it
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+// The move the policy makes as the asking snake, in the official API's words
+pub fn policy_move(template: &GameWrapper, policy: &OnnxPolicy, request: &Value) -> Result<&'static str, String> {
+    let (scenario, hazard_damage) = request_scenario(request)?;
+    let mut wrapper = template.with_position(&scenario, hazard_damage).map_err(|e| e.to_string())?;
+    let (obs, shape) = wrapper.model_obs_f32(0);
+    let action = policy.act(obs, shape, &mut rand::thread_rng()).map_err(|e| e.to_string())?[0];
+    wrapper.set_action(0, 0, action);
+    Ok(match wrapper.slot_move(0, 0).map_err(|e| e.to_string())? {
+        'u' => "up",
+        'd' => "down",
+        'l' => "left",
+        _ => "right",
+    })
+}
+
+// Answers one HTTP request of the official snake API: / describes the snake, /start and /end
+// are acknowledged and /move asks the policy. Move requests that can't be rendered get a 400
+// with the reason
+pub fn answer(mut stream: TcpStream, template: &GameWrapper, policy: &OnnxPolicy) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let (status, reply) = match (method, path.trim_end_matches('/')) {
+        ("GET", "") => ("200 OK", json!({ "apiversion": "1", "author": "gym-battlesnake", "color": "#2e86de", "head": "default", "tail": "default", "version": env!("CARGO_PKG_VERSION") })),
+        ("POST", "/start") | ("POST", "/end") => ("200 OK", json!({})),
+        ("POST", "/move") => match serde_json::from_slice::<Value>(&body).map_err(|e| e.to_string()).and_then(|request| policy_move(template, policy, &request)) {
+            Ok(m) => ("200 OK", json!({ "move": m })),
+            Err(e) => ("400 Bad Request", json!({ "error": e })),
+        },
+        _ => ("404 Not Found", json!({})),
+    };
+    let reply = reply.to_string();
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, reply.len(), reply)
+}
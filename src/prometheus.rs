@@ -0,0 +1,125 @@
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Instant;
+
+use crate::gameinstance::DeathReason;
+use crate::metrics::{MetricsSink, StepMetrics};
+
+// Running totals of every StepMetrics seen, and where the last scrape left off so rates cover
+// the time between scrapes
+struct Totals {
+    n_envs: u32,
+    steps: u64,
+    episodes: u64,
+    env_resets: u64,
+    death_reasons: [u64; DeathReason::ALL.len()],
+    step_secs: f64,
+    // Per Phase, summed over envs
+    phase_secs: [f64; 3],
+    last_scrape: (Instant, u64, u64),
+}
+
+impl Totals {
+    fn render(&mut self) -> String {
+        let now = Instant::now();
+        let (then, steps, episodes) = self.last_scrape;
+        let span = (now - then).as_secs_f64();
+        let rate = |count: u64| if span > 0.0 { count as f64 / span } else { 0.0 };
+        let (steps_per_sec, episodes_per_sec) = (rate(self.steps - steps), rate(self.episodes - episodes));
+        self.last_scrape = (now, self.steps, self.episodes);
+
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP battlesnake_{} {}\n# TYPE battlesnake_{} {}", name, help, name, kind);
+            for (labels, value) in samples {
+                let _ = writeln!(out, "battlesnake_{}{} {}", name, labels, value);
+            }
+        };
+        let one = |value: String| [(String::new(), value)];
+        metric("envs", "gauge", "Envs stepped together.", &one(self.n_envs.to_string()));
+        metric("steps_total", "counter", "Steps taken.", &one(self.steps.to_string()));
+        metric("env_steps_total", "counter", "Steps taken times envs.", &one((self.steps * self.n_envs as u64).to_string()));
+        metric("episodes_total", "counter", "Episodes of the learning snake finished.", &one(self.episodes.to_string()));
+        metric("env_resets_total", "counter", "Envs reset into a new episode within a step.", &one(self.env_resets.to_string()));
+        metric("steps_per_second", "gauge", "Steps per second since the previous scrape.", &one(steps_per_sec.to_string()));
+        metric("episodes_per_second", "gauge", "Episodes per second since the previous scrape.", &one(episodes_per_sec.to_string()));
+        let reasons: Vec<_> = DeathReason::ALL.iter().map(|r| (format!("{{reason=\"{}\"}}", r.name()), self.death_reasons[*r as usize].to_string())).collect();
+        metric("episode_deaths_total", "counter", "Finished episodes by how the learning snake died, none for survivors.", &reasons);
+        metric("step_seconds_total", "counter", "Wall time spent in steps.", &one(self.step_secs.to_string()));
+        let phases: Vec<_> = ["sim", "obs_write", "reset"].iter().zip(self.phase_secs).map(|(phase, secs)| (format!("{{phase=\"{}\"}}", phase), secs.to_string())).collect();
+        metric("phase_seconds_total", "counter", "Time per step phase, summed over envs.", &phases);
+        out
+    }
+}
+
+// Serves the totals of the StepMetrics it records in the Prometheus text format on /metrics.
+// The listener thread outlives the sink until its next request
+pub struct PrometheusSink {
+    totals: Arc<Mutex<Totals>>,
+    addr: SocketAddr,
+}
+
+impl PrometheusSink {
+    // addr like "0.0.0.0:9100", port 0 picks a free one, see local_addr
+    pub fn serve(addr: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let totals = Arc::new(Mutex::new(Totals {
+            n_envs: 0,
+            steps: 0,
+            episodes: 0,
+            env_resets: 0,
+            death_reasons: [0; DeathReason::ALL.len()],
+            step_secs: 0.0,
+            phase_secs: [0.0; 3],
+            last_scrape: (Instant::now(), 0, 0),
+        }));
+        let weak = Arc::downgrade(&totals);
+        std::thread::Builder::new().name("prometheus".to_string()).spawn(move || listen(listener, weak))?;
+        Ok(Self { totals, addr })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl MetricsSink for PrometheusSink {
+    fn record(&mut self, metrics: &StepMetrics) {
+        let mut totals = self.totals.lock().unwrap();
+        totals.n_envs = metrics.n_envs;
+        totals.steps = metrics.step;
+        totals.episodes += metrics.episodes_finished as u64;
+        totals.env_resets += metrics.env_resets as u64;
+        for (total, &count) in totals.death_reasons.iter_mut().zip(&metrics.death_reasons) {
+            *total += count as u64;
+        }
+        totals.step_secs += metrics.step_secs;
+        totals.phase_secs[0] += metrics.sim_secs;
+        totals.phase_secs[1] += metrics.obs_write_secs;
+        totals.phase_secs[2] += metrics.reset_secs;
+    }
+}
+
+fn listen(listener: TcpListener, totals: Weak<Mutex<Totals>>) {
+    for stream in listener.incoming() {
+        let totals = match totals.upgrade() {
+            Some(totals) => totals,
+            None => return,
+        };
+        // A client that hangs up early only costs its own response
+        if let Ok(stream) = stream {
+            let _ = respond(stream, &totals);
+        }
+    }
+}
+
+fn respond(mut stream: TcpStream, totals: &Mutex<Totals>) -> io::Result<()> {
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let (status, body) = if path == "/metrics" { ("200 OK", totals.lock().unwrap().render()) } else { ("404 Not Found", String::new()) };
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body)
+}
@@ -0,0 +1,104 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::gameinstance::{field, uint};
+
+pub const INITIAL_RATING: f64 = 1500.0;
+pub const DEFAULT_K: f64 = 32.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub games: u32,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self { rating: INITIAL_RATING, games: 0 }
+    }
+}
+
+impl Rating {
+    fn to_json(self) -> Value {
+        json!({ "rating": self.rating, "games": self.games })
+    }
+
+    fn from_json(v: &Value) -> Result<Self, String> {
+        let rating = field(v, "rating")?.as_f64().ok_or("rating is not a number")?;
+        Ok(Self { rating, games: uint(v, "games")? as u32 })
+    }
+}
+
+// Elo ratings of the learner and of pooled opponents, which are keyed by name so they carry
+// over when the same opponents are added again, e.g. after loading a pickled wrapper
+#[derive(Clone, Debug)]
+pub struct Ratings {
+    k: f64,
+    learner: Rating,
+    opponents: HashMap<String, Rating>,
+}
+
+impl Default for Ratings {
+    fn default() -> Self {
+        Self { k: DEFAULT_K, learner: Rating::default(), opponents: HashMap::new() }
+    }
+}
+
+impl Ratings {
+    // The largest change of a rating per game
+    pub fn set_k(&mut self, k: f64) {
+        self.k = k;
+    }
+
+    pub fn k(&self) -> f64 {
+        self.k
+    }
+
+    pub fn learner(&self) -> Rating {
+        self.learner
+    }
+
+    // An opponent that hasn't played yet starts at INITIAL_RATING
+    pub fn opponent(&self, name: &str) -> Rating {
+        self.opponents.get(name).copied().unwrap_or_default()
+    }
+
+    // One game of the learner against the named opponent, score being the learner's: 1 for a
+    // win, 0.5 for a draw and 0 for a loss
+    pub fn record(&mut self, name: &str, score: f64) {
+        let opponent = self.opponents.entry(name.to_string()).or_default();
+        let expected = 1.0 / (1.0 + 10f64.powf((opponent.rating - self.learner.rating) / 400.0));
+        let change = self.k * (score - expected);
+        self.learner.rating += change;
+        self.learner.games += 1;
+        opponent.rating -= change;
+        opponent.games += 1;
+    }
+
+    pub fn to_json(&self) -> Value {
+        let opponents: serde_json::Map<String, Value> = self.opponents.iter().map(|(name, r)| (name.clone(), r.to_json())).collect();
+        json!({ "k": self.k, "learner": self.learner.to_json(), "opponents": opponents })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let k = field(v, "k")?.as_f64().ok_or("k is not a number")?;
+        let opponents = field(v, "opponents")?
+            .as_object()
+            .ok_or("opponents is not a dict")?
+            .iter()
+            .map(|(name, r)| Ok((name.clone(), Rating::from_json(r)?)))
+            .collect::<Result<_, String>>()?;
+        Ok(Self { k, learner: Rating::from_json(field(v, "learner")?)?, opponents })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_json().to_string()).map_err(|e| e.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let v: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        Self::from_json(&v)
+    }
+}
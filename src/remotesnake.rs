@@ -0,0 +1,97 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use crate::gameinstance::{GameInstance, Tile};
+use crate::policy::Policy;
+
+// A snake server speaking the official Battlesnake API, asked over HTTP for every move. Only
+// /move is called, servers that need /start and /end to keep state won't play well
+pub struct RemoteSnake {
+    url: String,
+    agent: ureq::Agent,
+    timeout: Duration,
+}
+
+impl RemoteSnake {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Self { url: url.trim_end_matches('/').to_string(), agent: ureq::AgentBuilder::new().timeout(timeout).build(), timeout }
+    }
+
+    // The official move request for player_id. The engine's y axis points up, so rows are
+    // flipped and its "up" is the gym's 'u'
+    pub fn move_request(game: &GameInstance, player_id: u32, timeout: Duration) -> Value {
+        let (_, players, food, width, height, turn, hazards) = game.get_state();
+        let point = |t: &Tile| json!({ "x": t.x, "y": height - 1 - t.y });
+        let snake = |id: u32| {
+            let p = &players[&id];
+            json!({
+                "id": id.to_string(),
+                "name": format!("snake_{}", id),
+                "health": p.health,
+                "body": p.body.iter().map(point).collect::<Vec<_>>(),
+                "head": point(&p.body[0]),
+                "length": p.body.len(),
+                "latency": "0",
+                "shout": "",
+                "squad": p.squad.to_string(),
+                "customizations": { "color": "#888888", "head": "default", "tail": "default" },
+            })
+        };
+        let params = game.parameters();
+        json!({
+            "game": {
+                "id": game.get_game_id().to_string(),
+                "ruleset": {
+                    "name": "standard",
+                    "version": "gym-battlesnake",
+                    "settings": {
+                        "foodSpawnChance": (params.food_spawn_chance * 100.0).round() as u32,
+                        "minimumFood": params.minimum_food,
+                        "hazardDamagePerTurn": params.hazard_damage,
+                    },
+                },
+                "map": "standard",
+                "timeout": timeout.as_millis() as u64,
+                "source": "gym-battlesnake",
+            },
+            "turn": turn,
+            "board": {
+                "height": height,
+                "width": width,
+                "food": food.iter().map(point).collect::<Vec<_>>(),
+                // Stacked hazards are repeated like the official API does
+                "hazards": hazards
+                    .iter()
+                    .chain(game.get_trails().iter().flat_map(|(t, &stacks)| std::iter::repeat(t).take(stacks as usize)))
+                    .map(point)
+                    .collect::<Vec<_>>(),
+                "snakes": game.get_player_ids().into_iter().filter(|id| players[id].alive && !players[id].body.is_empty()).map(snake).collect::<Vec<_>>(),
+            },
+            "you": snake(player_id),
+        })
+    }
+}
+
+impl Policy for RemoteSnake {
+    fn act(&self, game: &GameInstance, player_id: u32) -> char {
+        // Like the official engine, a snake that times out or answers nonsense keeps going the
+        // way it went
+        let fallback = game.get_state().1[&player_id].move_dir;
+        let request = Self::move_request(game, player_id, self.timeout).to_string();
+        let answer = self
+            .agent
+            .post(&format!("{}/move", self.url))
+            .set("Content-Type", "application/json")
+            .send_string(&request)
+            .ok()
+            .and_then(|response| response.into_string().ok())
+            .and_then(|text| serde_json::from_str::<Value>(&text).ok());
+        match answer.as_ref().and_then(|v| v.get("move")).and_then(Value::as_str) {
+            Some("up") => 'u',
+            Some("down") => 'd',
+            Some("left") => 'l',
+            Some("right") => 'r',
+            _ => fallback,
+        }
+    }
+}
@@ -0,0 +1,421 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::fs::File;
+
+use crate::gameinstance::{boolean, field, tiles, uint, DeathReason, GameInstance, Tile};
+
+// Background, grid, food and hazard, then a (body, head) pair per snake color
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const FOOD: u8 = 2;
+const HAZARD: u8 = 3;
+const SNAKE_COLORS: [[u8; 3]; 8] = [
+    [0x2e, 0x86, 0xde],
+    [0xe6, 0x7e, 0x22],
+    [0x27, 0xae, 0x60],
+    [0x8e, 0x44, 0xad],
+    [0xf1, 0xc4, 0x0f],
+    [0x16, 0xa0, 0x85],
+    [0xd3, 0x54, 0x9a],
+    [0x7f, 0x8c, 0x8d],
+];
+
+// One snake on one turn. Eliminated snakes stay listed with the body they died with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnakeFrame {
+    pub id: u32,
+    pub body: Vec<Tile>,
+    pub health: u32,
+    pub alive: bool,
+    pub death_reason: DeathReason,
+    pub killed_by: Option<u32>,
+    // The turn the snake was eliminated on, None while it is alive
+    pub death_turn: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub turn: u32,
+    // In model slot order
+    pub snakes: Vec<SnakeFrame>,
+    pub food: Vec<Tile>,
+    // Snail trails included
+    pub hazards: Vec<Tile>,
+}
+
+impl Frame {
+    // The game's current position
+    pub fn of(game: &GameInstance) -> Self {
+        let (_, players, food, _, _, turn, hazards) = game.get_state();
+        let snakes = game
+            .get_player_ids()
+            .iter()
+            .map(|id| {
+                let p = &players[id];
+                SnakeFrame {
+                    id: *id,
+                    body: p.body.clone(),
+                    health: p.health as u32,
+                    alive: p.alive,
+                    death_reason: p.death_reason,
+                    killed_by: p.killed_by,
+                    death_turn: if p.alive { None } else { Some(p.death_turn) },
+                }
+            })
+            .collect();
+        Frame { turn, snakes, food: sorted(food.iter()), hazards: sorted(hazards.iter().chain(game.get_trails().keys())) }
+    }
+}
+
+// Every turn of one episode, from the starting position to the final one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub game_id: u32,
+    pub width: u32,
+    pub height: u32,
+    pub frames: Vec<Frame>,
+    // Set once the game is over, None on draws and unfinished episodes
+    pub winner: Option<u32>,
+}
+
+impl Replay {
+    // Starts from the game's current position
+    pub fn new(game: &GameInstance) -> Self {
+        let (_, _, _, width, height, _, _) = game.get_state();
+        let mut replay = Self { game_id: game.get_game_id(), width, height, frames: Vec::new(), winner: None };
+        replay.push(game);
+        replay
+    }
+
+    pub fn push(&mut self, game: &GameInstance) {
+        self.frames.push(Frame::of(game));
+        if game.is_over() {
+            self.winner = game.get_winner();
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let tiles = |tiles: &[Tile]| tiles.iter().map(|t| json!([t.x, t.y])).collect::<Vec<_>>();
+        let frames: Vec<_> = self
+            .frames
+            .iter()
+            .map(|f| {
+                let snakes: Vec<_> = f
+                    .snakes
+                    .iter()
+                    .map(|s| {
+                        json!({
+                            "id": s.id,
+                            "body": tiles(&s.body),
+                            "health": s.health,
+                            "alive": s.alive,
+                            "death_reason": s.death_reason as u32,
+                            "killed_by": s.killed_by,
+                            "death_turn": s.death_turn,
+                        })
+                    })
+                    .collect();
+                json!({ "turn": f.turn, "snakes": snakes, "food": tiles(&f.food), "hazards": tiles(&f.hazards) })
+            })
+            .collect();
+        json!({
+            "game_id": self.game_id,
+            "width": self.width,
+            "height": self.height,
+            "winner": self.winner,
+            "frames": frames,
+        })
+    }
+
+    pub fn from_json(v: &Value) -> Result<Self, String> {
+        let frames = field(v, "frames")?.as_array().ok_or("frames is not a list")?;
+        let frames = frames
+            .iter()
+            .map(|f| {
+                let snakes = field(f, "snakes")?.as_array().ok_or("snakes is not a list")?;
+                let snakes = snakes
+                    .iter()
+                    .map(|s| {
+                        let reason = uint(s, "death_reason")?;
+                        Ok(SnakeFrame {
+                            id: uint(s, "id")? as u32,
+                            body: tiles(field(s, "body")?)?,
+                            health: uint(s, "health")? as u32,
+                            alive: boolean(s, "alive")?,
+                            death_reason: *DeathReason::ALL.get(reason as usize).ok_or_else(|| format!("unknown death_reason {}", reason))?,
+                            killed_by: field(s, "killed_by")?.as_u64().map(|id| id as u32),
+                            // Replays recorded before death turns were kept read as unknown
+                            death_turn: s.get("death_turn").and_then(Value::as_u64).map(|turn| turn as u32),
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(Frame { turn: uint(f, "turn")? as u32, snakes, food: tiles(field(f, "food")?)?, hazards: tiles(field(f, "hazards")?)? })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Self {
+            game_id: uint(v, "game_id")? as u32,
+            width: uint(v, "width")? as u32,
+            height: uint(v, "height")? as u32,
+            frames,
+            winner: field(v, "winner")?.as_u64().map(|id| id as u32),
+        })
+    }
+
+    // The game and frames the way the official engine serves them to the board viewer
+    // (BattlesnakeOfficial/board): {"Game": ..., "Count": n, "Frames": [...]}. The engine's y
+    // axis points up, so rows are flipped
+    pub fn to_board_json(&self) -> Value {
+        let point = |t: &Tile| json!({ "X": t.x, "Y": self.height - 1 - t.y });
+        // The turn each snake was first seen eliminated, for replays without death turns
+        let mut death_turns: Vec<Option<u32>> = vec![None; self.frames.first().map_or(0, |f| f.snakes.len())];
+        let frames: Vec<_> = self
+            .frames
+            .iter()
+            .map(|f| {
+                let snakes: Vec<_> = f
+                    .snakes
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, s)| {
+                        let death = if s.alive {
+                            Value::Null
+                        } else {
+                            let turn = *death_turns[slot].get_or_insert(s.death_turn.unwrap_or(f.turn));
+                            json!({
+                                "Cause": board_cause(s.death_reason),
+                                "Turn": turn,
+                                "EliminatedBy": s.killed_by.map_or(String::new(), |id| id.to_string()),
+                            })
+                        };
+                        let [r, g, b] = SNAKE_COLORS[slot % SNAKE_COLORS.len()];
+                        json!({
+                            "ID": s.id.to_string(),
+                            "Name": format!("snake_{}", slot),
+                            "Body": s.body.iter().map(point).collect::<Vec<_>>(),
+                            "Health": s.health,
+                            "Death": death,
+                            "Color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                            "HeadType": "default",
+                            "TailType": "default",
+                            "Latency": "0",
+                            "Shout": "",
+                            "Squad": "",
+                            "Author": "",
+                            "IsBot": false,
+                            "IsEnvironment": false,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "Turn": f.turn,
+                    "Snakes": snakes,
+                    "Food": f.food.iter().map(point).collect::<Vec<_>>(),
+                    "Hazards": f.hazards.iter().map(point).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        json!({
+            "Game": {
+                "ID": self.game_id.to_string(),
+                "Status": "complete",
+                "Width": self.width,
+                "Height": self.height,
+                "Ruleset": { "name": "standard" },
+                "SnakeTimeout": 500,
+                "Source": "gym-battlesnake",
+            },
+            "Count": frames.len(),
+            "Frames": frames,
+        })
+    }
+
+    // Reads a game in the board viewer's format, as to_board_json writes it and the official
+    // engine exports it. Snakes get the ids 0.. in the order the first frame lists them, and
+    // segments off the board, left by a wall collision, are dropped. The winner is the last
+    // snake standing, if any
+    pub fn from_board_json(v: &Value) -> Result<Self, String> {
+        let game = field(v, "Game")?;
+        let (width, height) = (uint(game, "Width")? as u32, uint(game, "Height")? as u32);
+        let point = |p: &Value| -> Result<Option<Tile>, String> {
+            let coord = |key: &str| field(p, key)?.as_i64().ok_or_else(|| format!("{} is not an integer", key));
+            let (x, y) = (coord("X")?, coord("Y")?);
+            let on_board = (0..width as i64).contains(&x) && (0..height as i64).contains(&y);
+            Ok(if on_board { Some(Tile { x: x as u32, y: height - 1 - y as u32 }) } else { None })
+        };
+        let points = |v: &Value| -> Result<Vec<Tile>, String> {
+            let list = v.as_array().ok_or_else(|| format!("{} is not a list of points", v))?;
+            list.iter().filter_map(|p| point(p).transpose()).collect()
+        };
+        let frames = field(v, "Frames")?.as_array().ok_or("Frames is not a list")?;
+        let mut ids: Vec<String> = Vec::new();
+        let mut parsed = Vec::new();
+        for f in frames {
+            let snakes = field(f, "Snakes")?.as_array().ok_or("Snakes is not a list")?;
+            let id = |s: &Value| field(s, "ID")?.as_str().map(str::to_string).ok_or_else(|| "ID is not a string".to_string());
+            if parsed.is_empty() {
+                ids = snakes.iter().map(id).collect::<Result<_, String>>()?;
+            }
+            let mut slots = Vec::new();
+            for s in snakes {
+                let id = id(s)?;
+                let slot = ids.iter().position(|i| *i == id).ok_or_else(|| format!("snake {} is missing from the first frame", id))?;
+                let death = s.get("Death").filter(|d| !d.is_null());
+                let (death_reason, killed_by, death_turn) = match death {
+                    Some(d) => (
+                        from_board_cause(field(d, "Cause")?.as_str().unwrap_or("")),
+                        d.get("EliminatedBy").and_then(Value::as_str).and_then(|by| ids.iter().position(|i| i == by)).map(|slot| slot as u32),
+                        d.get("Turn").and_then(Value::as_u64).map(|turn| turn as u32),
+                    ),
+                    None => (DeathReason::None, None, None),
+                };
+                slots.push(SnakeFrame {
+                    id: slot as u32,
+                    body: points(field(s, "Body")?)?,
+                    health: uint(s, "Health")? as u32,
+                    alive: death.is_none(),
+                    death_reason,
+                    killed_by,
+                    death_turn,
+                });
+            }
+            if slots.len() != ids.len() {
+                return Err(format!("turn {} lists {} snakes, the first frame {}", uint(f, "Turn")?, slots.len(), ids.len()));
+            }
+            slots.sort_by_key(|s| s.id);
+            parsed.push(Frame {
+                turn: uint(f, "Turn")? as u32,
+                snakes: slots,
+                food: sorted(points(field(f, "Food")?)?.iter()),
+                hazards: sorted(points(field(f, "Hazards")?)?.iter()),
+            });
+        }
+        let winner = parsed.last().and_then(|f| match f.snakes.iter().filter(|s| s.alive).collect::<Vec<_>>()[..] {
+            [s] if f.snakes.len() > 1 => Some(s.id),
+            _ => None,
+        });
+        Ok(Self { game_id: field(game, "ID")?.as_str().and_then(|id| id.parse().ok()).unwrap_or(0), width, height, frames: parsed, winner })
+    }
+
+    // One GIF frame per turn, cell_size pixels per tile with y = 0 at the top. Snakes take
+    // their colors in slot order and eliminated snakes are left out
+    pub fn write_gif(&self, path: &str, cell_size: u32, frame_ms: u32) -> Result<(), String> {
+        if cell_size < 2 {
+            return Err(format!("cell_size must be at least 2, got {}", cell_size));
+        }
+        let (width, height) = (self.width * cell_size, self.height * cell_size);
+        if width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(format!("a {}x{} image is too large for a GIF", width, height));
+        }
+        let mut palette = vec![0x1b, 0x1f, 0x2a, 0x2c, 0x31, 0x3c, 0xe7, 0x4c, 0x3c, 0x54, 0x4a, 0x5e];
+        for color in SNAKE_COLORS {
+            palette.extend(color);
+            palette.extend(color.map(|c| c / 2));
+        }
+        let file = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &palette).map_err(|e| e.to_string())?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+        for frame in &self.frames {
+            let mut pixels = vec![GRID; (width * height) as usize];
+            // Tiles are drawn one pixel short of the cell so the grid shows between them
+            let mut fill = |t: Tile, color: u8, inset: u32| {
+                for py in t.y * cell_size + inset..(t.y + 1) * cell_size - 1 - inset {
+                    let row = (py * width) as usize;
+                    pixels[row + (t.x * cell_size + inset) as usize..row + ((t.x + 1) * cell_size - 1 - inset) as usize].fill(color);
+                }
+            };
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    fill(Tile { x, y }, BACKGROUND, 0);
+                }
+            }
+            for &t in &frame.hazards {
+                fill(t, HAZARD, 0);
+            }
+            for &t in &frame.food {
+                fill(t, FOOD, cell_size / 4);
+            }
+            for (slot, snake) in frame.snakes.iter().enumerate().filter(|(_, s)| s.alive) {
+                let body = 4 + 2 * (slot % SNAKE_COLORS.len()) as u8;
+                for &t in snake.body.iter().rev() {
+                    fill(t, body, 0);
+                }
+                fill(snake.body[0], body + 1, 0);
+            }
+            let gif_frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                buffer: Cow::Owned(pixels),
+                // In hundredths of a second
+                delay: (frame_ms / 10) as u16,
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&gif_frame).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+// Elimination causes as the official rules name them
+fn board_cause(reason: DeathReason) -> &'static str {
+    match reason {
+        DeathReason::None => "",
+        DeathReason::Wall => "wall-collision",
+        DeathReason::SelfCollision => "snake-self-collision",
+        DeathReason::EnemyBody => "snake-collision",
+        DeathReason::HeadToHead => "head-collision",
+        DeathReason::Starvation | DeathReason::Hazard => "out-of-health",
+        DeathReason::Squad => "squad-eliminated",
+    }
+}
+
+// Hazard damage reads as starvation, the engine doesn't tell them apart
+fn from_board_cause(cause: &str) -> DeathReason {
+    match cause {
+        "wall-collision" => DeathReason::Wall,
+        "snake-self-collision" => DeathReason::SelfCollision,
+        "snake-collision" => DeathReason::EnemyBody,
+        "head-collision" => DeathReason::HeadToHead,
+        "out-of-health" => DeathReason::Starvation,
+        "squad-eliminated" => DeathReason::Squad,
+        _ => DeathReason::None,
+    }
+}
+
+fn sorted<'a>(tiles: impl Iterator<Item = &'a Tile>) -> Vec<Tile> {
+    let mut tiles: Vec<Tile> = tiles.copied().collect();
+    tiles.sort_unstable_by_key(|t| (t.y, t.x));
+    tiles.dedup();
+    tiles
+}
+
+// A get_replay string, or an engine game in the board viewer's format
+pub(crate) fn parse_any(replay: &str) -> PyResult<Replay> {
+    serde_json::from_str::<Value>(replay)
+        .map_err(|e| e.to_string())
+        .and_then(|v| if v.get("Frames").is_some() { Replay::from_board_json(&v) } else { Replay::from_json(&v) })
+        .map_err(|e| PyValueError::new_err(format!("invalid replay: {}", e)))
+}
+
+fn parse(replay: &str) -> PyResult<Replay> {
+    serde_json::from_str(replay)
+        .map_err(|e| e.to_string())
+        .and_then(|v| Replay::from_json(&v))
+        .map_err(|e| PyValueError::new_err(format!("invalid replay: {}", e)))
+}
+
+// Renders a replay saved from GameWrapper.get_replay to a GIF, see Replay::write_gif
+#[pyfunction]
+#[pyo3(signature = (replay, path, cell_size=16, frame_ms=200))]
+pub fn replay_to_gif(py: Python<'_>, replay: &str, path: &str, cell_size: u32, frame_ms: u32) -> PyResult<()> {
+    let replay = parse(replay)?;
+    py.allow_threads(|| replay.write_gif(path, cell_size, frame_ms)).map_err(PyIOError::new_err)
+}
+
+// Converts a replay saved from GameWrapper.get_replay for the board viewer, see
+// Replay::to_board_json
+#[pyfunction]
+pub fn replay_to_board(replay: &str) -> PyResult<String> {
+    Ok(parse(replay)?.to_board_json().to_string())
+}
@@ -0,0 +1,75 @@
+use arrow::array::{ArrayRef, BooleanArray, FixedSizeListArray, Float32Array, UInt32Array, UInt8Array};
+use arrow::datatypes::{DataType, Field};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+use crate::gamewrapper::Info;
+
+// Rollout data as Arrow record batches, one per step with a row for every slot that acted on
+// it: model and env, the stacked observation it acted on, its action, its reward and done,
+// and its Info after the step. The rows are owned by Arrow buffers, so Python receives them
+// without a copy
+#[derive(Default)]
+pub struct RolloutLog {
+    batches: Vec<RecordBatch>,
+    // Indexes (model_i * n_envs + env_i) of the slots acting on the coming step, with their
+    // observations
+    pending: Option<(Vec<usize>, ArrayRef)>,
+}
+
+impl RolloutLog {
+    // obs holds the stacks of slots back to back, each obs_len values long
+    pub fn begin_u8(&mut self, slots: Vec<usize>, obs: Vec<u8>, obs_len: usize) {
+        let values: ArrayRef = Arc::new(UInt8Array::from(obs));
+        self.pending = Some((slots, stacks(DataType::UInt8, values, obs_len)));
+    }
+
+    pub fn begin_f32(&mut self, slots: Vec<usize>, obs: Vec<f32>, obs_len: usize) {
+        let values: ArrayRef = Arc::new(Float32Array::from(obs));
+        self.pending = Some((slots, stacks(DataType::Float32, values, obs_len)));
+    }
+
+    // Completes the batch begun before the step from its actions and resulting Info
+    pub fn finish(&mut self, n_envs: usize, acts: &[u8], info: &[Info]) -> Result<(), ArrowError> {
+        let (slots, obs) = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let rows: Vec<&Info> = slots.iter().map(|&k| &info[k]).collect();
+        let u32s = |f: fn(&Info) -> u32| -> ArrayRef { Arc::new(UInt32Array::from(rows.iter().map(|i| f(i)).collect::<Vec<_>>())) };
+        let bools = |f: fn(&Info) -> bool| -> ArrayRef { Arc::new(BooleanArray::from(rows.iter().map(|i| f(i)).collect::<Vec<_>>())) };
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("model", Arc::new(UInt32Array::from(slots.iter().map(|&k| (k / n_envs) as u32).collect::<Vec<_>>()))),
+            ("env", Arc::new(UInt32Array::from(slots.iter().map(|&k| (k % n_envs) as u32).collect::<Vec<_>>()))),
+            ("obs", obs),
+            ("action", Arc::new(UInt8Array::from(slots.iter().map(|&k| acts[k]).collect::<Vec<_>>()))),
+            ("reward", Arc::new(Float32Array::from(rows.iter().map(|i| i.reward()).collect::<Vec<_>>()))),
+            ("done", bools(|i| i.over || !i.alive)),
+            ("turn", u32s(|i| i.turn)),
+            ("health", u32s(|i| i.health)),
+            ("length", u32s(|i| i.length)),
+            ("alive_count", u32s(|i| i.alive_count)),
+            ("death_reason", u32s(|i| i.death_reason)),
+            ("rank", u32s(|i| i.rank)),
+            ("kills", u32s(|i| i.kills)),
+            ("food_eaten", u32s(|i| i.food_eaten)),
+            ("game_id", u32s(|i| i.game_id)),
+            ("alive", bools(|i| i.alive)),
+            ("ate", bools(|i| i.ate)),
+            ("won", bools(|i| i.won)),
+            ("draw", bools(|i| i.draw)),
+            ("max_turn_reached", bools(|i| i.max_turn_reached)),
+        ];
+        self.batches.push(RecordBatch::try_from_iter(columns)?);
+        Ok(())
+    }
+
+    pub fn take(&mut self) -> Vec<RecordBatch> {
+        std::mem::take(&mut self.batches)
+    }
+}
+
+fn stacks(dtype: DataType, values: ArrayRef, obs_len: usize) -> ArrayRef {
+    Arc::new(FixedSizeListArray::new(Arc::new(Field::new("item", dtype, false)), obs_len as i32, values, None))
+}
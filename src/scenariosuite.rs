@@ -0,0 +1,139 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::gameinstance::{field, uint};
+use crate::gamewrapper::GameWrapper;
+use crate::policy;
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// One position of a suite: a scenario for GameWrapper.load_scenario, the snake under test and
+// the moves that count as a pass
+struct Case {
+    name: String,
+    scenario: Value,
+    snake: usize,
+    safe_moves: Vec<char>,
+}
+
+impl Case {
+    // {"scenario": {...}, "snake": 0, "safe_moves": ["u", "l"]}, snake defaulting to 0
+    fn from_json(name: String, v: &Value) -> Result<Self, String> {
+        let scenario = field(v, "scenario")?.clone();
+        let snake = match v.get("snake") {
+            Some(_) => uint(v, "snake")? as usize,
+            None => 0,
+        };
+        let safe_moves = field(v, "safe_moves")?
+            .as_array()
+            .and_then(|moves| moves.iter().map(|m| m.as_str().and_then(|m| m.chars().next()).filter(|m| MOVES.contains(m))).collect::<Option<Vec<_>>>())
+            .filter(|moves| !moves.is_empty())
+            .ok_or("safe_moves is not a non-empty list of u, d, l and r")?;
+        let snakes = field(field(&scenario, "board")?, "snakes")?.as_array().map_or(0, Vec::len);
+        if snake >= snakes {
+            return Err(format!("snake {} is out of range for {} snakes", snake, snakes));
+        }
+        Ok(Self { name, scenario, snake, safe_moves })
+    }
+}
+
+// Behavioral regression test for policies: a directory of hand-made positions, each with the
+// moves a good snake may make there. run() asks a policy for its move in every position and
+// reports which ones it got right
+#[pyclass]
+pub struct ScenarioSuite {
+    cases: Vec<Case>,
+    n_stack: usize,
+    float_obs: bool,
+    orientation_mode: Option<String>,
+}
+
+#[pymethods]
+impl ScenarioSuite {
+    // Loads every .json file in path, in file name order. Observations are built like a
+    // GameWrapper with the same n_stack, float_obs and orientation_mode would
+    #[new]
+    #[pyo3(signature = (path, n_stack=1, float_obs=false, orientation_mode=None))]
+    pub fn new(path: &str, n_stack: usize, float_obs: bool, orientation_mode: Option<String>) -> PyResult<Self> {
+        let mut files: Vec<_> = std::fs::read_dir(path)
+            .map_err(|e| PyIOError::new_err(format!("failed to read {}: {}", path, e)))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map_or(false, |ext| ext == "json"))
+            .collect();
+        files.sort();
+        let mut cases = Vec::new();
+        for file in files {
+            cases.push(load_case(&file)?);
+        }
+        Ok(Self { cases, n_stack, float_obs, orientation_mode })
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.cases.len()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.cases.iter().map(|c| c.name.clone()).collect()
+    }
+
+    // policy is the name of a scripted policy, or a callable that gets a (1, channels, width,
+    // height) observation array and returns one action, read like GameWrapper.set_actions.
+    // Returns one dict per scenario with its name, the move made, the safe moves and passed
+    pub fn run<'py>(&self, py: Python<'py>, policy: &PyAny) -> PyResult<Vec<&'py PyDict>> {
+        let scripted = match policy.extract::<&str>() {
+            Ok(kind) => Some(policy::scripted(kind).ok_or_else(|| PyValueError::new_err(format!("unknown scripted policy {}, expected one of {:?}", kind, policy::SCRIPTED)))?),
+            Err(_) => None,
+        };
+        let mut results = Vec::new();
+        for case in &self.cases {
+            let made = match &scripted {
+                Some(scripted) => {
+                    let game = self.game(py, case)?;
+                    let game = game.env(0)?;
+                    scripted.act(game, game.get_player_ids()[case.snake])
+                }
+                None => {
+                    let mut game = self.game(py, case)?;
+                    let actions: Vec<i64> = policy.call1((game.get_obs(py, case.snake)?,))?.extract()?;
+                    match actions[..] {
+                        [a] if (0..4).contains(&a) => game.set_action(case.snake, 0, a as u8),
+                        _ => return Err(PyValueError::new_err(format!("policy must return 1 action in 0..4 for {}", case.name))),
+                    }
+                    game.slot_move(case.snake, 0)?
+                }
+            };
+            let result = PyDict::new(py);
+            result.set_item("name", &case.name)?;
+            result.set_item("move", made.to_string())?;
+            result.set_item("safe_moves", case.safe_moves.iter().map(char::to_string).collect::<Vec<_>>())?;
+            result.set_item("passed", case.safe_moves.contains(&made))?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+impl ScenarioSuite {
+    // A single-env wrapper sized to the case, with its scenario loaded
+    fn game(&self, py: Python<'_>, case: &Case) -> PyResult<GameWrapper> {
+        let board = &case.scenario["board"];
+        let invalid = |e: String| PyValueError::new_err(format!("invalid scenario {}: {}", case.name, e));
+        let (width, height) = (uint(board, "width").map_err(invalid)? as u32, uint(board, "height").map_err(invalid)? as u32);
+        let snakes = board["snakes"].as_array().map_or(0, Vec::len);
+        let mut game = GameWrapper::new(py, 1, snakes, false, false, 0, self.n_stack, self.float_obs, false, width, height, None, 0, false, false, false, false, false, false, true, false, false, false, false, false, false, false, false, self.orientation_mode.as_deref(), false, false, "egocentric", None)?;
+        game.set_end_on_learner_death(false);
+        game.reset()?;
+        game.load_scenario_value(0, &case.scenario)?;
+        Ok(game)
+    }
+}
+
+fn load_case(file: &Path) -> PyResult<Case> {
+    let name = file.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+    let text = std::fs::read_to_string(file).map_err(|e| PyIOError::new_err(format!("failed to read {}: {}", file.display(), e)))?;
+    let v: Value = serde_json::from_str(&text).map_err(|e| PyValueError::new_err(format!("{} is not JSON: {}", file.display(), e)))?;
+    Case::from_json(name, &v).map_err(|e| PyValueError::new_err(format!("invalid scenario {}: {}", file.display(), e)))
+}
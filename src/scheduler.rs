@@ -0,0 +1,54 @@
+use rayon::prelude::*;
+
+use crate::gameinstance::GameInstance;
+
+// One env's part of a step or reset, given the env index and its slot
+pub type EnvTask = dyn Fn(usize, &mut Option<GameInstance>) -> Result<(), String> + Sync;
+
+// How GameWrapper fans work out over its envs. Each env's error slot gets the error of its task,
+// other slots are left as they are
+pub trait Scheduler: Send + Sync {
+    fn for_each_env(&self, envs: &mut [Option<GameInstance>], errors: &mut [Option<String>], f: &EnvTask);
+}
+
+fn run(f: &EnvTask, env_i: usize, gi: &mut Option<GameInstance>, error: &mut Option<String>) {
+    if let Err(e) = f(env_i, gi) {
+        *error = Some(e);
+    }
+}
+
+// One rayon task per env on the global pool
+pub struct RayonScheduler;
+
+impl Scheduler for RayonScheduler {
+    fn for_each_env(&self, envs: &mut [Option<GameInstance>], errors: &mut [Option<String>], f: &EnvTask) {
+        envs.par_iter_mut().zip(errors).enumerate().for_each(|(ii, (gi, error))| run(f, ii, gi, error));
+    }
+}
+
+// Envs in index order on the calling thread, for deterministic debugging and profiling
+pub struct SerialScheduler;
+
+impl Scheduler for SerialScheduler {
+    fn for_each_env(&self, envs: &mut [Option<GameInstance>], errors: &mut [Option<String>], f: &EnvTask) {
+        envs.iter_mut().zip(errors).enumerate().for_each(|(ii, (gi, error))| run(f, ii, gi, error));
+    }
+}
+
+// One rayon task per env on a pool of its own, so the envs stay on that pool's threads
+pub struct PoolScheduler {
+    pool: rayon::ThreadPool,
+}
+
+impl PoolScheduler {
+    pub fn new(num_threads: usize, name: String) -> Result<Self, rayon::ThreadPoolBuildError> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(num_threads).thread_name(move |i| format!("{}-{}", name, i)).build()?;
+        Ok(Self { pool })
+    }
+}
+
+impl Scheduler for PoolScheduler {
+    fn for_each_env(&self, envs: &mut [Option<GameInstance>], errors: &mut [Option<String>], f: &EnvTask) {
+        self.pool.install(|| envs.par_iter_mut().zip(errors).enumerate().for_each(|(ii, (gi, error))| run(f, ii, gi, error)));
+    }
+}
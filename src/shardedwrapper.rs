@@ -0,0 +1,146 @@
+use numpy::PyReadonlyArray2;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple};
+
+use crate::gamewrapper::GameWrapper;
+use crate::scheduler::PoolScheduler;
+
+// Several GameWrappers behind one interface, each stepping its envs on a thread pool of its
+// own. On NUMA machines a handful of small pools scale better than one pool over every core.
+// Envs are numbered shard by shard, so every array reads like a single GameWrapper's
+#[pyclass]
+pub struct ShardedGameWrapper {
+    shards: Vec<Py<GameWrapper>>,
+    // Envs per shard
+    sizes: Vec<usize>,
+    n_models: usize,
+}
+
+#[pymethods]
+impl ShardedGameWrapper {
+    // Splits n_envs as evenly as possible over n_shards. threads_per_shard defaults to the
+    // available cores over n_shards, and every other keyword goes to each GameWrapper
+    #[new]
+    #[pyo3(signature = (n_shards, n_envs, n_models, threads_per_shard=None, **kwargs))]
+    pub fn new(py: Python<'_>, n_shards: usize, n_envs: usize, n_models: usize, threads_per_shard: Option<usize>, kwargs: Option<&PyDict>) -> PyResult<Self> {
+        if n_shards == 0 || n_envs < n_shards {
+            return Err(PyValueError::new_err(format!("n_shards must be in 1..={}, got {}", n_envs, n_shards)));
+        }
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = threads_per_shard.unwrap_or(cores / n_shards).max(1);
+        let sizes: Vec<usize> = (0..n_shards).map(|i| n_envs / n_shards + (i < n_envs % n_shards) as usize).collect();
+        let mut shards = Vec::new();
+        for (i, &size) in sizes.iter().enumerate() {
+            let shard: Py<GameWrapper> = py.get_type::<GameWrapper>().call((size, n_models), kwargs)?.extract()?;
+            let scheduler = PoolScheduler::new(threads, format!("shard-{}", i)).map_err(|e| PyRuntimeError::new_err(format!("failed to start the threads of shard {}: {}", i, e)))?;
+            shard.borrow_mut(py).set_scheduler(Box::new(scheduler));
+            // A shared seed keys every env by its global index
+            shard.borrow_mut(py).set_first_stream(sizes[..i].iter().sum::<usize>() as u64);
+            shards.push(shard);
+        }
+        Ok(Self { shards, sizes, n_models })
+    }
+
+    pub fn reset(&self, py: Python<'_>) -> PyResult<()> {
+        for shard in &self.shards {
+            shard.borrow_mut(py).reset()?;
+        }
+        Ok(())
+    }
+
+    // Steps every shard at once with the actions already set
+    pub fn step(&self, py: Python<'_>) -> PyResult<()> {
+        for shard in &self.shards {
+            shard.borrow_mut(py).start_step(py)?;
+        }
+        for shard in &self.shards {
+            shard.borrow_mut(py).step_wait(py)?;
+        }
+        Ok(())
+    }
+
+    // Shaped (n_models, n_envs) like GameWrapper.set_actions
+    pub fn set_actions(&self, py: Python<'_>, actions: PyReadonlyArray2<u8>) -> PyResult<()> {
+        let actions = actions.as_array();
+        let n_envs: usize = self.sizes.iter().sum();
+        if actions.shape() != [self.n_models, n_envs] {
+            return Err(PyValueError::new_err(format!("expected actions of shape ({}, {}), got {:?}", self.n_models, n_envs, actions.shape())));
+        }
+        if let Some(((m, e), &a)) = actions.indexed_iter().find(|&(_, &a)| a >= 4) {
+            return Err(PyValueError::new_err(format!("action {} for model {} in env {} is not in 0..4", a, m, e)));
+        }
+        let mut start = 0;
+        for (shard, &size) in self.shards.iter().zip(&self.sizes) {
+            let mut shard = shard.borrow_mut(py);
+            for m in 0..self.n_models {
+                let row: Vec<u8> = actions.row(m).iter().skip(start).take(size).copied().collect();
+                shard.set_model_actions(m, &row);
+            }
+            start += size;
+        }
+        Ok(())
+    }
+
+    pub fn get_obs(&self, py: Python<'_>, model_i: usize) -> PyResult<PyObject> {
+        let parts = self.shards.iter().map(|s| s.borrow(py).get_obs(py, model_i)).collect::<PyResult<Vec<_>>>()?;
+        concatenate(py, parts, 0)
+    }
+
+    pub fn get_info<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let infos = self.shards.iter().map(|s| s.borrow(py).get_info(py)).collect::<PyResult<Vec<_>>>()?;
+        concatenate_dicts(py, &infos)
+    }
+
+    pub fn get_dones<'py>(&self, py: Python<'py>) -> PyResult<&'py PyTuple> {
+        let dones = self.shards.iter().map(|s| s.borrow(py).get_dones(py)).collect::<PyResult<Vec<_>>>()?;
+        let terminated = concatenate(py, dones.iter().map(|d| d.0.to_object(py)).collect(), 1)?;
+        let truncated = concatenate(py, dones.iter().map(|d| d.1.to_object(py)).collect(), 1)?;
+        Ok(PyTuple::new(py, [terminated, truncated]))
+    }
+
+    pub fn get_action_masks(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let parts = self.shards.iter().map(|s| s.borrow(py).get_action_masks(py).map(|m| m.to_object(py))).collect::<PyResult<Vec<_>>>()?;
+        concatenate(py, parts, 1)
+    }
+
+    pub fn obs_shape(&self, py: Python<'_>) -> PyResult<(usize, usize, usize)> {
+        self.shards[0].borrow(py).obs_shape()
+    }
+
+    pub fn num_actions(&self, py: Python<'_>) -> usize {
+        self.shards[0].borrow(py).num_actions()
+    }
+
+    pub fn layer_names(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        self.shards[0].borrow(py).layer_names()
+    }
+
+    // Envs in each shard, in env order
+    pub fn shard_sizes(&self) -> Vec<usize> {
+        self.sizes.clone()
+    }
+
+    // GameWrapper.throughput_stats of every shard
+    pub fn throughput_stats<'py>(&self, py: Python<'py>) -> PyResult<Vec<&'py PyDict>> {
+        self.shards.iter().map(|s| s.borrow(py).throughput_stats(py)).collect()
+    }
+}
+
+// Joins the shards' info dicts key by key along the env axis, nested dicts like episode included
+fn concatenate_dicts<'py>(py: Python<'py>, dicts: &[&'py PyDict]) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    for key in dicts[0].keys() {
+        let parts: Vec<&PyAny> = dicts.iter().filter_map(|d| d.get_item(key)).collect();
+        let joined = match parts.iter().map(|p| p.downcast::<PyDict>()).collect::<Result<Vec<_>, _>>() {
+            Ok(nested) => concatenate_dicts(py, &nested)?.to_object(py),
+            Err(_) => concatenate(py, parts.iter().map(|p| p.to_object(py)).collect(), 1)?,
+        };
+        dict.set_item(key, joined)?;
+    }
+    Ok(dict)
+}
+
+fn concatenate(py: Python<'_>, parts: Vec<PyObject>, axis: usize) -> PyResult<PyObject> {
+    Ok(py.import("numpy")?.call_method1("concatenate", (parts, axis))?.to_object(py))
+}
@@ -0,0 +1,87 @@
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use crate::gameinstance::{DeathReason, Tile};
+use crate::replay::Frame;
+
+// How one snake moved since the frame a viewer last saw. Its body now is heads followed by
+// the old body without its last tail_removed tiles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnakeDelta {
+    pub id: u32,
+    // Head first
+    pub heads: Vec<Tile>,
+    pub tail_removed: usize,
+    pub health: u32,
+}
+
+// What changed between two frames of a game, for viewers that keep their own copy of the board
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateDelta {
+    pub game_id: u32,
+    pub turn: u32,
+    // The viewer saw nothing of this episode yet, so every snake comes as heads over an empty
+    // body and all food and hazards as added
+    pub full: bool,
+    // Only the snakes that moved, shrank or changed health
+    pub snakes: Vec<SnakeDelta>,
+    pub food_added: Vec<Tile>,
+    pub food_removed: Vec<Tile>,
+    pub hazards_added: Vec<Tile>,
+    pub hazards_removed: Vec<Tile>,
+    // Snakes eliminated since the frame seen
+    pub deaths: Vec<(u32, DeathReason)>,
+}
+
+impl StateDelta {
+    // seen is the (game id, frame) last handed to the viewer, None if there was none
+    pub fn between(seen: Option<(u32, &Frame)>, game_id: u32, now: &Frame) -> Self {
+        let seen = seen.filter(|&(seen_id, _)| seen_id == game_id).map(|(_, frame)| frame);
+        let mut snakes = Vec::new();
+        let mut deaths = Vec::new();
+        for snake in &now.snakes {
+            let before = seen.and_then(|frame| frame.snakes.iter().find(|s| s.id == snake.id));
+            let (body, health, alive) = before.map_or((&[][..], None, true), |s| (&s.body[..], Some(s.health), s.alive));
+            // The fewest new heads that line the rest of the body up with the old one's front
+            let new = &snake.body;
+            let k = (0..=new.len()).find(|&k| new.len() - k <= body.len() && new[k..] == body[..new.len() - k]).unwrap();
+            let tail_removed = body.len() - (new.len() - k);
+            if k > 0 || tail_removed > 0 || health != Some(snake.health) {
+                snakes.push(SnakeDelta { id: snake.id, heads: new[..k].to_vec(), tail_removed, health: snake.health });
+            }
+            if alive && !snake.alive {
+                deaths.push((snake.id, snake.death_reason));
+            }
+        }
+        let (food_added, food_removed) = changes(seen.map_or(&[][..], |frame| &frame.food), &now.food);
+        let (hazards_added, hazards_removed) = changes(seen.map_or(&[][..], |frame| &frame.hazards), &now.hazards);
+        Self { game_id, turn: now.turn, full: seen.is_none(), snakes, food_added, food_removed, hazards_added, hazards_removed, deaths }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let tiles = |tiles: &[Tile]| tiles.iter().map(|t| json!([t.x, t.y])).collect::<Vec<_>>();
+        let snakes: Vec<_> = self
+            .snakes
+            .iter()
+            .map(|s| json!({ "id": s.id, "heads": tiles(&s.heads), "tail_removed": s.tail_removed, "health": s.health }))
+            .collect();
+        let deaths: Vec<_> = self.deaths.iter().map(|&(id, reason)| json!({ "id": id, "death_reason": reason.name() })).collect();
+        json!({
+            "game_id": self.game_id,
+            "turn": self.turn,
+            "full": self.full,
+            "snakes": snakes,
+            "food_added": tiles(&self.food_added),
+            "food_removed": tiles(&self.food_removed),
+            "hazards_added": tiles(&self.hazards_added),
+            "hazards_removed": tiles(&self.hazards_removed),
+            "deaths": deaths,
+        })
+    }
+}
+
+// (added, removed) going from before to now, in the order of each side
+fn changes(before: &[Tile], now: &[Tile]) -> (Vec<Tile>, Vec<Tile>) {
+    let (old, new): (HashSet<_>, HashSet<_>) = (before.iter().collect(), now.iter().collect());
+    (now.iter().filter(|t| !old.contains(t)).copied().collect(), before.iter().filter(|t| !new.contains(t)).copied().collect())
+}
@@ -0,0 +1,149 @@
+use crate::gameinstance::Tile;
+
+// u, d, l, r as (x, y) steps with y pointing down
+const STEPS: [(char, (i32, i32)); 4] = [('u', (0, -1)), ('d', (0, 1)), ('l', (-1, 0)), ('r', (1, 0))];
+
+// One of the 8 symmetries of the square as an orthogonal matrix over board offsets. The
+// egocentric canvas shows the tile at offset (dx, dy) from the head at matrix * (dx, dy) from
+// its center, and a move in the board's frame turns into map_action of it on the canvas
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SymmetryTransform {
+    m: [[i32; 2]; 2],
+}
+
+impl SymmetryTransform {
+    pub const IDENTITY: Self = Self { m: [[1, 0], [0, 1]] };
+
+    // Mirrors left to right and/or top to bottom
+    pub fn flips(flip_x: bool, flip_y: bool) -> Self {
+        let sign = |flip: bool| if flip { -1 } else { 1 };
+        Self { m: [[sign(flip_x), 0], [0, sign(flip_y)]] }
+    }
+
+    // Turns the snake so its neck is below its head. Snakes whose head and neck share a tile
+    // (the first turn) are left as they are
+    pub fn face_up(head: Tile, neck: Tile) -> Self {
+        let diff_x = head.x as i32 - neck.x as i32;
+        let diff_y = head.y as i32 - neck.y as i32;
+        match (diff_x, diff_y) {
+            // Heading down: flip the y axis
+            (0, 1) => Self::flips(false, true),
+            // Heading right: rotate a quarter turn counterclockwise
+            (1, 0) => Self { m: [[0, 1], [-1, 0]] },
+            // Heading left: transpose
+            (-1, 0) => Self { m: [[0, 1], [1, 0]] },
+            _ => Self::IDENTITY,
+        }
+    }
+
+    // An offset from the head, as seen on the canvas
+    pub fn map_offset(&self, (dx, dy): (i32, i32)) -> (i32, i32) {
+        (self.m[0][0] * dx + self.m[0][1] * dy, self.m[1][0] * dx + self.m[1][1] * dy)
+    }
+
+    // Canvas position of a board tile on a canvas of the given size centered on head
+    pub fn map_point(&self, t: Tile, head: Tile, width: i32, height: i32) -> (i32, i32) {
+        let (x, y) = self.map_offset((t.x as i32 - head.x as i32, t.y as i32 - head.y as i32));
+        (x + width / 2, y + height / 2)
+    }
+
+    // A move in the board's frame as seen on the canvas. Anything but u, d, l and r is kept
+    pub fn map_action(&self, action: char) -> char {
+        let step = match STEPS.iter().find(|(a, _)| *a == action) {
+            Some(&(_, step)) => step,
+            None => return action,
+        };
+        let mapped = self.map_offset(step);
+        STEPS.iter().find(|(_, s)| *s == mapped).unwrap().0
+    }
+
+    // Orthogonal, so the inverse is the transpose
+    pub fn inverse(&self) -> Self {
+        Self { m: [[self.m[0][0], self.m[1][0]], [self.m[0][1], self.m[1][1]]] }
+    }
+
+    // self applied after other
+    pub fn compose(&self, other: &Self) -> Self {
+        let mut m = [[0; 2]; 2];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, v) in row.iter_mut().enumerate() {
+                *v = self.m[i][0] * other.m[0][j] + self.m[i][1] * other.m[1][j];
+            }
+        }
+        Self { m }
+    }
+}
+
+// The dihedral group of the square: Rot* turn the canvas clockwise, Mirror* mirror it left to
+// right first. Indexed in that order by ALL, which augment's transform numbers follow
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum D4 {
+    Identity,
+    Rot90,
+    Rot180,
+    Rot270,
+    Mirror,
+    MirrorRot90,
+    MirrorRot180,
+    MirrorRot270,
+}
+
+impl D4 {
+    pub const ALL: [D4; 8] = [D4::Identity, D4::Rot90, D4::Rot180, D4::Rot270, D4::Mirror, D4::MirrorRot90, D4::MirrorRot180, D4::MirrorRot270];
+
+    pub fn transform(self) -> SymmetryTransform {
+        let i = self as usize;
+        let quarter = SymmetryTransform { m: [[0, -1], [1, 0]] };
+        let mut t = SymmetryTransform::flips(i >= 4, false);
+        for _ in 0..i % 4 {
+            t = quarter.compose(&t);
+        }
+        t
+    }
+
+    // The element with the same axis flips
+    pub fn from_flips(flip_x: bool, flip_y: bool) -> Self {
+        match (flip_x, flip_y) {
+            (false, false) => D4::Identity,
+            (true, false) => D4::Mirror,
+            (false, true) => D4::MirrorRot180,
+            (true, true) => D4::Rot180,
+        }
+    }
+}
+
+// How each slot's canvas is oriented. Random orientations are redrawn every turn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrientationMode {
+    // The board as it is
+    None,
+    // A random axis flip
+    Flips,
+    // A random element of D4
+    D4,
+    // Turned so the snake faces up, square boards only
+    FaceUp,
+}
+
+impl OrientationMode {
+    pub const NAMES: [&'static str; 4] = ["none", "flips", "d4", "face_up"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(OrientationMode::None),
+            "flips" => Some(OrientationMode::Flips),
+            "d4" => Some(OrientationMode::D4),
+            "face_up" => Some(OrientationMode::FaceUp),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OrientationMode::None => "none",
+            OrientationMode::Flips => "flips",
+            OrientationMode::D4 => "d4",
+            OrientationMode::FaceUp => "face_up",
+        }
+    }
+}
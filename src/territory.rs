@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::gameinstance::{next_tile, GameInstance, Players, Tile};
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+// Columns of features, the four space_ ones in MOVES order
+pub const FEATURE_NAMES: [&str; 8] = ["food_distance", "space_up", "space_down", "space_left", "space_right", "health", "length", "length_lead"];
+
+// Multi-source BFS from every living head over the tiles no body or wall covers. Both vectors are
+// indexed y * width + x
+pub struct Territory {
+    // The snake that reaches a tile strictly first, None for bodies, ties and unreachable tiles
+    pub owner: Vec<Option<u32>>,
+    // Moves from the nearest head, u32::MAX if no head reaches the tile
+    pub dist: Vec<u32>,
+}
+
+impl Territory {
+    pub fn area(&self, id: u32) -> usize {
+        self.owner.iter().filter(|&&o| o == Some(id)).count()
+    }
+}
+
+fn blocked(players: &Players, walls: &HashSet<Tile>, width: u32, length: u32) -> Vec<bool> {
+    let mut blocked = vec![false; (width * length) as usize];
+    for &t in walls {
+        blocked[(t.y * width + t.x) as usize] = true;
+    }
+    for player in players.values().filter(|p| p.alive) {
+        for &t in &player.body {
+            blocked[(t.y * width + t.x) as usize] = true;
+        }
+    }
+    blocked
+}
+
+// Runs the BFS from the given (tile, owner) sources, see Territory
+fn flood(players: &Players, walls: &HashSet<Tile>, width: u32, length: u32, sources: &[(Tile, Option<u32>)]) -> Territory {
+    let index = |t: Tile| (t.y * width + t.x) as usize;
+    let blocked = blocked(players, walls, width, length);
+    let mut owner = vec![None; (width * length) as usize];
+    let mut dist = vec![u32::MAX; (width * length) as usize];
+    let mut queue = VecDeque::new();
+    for &(t, o) in sources {
+        owner[index(t)] = o;
+        dist[index(t)] = 0;
+        queue.push_back(t);
+    }
+    while let Some(t) = queue.pop_front() {
+        let (d, o) = (dist[index(t)], owner[index(t)]);
+        for &m in &MOVES {
+            let next = match next_tile(t, m, width, length) {
+                Some(next) if !blocked[index(next)] => next,
+                _ => continue,
+            };
+            let i = index(next);
+            if dist[i] == u32::MAX {
+                dist[i] = d + 1;
+                owner[i] = o;
+                queue.push_back(next);
+            } else if dist[i] == d + 1 && owner[i] != o {
+                // Reached by two snakes on the same move
+                owner[i] = None;
+            }
+        }
+    }
+    Territory { owner, dist }
+}
+
+pub fn voronoi(players: &Players, walls: &HashSet<Tile>, width: u32, length: u32) -> Territory {
+    let heads: Vec<_> = players.values().filter(|p| p.alive).map(|p| (p.body[0], Some(p.id as u32))).collect();
+    flood(players, walls, width, length, &heads)
+}
+
+// Moves from one tile to every other, u32::MAX where bodies or walls cut it off
+pub fn distances(players: &Players, walls: &HashSet<Tile>, width: u32, length: u32, from: Tile) -> Vec<u32> {
+    flood(players, walls, width, length, &[(from, None)]).dist
+}
+
+// Moves from a head to the closest reachable food, None if bodies or walls cut every food off
+pub fn nearest_food(players: &Players, walls: &HashSet<Tile>, food: &HashSet<Tile>, width: u32, length: u32, head: Tile) -> Option<u32> {
+    let dist = distances(players, walls, width, length, head);
+    food.iter().map(|t| dist[(t.y * width + t.x) as usize]).filter(|&d| d != u32::MAX).min()
+}
+
+// Shaping potentials of every living snake: (controlled area, minus the moves to the nearest
+// food, length lead over the longest opponent). Unreachable food counts as width * height moves
+pub fn potentials(game: &GameInstance) -> HashMap<u32, [f32; 3]> {
+    let (_, players, food, width, length, _, _) = game.get_state();
+    let walls = game.get_walls();
+    let territory = voronoi(players, walls, width, length);
+    let alive: Vec<_> = players.values().filter(|p| p.alive).collect();
+    alive
+        .iter()
+        .map(|p| {
+            let id = p.id as u32;
+            let food = nearest_food(players, walls, food, width, length, p.body[0]).unwrap_or(width * length);
+            let longest = alive.iter().filter(|o| o.id != p.id).map(|o| o.body.len()).max().unwrap_or(p.body.len());
+            (id, [territory.area(id) as f32, -(food as f32), p.body.len() as f32 - longest as f32])
+        })
+        .collect()
+}
+
+// Scalars about one living snake for hybrid CNN + MLP policies, see FEATURE_NAMES: moves to the
+// nearest food (width * height when none is reachable), tiles reachable after each move (0 when
+// the move is blocked), health, length and length lead over the longest opponent
+pub fn features(game: &GameInstance, id: u32) -> [f32; 8] {
+    let (_, players, food, width, length, _, _) = game.get_state();
+    let walls = game.get_walls();
+    let p = &players[&id];
+    let head = p.body[0];
+    let blocked = blocked(players, walls, width, length);
+    let mut features = [0.0; 8];
+    features[0] = nearest_food(players, walls, food, width, length, head).unwrap_or(width * length) as f32;
+    for (j, &m) in MOVES.iter().enumerate() {
+        if let Some(next) = next_tile(head, m, width, length).filter(|t| !blocked[(t.y * width + t.x) as usize]) {
+            features[1 + j] = distances(players, walls, width, length, next).iter().filter(|&&d| d != u32::MAX).count() as f32;
+        }
+    }
+    let longest = players.values().filter(|o| o.alive && o.id != p.id).map(|o| o.body.len()).max().unwrap_or(p.body.len());
+    features[5] = p.health as f32;
+    features[6] = p.body.len() as f32;
+    features[7] = p.body.len() as f32 - longest as f32;
+    features
+}
+
+// Room around a living snake's head, see analyze
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Space {
+    // Free tiles the head can reach
+    pub reachable: usize,
+    // Fewer tiles are reachable than the snake is long
+    pub dead_end: bool,
+    // Reachable tiles that cut some of the others off from the head once taken, in row order
+    pub chokes: Vec<Tile>,
+}
+
+// Flood fill from the head over tiles no body or wall covers, with the choke tiles found as
+// the articulation points of that area rooted at the head. None for snakes not in the game or
+// eliminated
+pub fn analyze(game: &GameInstance, id: u32) -> Option<Space> {
+    let (_, players, _, width, length, _, _) = game.get_state();
+    let player = players.get(&id).filter(|p| p.alive)?;
+    let head = player.body[0];
+    let index = |t: Tile| (t.y * width + t.x) as usize;
+    let dist = distances(players, game.get_walls(), width, length, head);
+    let reachable = dist.iter().filter(|&&d| d != u32::MAX && d > 0).count();
+
+    // Tarjan's depth-first search without recursion: a tile stays on the stack with the next
+    // move to try, and once it has tried all four it hands its low point to the tile below
+    let n = (width * length) as usize;
+    let (mut order, mut low, mut parent) = (vec![0; n], vec![0; n], vec![usize::MAX; n]);
+    let mut choke = vec![false; n];
+    let mut time = 1;
+    order[index(head)] = time;
+    low[index(head)] = time;
+    let mut stack = vec![(head, 0)];
+    while let Some((t, m)) = stack.pop() {
+        let i = index(t);
+        if m < MOVES.len() {
+            stack.push((t, m + 1));
+            let next = match next_tile(t, MOVES[m], width, length) {
+                Some(next) if dist[index(next)] != u32::MAX => next,
+                _ => continue,
+            };
+            let j = index(next);
+            if order[j] == 0 {
+                time += 1;
+                order[j] = time;
+                low[j] = time;
+                parent[j] = i;
+                stack.push((next, 0));
+            } else if j != parent[i] {
+                low[i] = std::cmp::min(low[i], order[j]);
+            }
+        } else if let Some(&(up, _)) = stack.last() {
+            let p = index(up);
+            low[p] = std::cmp::min(low[p], low[i]);
+            // The head isn't free, so it's never a choke
+            if up != head && low[i] >= order[p] {
+                choke[p] = true;
+            }
+        }
+    }
+    let chokes = (0..n).filter(|&i| choke[i]).map(|i| Tile { x: i as u32 % width, y: i as u32 / width }).collect();
+    Some(Space { reachable, dead_end: reachable < player.body.len(), chokes })
+}
@@ -0,0 +1,95 @@
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::metrics::StepMetrics;
+
+// Steps kept for the rolling figures
+const WINDOW: usize = 100;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Phase {
+    Sim,
+    ObsWrite,
+    Reset,
+}
+
+struct StepTiming {
+    started: Instant,
+    finished: Instant,
+    // Nanoseconds per Phase, summed over envs
+    phases: [u64; 3],
+    resets: u64,
+}
+
+// Rolling timings of the last WINDOW steps. Phase times are summed over envs, so with the
+// rayon scheduler they measure CPU time rather than wall time
+#[derive(Default)]
+pub struct Throughput {
+    current: [AtomicU64; 3],
+    // Envs reset in the running step, one per Phase::Reset timing
+    current_resets: AtomicU64,
+    window: VecDeque<StepTiming>,
+    total_steps: u64,
+}
+
+impl Throughput {
+    // Safe to call from the env tasks of a running step
+    pub fn add(&self, phase: Phase, elapsed: Duration) {
+        self.current[phase as usize].fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        if let Phase::Reset = phase {
+            self.current_resets.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn end_step(&mut self, started: Instant) {
+        let phases = [0, 1, 2].map(|i| self.current[i].swap(0, Ordering::Relaxed));
+        let resets = self.current_resets.swap(0, Ordering::Relaxed);
+        if self.window.len() == WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(StepTiming { started, finished: Instant::now(), phases, resets });
+        self.total_steps += 1;
+    }
+
+    pub fn total_steps(&self) -> u64 {
+        self.total_steps
+    }
+
+    // Timings and resets of the last step into metrics
+    pub fn fill_metrics(&self, metrics: &mut StepMetrics) {
+        metrics.step = self.total_steps;
+        if let Some(last) = self.window.back() {
+            metrics.env_resets = last.resets as u32;
+            metrics.step_secs = (last.finished - last.started).as_secs_f64();
+            metrics.sim_secs = last.phases[Phase::Sim as usize] as f64 / 1e9;
+            metrics.obs_write_secs = last.phases[Phase::ObsWrite as usize] as f64 / 1e9;
+            metrics.reset_secs = last.phases[Phase::Reset as usize] as f64 / 1e9;
+        }
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>, n_envs: usize) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("steps", self.total_steps)?;
+        let (first, last) = match (self.window.front(), self.window.back()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(dict),
+        };
+        // Wall clock from the start of the oldest step, so time spent in Python between steps counts
+        let span = (last.finished - first.started).as_secs_f64();
+        let steps = self.window.len() as f64;
+        if span > 0.0 {
+            dict.set_item("steps_per_sec", steps / span)?;
+            dict.set_item("env_steps_per_sec", steps * n_envs as f64 / span)?;
+        }
+        let mean_ms = |i: usize| self.window.iter().map(|t| t.phases[i]).sum::<u64>() as f64 / steps / 1e6;
+        dict.set_item("sim_ms", mean_ms(Phase::Sim as usize))?;
+        dict.set_item("obs_write_ms", mean_ms(Phase::ObsWrite as usize))?;
+        dict.set_item("reset_ms", mean_ms(Phase::Reset as usize))?;
+        let step_ms = self.window.iter().map(|t| (t.finished - t.started).as_secs_f64()).sum::<f64>() / steps * 1e3;
+        dict.set_item("step_ms", step_ms)?;
+        Ok(dict)
+    }
+}
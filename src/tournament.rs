@@ -0,0 +1,295 @@
+use numpy::PyArray1;
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::gamewrapper::GameWrapper;
+#[cfg(feature = "onnx")]
+use crate::onnxpolicy::OnnxPolicy;
+use crate::policy;
+#[cfg(feature = "cli")]
+use crate::remotesnake::RemoteSnake;
+#[cfg(feature = "cli")]
+use std::time::Duration;
+
+const INITIAL_ELO: f64 = 1500.0;
+const ELO_K: f64 = 16.0;
+
+enum Entrant {
+    // One of policy::SCRIPTED, built fresh for every match
+    Scripted(String),
+    // Called with a (games, channels, width, height) observation array like pooled opponents
+    Callback(PyObject),
+    #[cfg(feature = "onnx")]
+    Onnx(OnnxPolicy),
+    // Base URL of a snake server, asked for every move with this timeout
+    #[cfg(feature = "cli")]
+    Remote(String, Duration),
+}
+
+// Head-to-head evaluation of registered policies. Every match plays games_per_match 1v1 games
+// in parallel, and results accumulate over runs into the win matrix and Elo ratings
+#[pyclass]
+pub struct Tournament {
+    board_width: u32,
+    board_height: u32,
+    max_turns: u32,
+    games_per_match: usize,
+    names: Vec<String>,
+    entrants: Vec<Entrant>,
+    // [i][j] over games between entrants i and j
+    wins: Vec<Vec<u32>>,
+    draws: Vec<Vec<u32>>,
+    elo: Vec<f64>,
+    // Every finished game is written here as replay JSON, see save_replays
+    replay_dir: Option<String>,
+    matches: u32,
+}
+
+#[pymethods]
+impl Tournament {
+    #[new]
+    #[pyo3(signature = (games_per_match=16, board_width=11, board_height=11, max_turns=0))]
+    pub fn new(games_per_match: usize, board_width: u32, board_height: u32, max_turns: u32) -> PyResult<Self> {
+        if games_per_match == 0 {
+            return Err(PyValueError::new_err("games_per_match must be positive"));
+        }
+        Ok(Self {
+            board_width,
+            board_height,
+            max_turns,
+            games_per_match,
+            names: Vec::new(),
+            entrants: Vec::new(),
+            wins: Vec::new(),
+            draws: Vec::new(),
+            elo: Vec::new(),
+            replay_dir: None,
+            matches: 0,
+        })
+    }
+
+    // random, greedy_food or flood_fill, see GameWrapper.set_scripted_policy
+    pub fn add_scripted(&mut self, name: String, kind: &str) -> PyResult<usize> {
+        if policy::scripted(kind).is_none() {
+            return Err(PyValueError::new_err(format!("unknown scripted policy {}, expected one of {:?}", kind, policy::SCRIPTED)));
+        }
+        self.add(name, Entrant::Scripted(kind.to_string()))
+    }
+
+    // Must return one action per observation row, read like GameWrapper.set_actions
+    pub fn add_callback(&mut self, name: String, policy: PyObject) -> PyResult<usize> {
+        self.add(name, Entrant::Callback(policy))
+    }
+
+    #[cfg(feature = "onnx")]
+    #[pyo3(signature = (name, path, deterministic=true))]
+    pub fn add_onnx(&mut self, name: String, path: &str, deterministic: bool) -> PyResult<usize> {
+        let policy = OnnxPolicy::load(path, deterministic).map_err(|e| PyValueError::new_err(format!("failed to load {}: {}", path, e)))?;
+        self.add(name, Entrant::Onnx(policy))
+    }
+
+    // A snake server speaking the official Battlesnake API at url, asked for every move over
+    // HTTP. One that times out or answers nonsense keeps going the way it went
+    #[cfg(feature = "cli")]
+    #[pyo3(signature = (name, url, timeout_ms=500))]
+    pub fn add_remote(&mut self, name: String, url: &str, timeout_ms: u64) -> PyResult<usize> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(PyValueError::new_err(format!("{} is not an http(s) URL", url)));
+        }
+        self.add(name, Entrant::Remote(url.to_string(), Duration::from_millis(timeout_ms)))
+    }
+
+    // Writes every game finished from now on into dir as <match>_<game>_<i>_vs_<j>.json, in
+    // the format of GameWrapper.get_replay. None stops
+    pub fn save_replays(&mut self, dir: Option<String>) -> PyResult<()> {
+        if let Some(dir) = &dir {
+            std::fs::create_dir_all(dir).map_err(|e| PyIOError::new_err(format!("failed to create {}: {}", dir, e)))?;
+        }
+        self.replay_dir = dir;
+        Ok(())
+    }
+
+    // Every pair of entrants plays one match
+    pub fn run_round_robin(&mut self, py: Python<'_>) -> PyResult<()> {
+        for i in 0..self.entrants.len() {
+            for j in i + 1..self.entrants.len() {
+                self.play_match(py, i, j)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Each round pairs entrants with the closest score they haven't met yet this run, so a
+    // few rounds rank many entrants without playing every pair. An odd entrant out sits the
+    // round out
+    pub fn run_swiss(&mut self, py: Python<'_>, rounds: usize) -> PyResult<()> {
+        let n = self.entrants.len();
+        let mut score = vec![0.0; n];
+        let mut met = vec![vec![false; n]; n];
+        for _ in 0..rounds {
+            let mut order: Vec<usize> = (0..n).collect();
+            order.sort_by(|&a, &b| score[b].partial_cmp(&score[a]).unwrap().then(self.elo[b].partial_cmp(&self.elo[a]).unwrap()));
+            let mut paired = vec![false; n];
+            for k in 0..n {
+                let i = order[k];
+                if paired[i] {
+                    continue;
+                }
+                let unpaired: Vec<usize> = order[k + 1..].iter().copied().filter(|&j| !paired[j]).collect();
+                let j = match unpaired.iter().find(|&&j| !met[i][j]).or(unpaired.first()) {
+                    Some(&j) => j,
+                    None => break,
+                };
+                paired[i] = true;
+                paired[j] = true;
+                met[i][j] = true;
+                met[j][i] = true;
+                let (wins_i, wins_j, draws) = self.play_match(py, i, j)?;
+                let games = (wins_i + wins_j + draws) as f64;
+                score[i] += (wins_i as f64 + 0.5 * draws as f64) / games;
+                score[j] += (wins_j as f64 + 0.5 * draws as f64) / games;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    // (wins, draws), both (n, n) where [i, j] counts games between entrants i and j. Wins are
+    // entrant i's, so games played are wins + wins.T + draws
+    pub fn results<'py>(&self, py: Python<'py>) -> PyResult<(PyObject, PyObject)> {
+        let n = self.entrants.len();
+        let matrix = |m: &Vec<Vec<u32>>| -> PyResult<PyObject> { Ok(PyArray1::from_vec(py, m.concat()).reshape([n, n])?.to_object(py)) };
+        Ok((matrix(&self.wins)?, matrix(&self.draws)?))
+    }
+
+    // The standings as a plain-text table, best Elo first
+    pub fn table(&self) -> String {
+        let n = self.entrants.len();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| self.elo[b].partial_cmp(&self.elo[a]).unwrap());
+        let w = self.names.iter().map(String::len).max().unwrap_or(0).max(4);
+        let mut table = format!("{:<w$}  {:>7}  {:>6}  {:>6}  {:>6}  {:>6}  {:>6}\n", "name", "elo", "games", "wins", "draws", "losses", "win%", w = w);
+        for i in order {
+            let wins: u32 = self.wins[i].iter().sum();
+            let draws: u32 = self.draws[i].iter().sum();
+            let losses: u32 = (0..n).map(|j| self.wins[j][i]).sum();
+            let games = wins + draws + losses;
+            let rate = if games > 0 { 100.0 * wins as f64 / games as f64 } else { 0.0 };
+            table += &format!("{:<w$}  {:>7.1}  {:>6}  {:>6}  {:>6}  {:>6}  {:>6.1}\n", self.names[i], self.elo[i], games, wins, draws, losses, rate, w = w);
+        }
+        table
+    }
+
+    // Name -> Elo, every entrant starting from 1500 and updated game by game with K = 16
+    pub fn ratings<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let ratings = PyDict::new(py);
+        for (name, elo) in self.names.iter().zip(&self.elo) {
+            ratings.set_item(name, elo)?;
+        }
+        Ok(ratings)
+    }
+}
+
+impl Tournament {
+    fn add(&mut self, name: String, entrant: Entrant) -> PyResult<usize> {
+        if self.names.contains(&name) {
+            return Err(PyValueError::new_err(format!("an entrant named {} is already registered", name)));
+        }
+        self.names.push(name);
+        self.entrants.push(entrant);
+        for row in self.wins.iter_mut().chain(self.draws.iter_mut()) {
+            row.push(0);
+        }
+        self.wins.push(vec![0; self.entrants.len()]);
+        self.draws.push(vec![0; self.entrants.len()]);
+        self.elo.push(INITIAL_ELO);
+        Ok(self.entrants.len() - 1)
+    }
+
+    // Plays games_per_match games of entrant i in slot 0 against entrant j in slot 1, each env
+    // counted on its first finished episode. Returns (wins of i, wins of j, draws)
+    fn play_match(&mut self, py: Python<'_>, i: usize, j: usize) -> PyResult<(u32, u32, u32)> {
+        let n = self.games_per_match;
+        let mut game = GameWrapper::new(py, n, 2, false, false, 0, 1, false, false, self.board_width, self.board_height, None, self.max_turns, false, false, false, false, false, false, false, false, false, false, false, false, false, false, false, None, false, false, "egocentric", None)?;
+        game.set_end_on_learner_death(false);
+        for (m, &e) in [i, j].iter().enumerate() {
+            match &self.entrants[e] {
+                Entrant::Scripted(kind) => game.set_policy(m, policy::scripted(kind))?,
+                #[cfg(feature = "cli")]
+                Entrant::Remote(url, timeout) => game.set_policy(m, Some(Box::new(RemoteSnake::new(url, *timeout))))?,
+                _ => {}
+            }
+        }
+        game.record_replays(self.replay_dir.is_some())?;
+        game.reset()?;
+        self.matches += 1;
+        let mut finished = vec![false; n];
+        let (mut wins_i, mut wins_j, mut draws) = (0, 0, 0);
+        while finished.iter().any(|&f| !f) {
+            for (m, &e) in [i, j].iter().enumerate() {
+                let actions = match &self.entrants[e] {
+                    Entrant::Scripted(_) => continue,
+                    #[cfg(feature = "cli")]
+                    Entrant::Remote(..) => continue,
+                    Entrant::Callback(policy) => policy.call1(py, (game.get_obs(py, m)?,))?.extract::<Vec<i64>>(py)?,
+                    #[cfg(feature = "onnx")]
+                    Entrant::Onnx(policy) => {
+                        let (obs, shape) = game.model_obs_f32(m);
+                        py.allow_threads(|| policy.act(obs, shape, &mut rand::thread_rng()))
+                            .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("{} failed: {}", self.names[e], e)))?
+                            .into_iter()
+                            .map(i64::from)
+                            .collect()
+                    }
+                };
+                if actions.len() != n || actions.iter().any(|a| !(0..4).contains(a)) {
+                    return Err(PyValueError::new_err(format!("{} must return {} actions in 0..4", self.names[e], n)));
+                }
+                game.set_model_actions(m, &actions.iter().map(|&a| a as u8).collect::<Vec<_>>());
+            }
+            game.step(py)?;
+            for (env_i, done) in finished.iter_mut().enumerate() {
+                let (a, b) = (game.info(0, env_i), game.info(1, env_i));
+                if *done || !a.over {
+                    continue;
+                }
+                *done = true;
+                if let Some(dir) = &self.replay_dir {
+                    let path = format!("{}/{:04}_{:03}_{}_vs_{}.json", dir, self.matches, env_i, self.names[i], self.names[j]);
+                    std::fs::write(&path, game.get_replay(env_i)?).map_err(|e| PyIOError::new_err(format!("failed to write {}: {}", path, e)))?;
+                }
+                let score = if a.won {
+                    wins_i += 1;
+                    1.0
+                } else if b.won {
+                    wins_j += 1;
+                    0.0
+                } else {
+                    draws += 1;
+                    0.5
+                };
+                self.record(i, j, score);
+            }
+        }
+        Ok((wins_i, wins_j, draws))
+    }
+
+    // score is entrant i's: 1 for a win, 0.5 for a draw and 0 for a loss
+    fn record(&mut self, i: usize, j: usize, score: f64) {
+        if score == 1.0 {
+            self.wins[i][j] += 1;
+        } else if score == 0.0 {
+            self.wins[j][i] += 1;
+        } else {
+            self.draws[i][j] += 1;
+            self.draws[j][i] += 1;
+        }
+        let expected = 1.0 / (1.0 + 10f64.powf((self.elo[j] - self.elo[i]) / 400.0));
+        self.elo[i] += ELO_K * (score - expected);
+        self.elo[j] -= ELO_K * (score - expected);
+    }
+}
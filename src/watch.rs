@@ -0,0 +1,170 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::Terminal;
+use std::collections::HashSet;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::gameinstance::{GameInstance, Tile};
+use crate::replay::Frame;
+
+const SNAKE_COLORS: [Color; 8] = [Color::Blue, Color::Yellow, Color::Green, Color::Magenta, Color::Cyan, Color::Red, Color::LightBlue, Color::LightGreen];
+// How often the viewer redraws and checks for keys
+const TICK: Duration = Duration::from_millis(50);
+
+// The latest position of the watched env, published by the wrapper after every step
+struct Published {
+    env_i: usize,
+    game_id: u32,
+    width: u32,
+    height: u32,
+    step: u64,
+    frame: Frame,
+}
+
+// Shared between the wrapper and the viewer thread. The viewer picks the env, the wrapper
+// publishes it
+struct Feed {
+    env_i: usize,
+    n_envs: usize,
+    latest: Option<Published>,
+    // Set by either side to end the viewer
+    closed: bool,
+}
+
+// A terminal view of one env that runs on its own thread while the envs keep stepping. Keys:
+// n and p or the arrows cycle envs, space pauses the view, q or Esc quits
+pub struct Watcher {
+    feed: Arc<Mutex<Feed>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Watcher {
+    pub fn start(env_i: usize, n_envs: usize) -> io::Result<Self> {
+        let feed = Arc::new(Mutex::new(Feed { env_i, n_envs, latest: None, closed: false }));
+        let shared = Arc::clone(&feed);
+        let thread = std::thread::Builder::new().name("watch".to_string()).spawn(move || view(&shared))?;
+        Ok(Self { feed, thread: Some(thread) })
+    }
+
+    // The env to publish, None once the viewer has quit
+    pub fn watched_env(&self) -> Option<usize> {
+        let feed = self.feed.lock().unwrap();
+        if feed.closed {
+            None
+        } else {
+            Some(feed.env_i)
+        }
+    }
+
+    pub fn publish(&self, env_i: usize, game: &GameInstance, step: u64) {
+        let (_, _, _, width, height, _, _) = game.get_state();
+        let published = Published { env_i, game_id: game.get_game_id(), width, height, step, frame: Frame::of(game) };
+        self.feed.lock().unwrap().latest = Some(published);
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        self.feed.lock().unwrap().closed = true;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn view(feed: &Mutex<Feed>) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let result = run(feed);
+    // The terminal is handed back however the viewer ended
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    feed.lock().unwrap().closed = true;
+    result
+}
+
+fn run(feed: &Mutex<Feed>) -> io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut paused = false;
+    let mut shown: Option<Published> = None;
+    loop {
+        {
+            let mut feed = feed.lock().unwrap();
+            if feed.closed {
+                return Ok(());
+            }
+            if !paused {
+                if let Some(latest) = feed.latest.take() {
+                    shown = Some(latest);
+                }
+            }
+        }
+        let env_i = feed.lock().unwrap().env_i;
+        terminal.draw(|f| {
+            let title = match &shown {
+                Some(p) => format!(" env {} | game {} | turn {} | step {}{} ", p.env_i, p.game_id, p.frame.turn, p.step, if paused { " | paused" } else { "" }),
+                None => format!(" env {} | waiting for the next step ", env_i),
+            };
+            let mut lines = shown.as_ref().map_or_else(Vec::new, board);
+            lines.push(Line::from("n/p: next/previous env  space: pause  q: quit"));
+            f.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), f.size());
+        })?;
+
+        if !event::poll(TICK)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let mut feed = feed.lock().unwrap();
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    feed.closed = true;
+                    return Ok(());
+                }
+                KeyCode::Char(' ') => paused = !paused,
+                KeyCode::Char('n') | KeyCode::Right => feed.env_i = (feed.env_i + 1) % feed.n_envs,
+                KeyCode::Char('p') | KeyCode::Left => feed.env_i = (feed.env_i + feed.n_envs - 1) % feed.n_envs,
+                _ => {}
+            }
+        }
+    }
+}
+
+// Two columns per tile with y = 0 at the bottom, like the official board viewer
+fn board(p: &Published) -> Vec<Line<'static>> {
+    let food: HashSet<Tile> = p.frame.food.iter().copied().collect();
+    let hazards: HashSet<Tile> = p.frame.hazards.iter().copied().collect();
+    let mut lines = Vec::new();
+    for y in (0..p.height).rev() {
+        let spans: Vec<Span> = (0..p.width)
+            .map(|x| {
+                let tile = Tile { x, y };
+                let snake = p.frame.snakes.iter().enumerate().filter(|(_, s)| s.alive).find_map(|(i, s)| s.body.iter().position(|&t| t == tile).map(|at| (i, at)));
+                match snake {
+                    Some((i, 0)) => Span::styled("@@", Style::default().fg(SNAKE_COLORS[i % SNAKE_COLORS.len()]).add_modifier(Modifier::BOLD)),
+                    Some((i, _)) => Span::styled("██", Style::default().fg(SNAKE_COLORS[i % SNAKE_COLORS.len()])),
+                    None if food.contains(&tile) => Span::styled("()", Style::default().fg(Color::LightRed)),
+                    None if hazards.contains(&tile) => Span::styled("░░", Style::default().fg(Color::DarkGray)),
+                    None => Span::raw(" ."),
+                }
+            })
+            .collect();
+        lines.push(Line::from(spans));
+    }
+    for (i, s) in p.frame.snakes.iter().enumerate() {
+        let status = if s.alive { format!("health {}", s.health) } else { s.death_reason.name().to_string() };
+        let text = format!("snake {} length {} {}", s.id, s.body.len(), status);
+        lines.push(Line::from(Span::styled(text, Style::default().fg(SNAKE_COLORS[i % SNAKE_COLORS.len()]))));
+    }
+    lines
+}
@@ -0,0 +1,22 @@
+// Fixtures shared by the integration tests, each test crate uses some of them
+#![allow(dead_code)]
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust::{GameInstance, Parameters};
+
+// The default rules with no food spawned, so the board only changes with the moves
+pub fn no_food() -> Parameters {
+    Parameters { food_spawn_chance: 0.0, minimum_food: 0, ..Parameters::default() }
+}
+
+// A game reset to params from rng
+pub fn game_with(params: Parameters, rng: &mut StdRng) -> GameInstance {
+    let mut game = GameInstance::new(params.board_width, params.board_length, params.num_players, params.food_spawn_chance);
+    game.reset_with_parameters(params, rng);
+    game
+}
+
+// A game of num_players without food, always starting from the same position
+pub fn game(num_players: u32) -> GameInstance {
+    game_with(Parameters { num_players, ..no_food() }, &mut StdRng::seed_from_u64(0))
+}
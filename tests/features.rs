@@ -0,0 +1,18 @@
+// Scalar features and action masks of freshly reset envs
+use pyo3::Python;
+use rust::GameWrapper;
+
+#[test]
+fn features_and_masks_are_ready_right_after_reset() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let mut wrapper = GameWrapper::new(py, 4, 4, false, false, 0, 1, false, false, 11, 11, None, 0, false, false, false, false, false, false, true, false, false, false, false, false, false, false, false, None, true, false, "egocentric", Some(0)).unwrap();
+        wrapper.reset().unwrap();
+        let features = wrapper.get_features(py).unwrap().to_vec().unwrap();
+        assert_eq!(features.len(), 4 * 4 * wrapper.feature_names().len());
+        // Every snake starts with room to move
+        assert!(features.chunks(wrapper.feature_names().len()).all(|f| f[1..5].iter().any(|&space| space > 0.0)));
+        let masks = wrapper.get_action_masks(py).unwrap().to_vec().unwrap();
+        assert!(masks.chunks(4).all(|mask| mask.iter().any(|&allowed| allowed)));
+    });
+}
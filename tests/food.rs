@@ -0,0 +1,121 @@
+// Food spawning against the reference engine's standard rules
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust::{GameInstance, Parameters, Tile};
+use std::collections::HashSet;
+
+mod common;
+use common::game_with;
+
+fn game(num_players: u32, food_spawn_chance: f32, minimum_food: u32, rng: &mut StdRng) -> GameInstance {
+    game_with(Parameters { num_players, food_spawn_chance, minimum_food, ..Parameters::default() }, rng)
+}
+
+fn food(game: &GameInstance) -> HashSet<Tile> {
+    game.get_state().2.clone()
+}
+
+#[test]
+fn starts_with_food_by_every_snake_and_in_the_center() {
+    let mut rng = StdRng::seed_from_u64(0);
+    for num_players in 1..=8 {
+        let game = game(num_players, 0.15, 1, &mut rng);
+        let food = food(&game);
+        assert_eq!(food.len(), num_players as usize + 1);
+        assert!(food.contains(&Tile { x: 5, y: 5 }));
+        for player in game.get_state().1.values() {
+            let head = player.body[0];
+            assert!(food.iter().any(|t| t.x.abs_diff(head.x) == 1 && t.y.abs_diff(head.y) == 1), "no food by {:?}", head);
+        }
+    }
+}
+
+#[test]
+fn crowded_small_boards_start_with_center_food_only() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut game = GameInstance::new(7, 7, 5, 0.0);
+    game.reset_in_place(&mut rng);
+    assert_eq!(food(&game), [Tile { x: 3, y: 3 }].into_iter().collect());
+}
+
+#[test]
+fn other_sizes_start_with_one_random_food_per_snake() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for num_players in 1..=4 {
+        let mut game = GameInstance::new(9, 13, num_players, 0.0);
+        game.reset_in_place(&mut rng);
+        assert_eq!(food(&game).len(), num_players as usize);
+    }
+}
+
+#[test]
+fn tops_up_to_minimum_food() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut game = game(1, 0.0, 4, &mut rng);
+    game.maybe_spawn_food(&mut rng);
+    assert_eq!(food(&game).len(), 4);
+    // At the minimum with no spawn chance nothing more is added
+    for _ in 0..100 {
+        game.maybe_spawn_food(&mut rng);
+    }
+    assert_eq!(food(&game).len(), 4);
+}
+
+#[test]
+fn spawn_chance_uses_reference_integer_draw() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut spawned = 0;
+    let trials = 20000;
+    for _ in 0..trials {
+        let mut game = game(1, 0.15, 1, &mut rng);
+        let before = food(&game).len();
+        game.maybe_spawn_food(&mut rng);
+        spawned += food(&game).len() - before;
+    }
+    // (100 - rand(0..100)) < 15 holds for 14 of the 100 draws
+    let rate = spawned as f64 / trials as f64;
+    assert!((rate - 0.14).abs() < 0.01, "spawn rate {}", rate);
+}
+
+#[test]
+fn never_spawns_on_bodies_hazards_or_next_to_heads() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut game = game(4, 0.0, 60, &mut rng);
+    let hazards: HashSet<Tile> = (0..11).map(|x| Tile { x, y: 5 }).collect();
+    game.set_hazards(hazards.clone());
+    let before = food(&game);
+    game.maybe_spawn_food(&mut rng);
+    let spawned: HashSet<Tile> = food(&game).difference(&before).copied().collect();
+    assert!(!spawned.is_empty());
+    for player in game.get_state().1.values() {
+        let head = player.body[0];
+        for t in &spawned {
+            assert!(!player.body.contains(t));
+            let dist = (t.x as i64 - head.x as i64).abs() + (t.y as i64 - head.y as i64).abs();
+            assert!(dist > 1, "food {:?} next to head {:?}", t, head);
+        }
+    }
+    assert!(spawned.is_disjoint(&hazards));
+}
+
+#[test]
+fn stops_when_the_board_is_full() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut game = game(2, 0.0, 200, &mut rng);
+    game.maybe_spawn_food(&mut rng);
+    assert!(game.unoccupied_points(false).is_empty());
+    assert!(food(&game).len() < 121);
+}
+
+#[test]
+fn spawns_on_hazards_when_allowed() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut game = game(1, 0.0, 0, &mut rng);
+    let hazards: HashSet<Tile> = (0..5).flat_map(|y| (0..11).map(move |x| Tile { x, y })).collect();
+    game.set_hazards(hazards.clone());
+    assert!(game.unoccupied_points(false).iter().all(|t| !hazards.contains(t)));
+    let params = Parameters { minimum_food: 60, food_spawns_in_hazard: true, ..game.parameters() };
+    game.reset_with_parameters(params, &mut rng);
+    game.maybe_spawn_food(&mut rng);
+    assert!(food(&game).iter().any(|t| hazards.contains(t)));
+}
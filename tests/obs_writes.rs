@@ -0,0 +1,117 @@
+// Per-layer write semantics on tiles shared by several snakes
+use rust::{CellWrite, ObsDtype, ObsSpec, Tile};
+
+mod common;
+use common::game;
+
+fn body(tiles: &[(u32, u32)]) -> Vec<Tile> {
+    tiles.iter().map(|&(x, y)| Tile { x, y }).collect()
+}
+
+fn layer(spec: &ObsSpec, name: &str) -> usize {
+    spec.layers().iter().position(|(n, _)| n == name).unwrap()
+}
+
+#[test]
+fn corpses_stacked_head_on_head_add_up() {
+    let mut game = game(2);
+    game.set_layout(&[body(&[(3, 5), (2, 5), (1, 5)]), body(&[(5, 5), (6, 5), (7, 5)])], &[]);
+    let ids = game.get_player_ids();
+    game.set_player_move(ids[0], 'r');
+    game.set_player_move(ids[1], 'l');
+    game.step();
+    let players = game.get_state().1;
+    let shared = Tile { x: 4, y: 5 };
+    assert!(ids.iter().all(|id| !players[id].alive && players[id].body[0] == shared));
+
+    let spec = ObsSpec::new(11, 11, 3, false, false, ObsDtype::U8);
+    let writes = spec.writes();
+    let (corpse, health) = (layer(&spec, "corpse"), layer(&spec, "health"));
+    assert_eq!(writes[corpse], CellWrite::Add);
+    assert_eq!(writes[health], CellWrite::Set);
+
+    let mut cell = 0;
+    for _ in &ids {
+        assert!(!writes[corpse].apply_u8(&mut cell, 3));
+    }
+    assert_eq!(cell, 6);
+
+    // Whichever head is written last, the tile shows the larger value
+    let healths: Vec<u8> = ids.iter().map(|id| players[id].health as u8).collect();
+    let (mut forward, mut backward) = (0, 0);
+    for &h in &healths {
+        writes[health].apply_u8(&mut forward, h);
+    }
+    for &h in healths.iter().rev() {
+        writes[health].apply_u8(&mut backward, h);
+    }
+    assert_eq!(forward, backward);
+    assert_eq!(forward, *healths.iter().max().unwrap());
+}
+
+#[test]
+fn added_layers_saturate_instead_of_wrapping() {
+    let mut cell = 200;
+    assert!(CellWrite::Add.apply_u8(&mut cell, 100));
+    assert_eq!(cell, 255);
+    assert!(!CellWrite::Add.apply_u8(&mut cell, 0));
+    assert_eq!(cell, 255);
+
+    let mut cell = 100;
+    assert!(!CellWrite::Set.apply_u8(&mut cell, 1));
+    assert_eq!(cell, 100);
+
+    let mut cell = 0.5;
+    CellWrite::Add.apply_f32(&mut cell, 0.75);
+    assert_eq!(cell, 1.25);
+    CellWrite::Set.apply_f32(&mut cell, 1.0);
+    assert_eq!(cell, 1.25);
+}
+
+#[test]
+fn body_counts_add_and_marks_set() {
+    let spec = ObsSpec::new(11, 11, 0, true, true, ObsDtype::U8);
+    let writes = spec.writes();
+    assert_eq!(writes.len(), spec.num_layers());
+    for name in ["body", "segment_index", "longer_diff", "shorter_diff", "ally"] {
+        assert_eq!(writes[layer(&spec, name)], CellWrite::Add, "{}", name);
+    }
+    for name in ["health", "head_larger", "food", "board", "ego_head", "double_tail", "alive_count_2", "hazard"] {
+        assert_eq!(writes[layer(&spec, name)], CellWrite::Set, "{}", name);
+    }
+}
+
+#[test]
+fn losing_head_to_heads_mark_over_winning_ones() {
+    let mut spec = ObsSpec::new(11, 11, 0, false, false, ObsDtype::U8);
+    spec.head_to_head_layer = true;
+    let h2h = layer(&spec, "head_to_head");
+    assert_eq!(spec.layers()[h2h].1, "2");
+    assert!(!spec.patchable());
+
+    // A tile between a shorter and a longer enemy head
+    let write = spec.writes()[h2h];
+    let (mut forward, mut backward) = (0, 0);
+    for outcome in [1, 2] {
+        write.apply_u8(&mut forward, outcome);
+    }
+    for outcome in [2, 1] {
+        write.apply_u8(&mut backward, outcome);
+    }
+    assert_eq!((forward, backward), (2, 2));
+}
+
+#[test]
+fn length_channels_follow_the_declared_layers() {
+    let mut spec = ObsSpec::new(11, 11, 0, false, false, ObsDtype::U8);
+    let before = spec.num_layers();
+    spec.longest_layer = true;
+    spec.length_rank_layer = true;
+    assert_eq!(spec.num_layers(), before + 2);
+    let layers = spec.layers();
+    assert_eq!(layers[before], ("longest".to_string(), "1".to_string()));
+    assert_eq!(layers[before + 1], ("length_rank".to_string(), "max_snakes".to_string()));
+    assert!(!spec.patchable());
+    let from_json = ObsSpec::from_json(&spec.to_json()).unwrap();
+    assert!(from_json.longest_layer && from_json.length_rank_layer);
+}
@@ -0,0 +1,38 @@
+// Scripted baselines on scripted positions
+use rust::{FloodFillPolicy, GreedyFoodPolicy, Policy, RandomPolicy, Tile};
+
+mod common;
+use common::game;
+
+fn body(tiles: &[(u32, u32)]) -> Vec<Tile> {
+    tiles.iter().map(|&(x, y)| Tile { x, y }).collect()
+}
+
+#[test]
+fn random_never_takes_a_fatal_move_when_it_has_a_choice() {
+    let mut game = game(1);
+    // In the corner with the neck below, only right is safe
+    game.set_layout(&[body(&[(0, 0), (0, 1), (0, 2)])], &[]);
+    let id = game.get_player_ids()[0];
+    for _ in 0..50 {
+        assert_eq!(RandomPolicy.act(&game, id), 'r');
+    }
+}
+
+#[test]
+fn greedy_food_heads_for_the_closest_food() {
+    let mut game = game(1);
+    game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)])], &[Tile { x: 2, y: 5 }, Tile { x: 9, y: 1 }]);
+    let id = game.get_player_ids()[0];
+    assert_eq!(GreedyFoodPolicy.act(&game, id), 'l');
+}
+
+#[test]
+fn flood_fill_stays_out_of_pockets() {
+    let mut game = game(1);
+    // The body walls off the top-left 3x3 corner, food and all
+    game.set_layout(&[body(&[(0, 3), (1, 3), (2, 3), (3, 3), (3, 2), (3, 1), (3, 0)])], &[Tile { x: 1, y: 1 }]);
+    let id = game.get_player_ids()[0];
+    assert_eq!(GreedyFoodPolicy.act(&game, id), 'u');
+    assert_eq!(FloodFillPolicy.act(&game, id), 'd');
+}
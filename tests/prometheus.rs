@@ -0,0 +1,40 @@
+// The /metrics endpoint adds up the step metrics it is handed. Run with
+// `cargo test --features prometheus`
+#![cfg(feature = "prometheus")]
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use rust::{DeathReason, MetricsSink, PrometheusSink, StepMetrics};
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn scrapes_report_the_recorded_totals() {
+    let mut sink = PrometheusSink::serve("127.0.0.1:0").unwrap();
+    for step in 1..=3 {
+        let mut metrics = StepMetrics { step, n_envs: 4, episodes_finished: 1, env_resets: 2, sim_secs: 0.5, ..StepMetrics::default() };
+        metrics.death_reasons[DeathReason::Wall as usize] = 1;
+        sink.record(&metrics);
+    }
+
+    let response = get(sink.local_addr(), "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    for line in [
+        "battlesnake_steps_total 3",
+        "battlesnake_env_steps_total 12",
+        "battlesnake_episodes_total 3",
+        "battlesnake_env_resets_total 6",
+        "battlesnake_episode_deaths_total{reason=\"wall\"} 3",
+        "battlesnake_episode_deaths_total{reason=\"none\"} 0",
+        "battlesnake_phase_seconds_total{phase=\"sim\"} 1.5",
+    ] {
+        assert!(response.lines().any(|l| l == line), "missing {}", line);
+    }
+    assert!(get(sink.local_addr(), "/").starts_with("HTTP/1.1 404"));
+}
@@ -0,0 +1,32 @@
+// Elo ratings of the learner against the opponent pool
+use rust::Ratings;
+
+#[test]
+fn an_even_match_moves_both_ratings_by_half_of_k() {
+    let mut ratings = Ratings::default();
+    ratings.record("old", 1.0);
+    assert_eq!(ratings.learner().rating, 1516.0);
+    assert_eq!(ratings.opponent("old").rating, 1484.0);
+    assert_eq!(ratings.opponent("old").games, 1);
+    // A draw against a weaker opponent costs the learner a little
+    ratings.record("old", 0.5);
+    assert!(ratings.learner().rating < 1516.0);
+    assert_eq!(ratings.learner().games, 2);
+    assert_eq!(ratings.opponent("new").rating, 1500.0);
+}
+
+#[test]
+fn ratings_survive_a_save_and_load() {
+    let mut ratings = Ratings::default();
+    ratings.set_k(16.0);
+    ratings.record("a", 0.0);
+    ratings.record("b", 1.0);
+    let path = std::env::temp_dir().join(format!("ratings_{}.json", std::process::id()));
+    ratings.save(&path).unwrap();
+    let loaded = Ratings::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.k(), 16.0);
+    assert_eq!(loaded.learner(), ratings.learner());
+    assert_eq!(loaded.opponent("a"), ratings.opponent("a"));
+    assert_eq!(loaded.opponent("b"), ratings.opponent("b"));
+}
@@ -0,0 +1,25 @@
+// Move requests sent to snake servers follow the official API. Run with
+// `cargo test --features cli`
+#![cfg(feature = "cli")]
+use std::time::Duration;
+
+use rust::{GameInstance, RemoteSnake, Tile};
+
+#[test]
+fn requests_point_y_up_and_name_the_asking_snake() {
+    let mut game = GameInstance::new(11, 11, 2, 0.0);
+    let body = |tiles: &[(u32, u32)]| tiles.iter().map(|&(x, y)| Tile { x, y }).collect::<Vec<_>>();
+    game.set_layout(&[body(&[(1, 0), (1, 1), (1, 2)]), body(&[(8, 9), (8, 8), (8, 7)])], &[Tile { x: 5, y: 3 }]);
+    let ids = game.get_player_ids();
+    let request = RemoteSnake::move_request(&game, ids[1], Duration::from_millis(300));
+
+    assert_eq!(request["you"]["id"], ids[1].to_string());
+    assert_eq!(request["you"]["head"], serde_json::json!({ "x": 8, "y": 1 }));
+    assert_eq!(request["board"]["food"][0], serde_json::json!({ "x": 5, "y": 7 }));
+    assert_eq!(request["board"]["snakes"].as_array().unwrap().len(), 2);
+    assert_eq!(request["game"]["timeout"], 300);
+    // The gym's 'u' lowers y, which is the engine's up once rows are flipped
+    let head = game.get_state().1[&ids[0]].body[0];
+    assert_eq!(game.next_tile(head, 'u'), None);
+    assert_eq!(request["board"]["snakes"][0]["head"]["y"], 10);
+}
@@ -0,0 +1,95 @@
+// Recorded episodes survive a JSON round trip and render to a GIF
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rust::{Parameters, Replay};
+use temp_testdir::TempDir;
+
+mod common;
+use common::game_with;
+
+fn played_replay(seed: u64) -> Replay {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut game = game_with(Parameters { num_players: 4, ..Parameters::default() }, &mut rng);
+    let mut replay = Replay::new(&game);
+    while !game.is_over() {
+        for id in game.get_player_ids() {
+            game.set_player_move(id, *['u', 'd', 'l', 'r'].choose(&mut rng).unwrap());
+        }
+        game.step();
+        replay.push(&game);
+    }
+    replay
+}
+
+#[test]
+fn records_every_turn() {
+    let replay = played_replay(0);
+    let turns: Vec<u32> = replay.frames.iter().map(|f| f.turn).collect();
+    assert_eq!(turns, (0..replay.frames.len() as u32).collect::<Vec<_>>());
+    assert!(replay.frames[0].snakes.iter().all(|s| s.alive));
+    let last = replay.frames.last().unwrap();
+    assert!(last.snakes.iter().filter(|s| s.alive).count() <= 1);
+    // Eliminated snakes carry the turn they died on from then on
+    for (slot, snake) in last.snakes.iter().enumerate().filter(|(_, s)| !s.alive) {
+        let turn = snake.death_turn.unwrap();
+        let first_dead = replay.frames.iter().find(|f| !f.snakes[slot].alive).unwrap();
+        assert_eq!(first_dead.turn, turn);
+    }
+}
+
+#[test]
+fn round_trips_through_json() {
+    let replay = played_replay(1);
+    assert_eq!(Replay::from_json(&replay.to_json()).unwrap(), replay);
+    let mut json = replay.to_json();
+    json["frames"][0]["snakes"][0]["death_reason"] = serde_json::json!(99);
+    assert!(Replay::from_json(&json).is_err());
+}
+
+#[test]
+fn renders_a_gif() {
+    let dir = TempDir::default();
+    let path = dir.join("episode.gif");
+    played_replay(2).write_gif(path.to_str().unwrap(), 8, 100).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[..6], b"GIF89a");
+    assert!(played_replay(2).write_gif(path.to_str().unwrap(), 1, 100).is_err());
+}
+
+#[test]
+fn exports_board_viewer_frames() {
+    let replay = played_replay(3);
+    let board = replay.to_board_json();
+    assert_eq!(board["Count"], replay.frames.len());
+    let first = &replay.frames[0];
+    let snake = &board["Frames"][0]["Snakes"][0];
+    assert_eq!(snake["ID"], first.snakes[0].id.to_string());
+    // The viewer's y axis points up
+    assert_eq!(snake["Body"][0]["Y"], 10 - first.snakes[0].body[0].y);
+    assert!(snake["Death"].is_null());
+    let last = board["Frames"].as_array().unwrap().last().unwrap();
+    for (s, frame) in last["Snakes"].as_array().unwrap().iter().zip(&replay.frames.last().unwrap().snakes) {
+        if !frame.alive {
+            assert!(s["Death"]["Cause"].as_str().is_some_and(|c| !c.is_empty()));
+            assert!(s["Death"]["Turn"].as_u64().unwrap() <= replay.frames.len() as u64);
+        }
+    }
+}
+
+#[test]
+fn reads_board_viewer_frames_back() {
+    let replay = played_replay(4);
+    let read = Replay::from_board_json(&replay.to_board_json()).unwrap();
+    assert_eq!((read.width, read.height, read.game_id), (replay.width, replay.height, replay.game_id));
+    assert_eq!(read.frames.len(), replay.frames.len());
+    for (a, b) in read.frames.iter().zip(&replay.frames) {
+        assert_eq!((a.turn, &a.food, &a.hazards), (b.turn, &b.food, &b.hazards));
+        for (s, t) in a.snakes.iter().zip(&b.snakes) {
+            assert_eq!((&s.body, s.health, s.alive, s.death_turn), (&t.body, t.health, t.alive, t.death_turn));
+        }
+    }
+    // Snakes are renumbered by slot
+    let winner = replay.winner.map(|id| replay.frames[0].snakes.iter().position(|s| s.id == id).unwrap() as u32);
+    assert_eq!(read.winner, winner);
+}
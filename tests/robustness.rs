@@ -0,0 +1,67 @@
+// Malformed input over long runs: games refuse it or play on, they never panic
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust::{GameInstance, Parameters};
+use serde_json::json;
+
+mod common;
+use common::game_with;
+
+fn game(num_players: u32, rng: &mut StdRng) -> GameInstance {
+    game_with(Parameters { num_players, ..Parameters::default() }, rng)
+}
+
+#[test]
+fn garbage_moves_play_out_many_episodes() {
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut game = game(4, &mut rng);
+    let moves = ['u', 'd', 'l', 'r', 'x', '\0', 'U', ' '];
+    for _ in 0..200 {
+        let mut turns = 0;
+        while !game.is_over() && turns < 500 {
+            for id in game.get_player_ids() {
+                game.set_player_move(id, moves[rng.gen_range(0..moves.len())]);
+            }
+            // Ids that aren't in the game are refused
+            assert!(!game.set_player_move(rng.gen_range(1000..2000), 'u'));
+            game.step();
+            turns += 1;
+            for player in game.get_state().1.values().filter(|p| p.alive) {
+                assert!(!player.body.is_empty());
+            }
+        }
+        game.reset_in_place(&mut rng);
+    }
+}
+
+#[test]
+fn malformed_scenarios_leave_the_game_playable() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut game = game(2, &mut rng);
+    let point = |x: u32, y: u32| json!({ "x": x, "y": y });
+    let snake = |body: serde_json::Value| json!({ "health": 50, "body": body });
+    let scenarios = [
+        json!(null),
+        json!([]),
+        json!({ "board": 3 }),
+        json!({ "board": { "width": 11, "height": 11 } }),
+        json!({ "board": { "width": 7, "height": 7, "food": [], "snakes": [] } }),
+        json!({ "board": { "width": 11, "height": 11, "food": "none", "snakes": [] } }),
+        json!({ "board": { "width": 11, "height": 11, "food": [], "snakes": [snake(json!([point(1, 1), point(1, 2)]))] } }),
+        json!({ "board": { "width": 11, "height": 11, "food": [], "snakes": [snake(json!([point(1, 1)])), snake(json!([point(3, 3), point(3, 4)]))] } }),
+        json!({ "board": { "width": 11, "height": 11, "food": [], "snakes": [snake(json!([point(1, 1), point(1, 2)])), snake(json!([point(30, 3), point(30, 4)]))] } }),
+        json!({ "board": { "width": 11, "height": 11, "food": [{ "x": -1, "y": 0 }], "snakes": [] } }),
+        json!({ "turn": "late", "board": { "width": 11, "height": 11, "food": [], "snakes": [] } }),
+    ];
+    let before = game.to_json();
+    for scenario in &scenarios {
+        assert!(game.load_scenario(scenario).is_err(), "accepted {}", scenario);
+        assert_eq!(game.to_json(), before);
+    }
+
+    for id in game.get_player_ids() {
+        game.set_player_move(id, 'u');
+    }
+    game.step();
+    assert_eq!(game.get_turn(), 1);
+}
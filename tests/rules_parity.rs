@@ -0,0 +1,233 @@
+// Replays random games through GameInstance and a port of the official standard ruleset
+// (BattlesnakeOfficial/rules, standard.go) and compares every turn. Food spawning is off on
+// both sides, the food placed at the start is all there is, so only the turn resolution is
+// compared. Run with `cargo test --features rules-parity`
+#![cfg(feature = "rules-parity")]
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rust::{DeathReason, GameInstance, Parameters, Tile};
+use std::collections::HashSet;
+
+mod common;
+use common::{game_with, no_food};
+
+const GAMES: u64 = 2000;
+const MAX_TURNS: u32 = 300;
+const HAZARD_DAMAGE: i64 = 14;
+
+type Point = (i64, i64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cause {
+    OutOfHealth,
+    OutOfBounds,
+    SelfCollision,
+    Collision,
+    HeadToHead,
+}
+
+#[derive(Clone, Debug)]
+struct Snake {
+    id: u32,
+    body: Vec<Point>,
+    health: i64,
+    eliminated: Option<(Cause, Option<u32>)>,
+}
+
+struct Board {
+    width: i64,
+    height: i64,
+    snakes: Vec<Snake>,
+    food: Vec<Point>,
+    hazards: Vec<Point>,
+}
+
+impl Board {
+    // moveSnakes, reduceSnakeHealth, damageHazards, feedSnakes, eliminateSnakes
+    fn step(&mut self, moves: &[char]) {
+        for (snake, &m) in self.snakes.iter_mut().zip(moves) {
+            if snake.eliminated.is_some() {
+                continue;
+            }
+            let (x, y) = snake.body[0];
+            let head = match m {
+                'u' => (x, y - 1),
+                'd' => (x, y + 1),
+                'l' => (x - 1, y),
+                _ => (x + 1, y),
+            };
+            snake.body.pop();
+            snake.body.insert(0, head);
+        }
+
+        for snake in self.snakes.iter_mut().filter(|s| s.eliminated.is_none()) {
+            snake.health -= 1;
+        }
+
+        for snake in self.snakes.iter_mut().filter(|s| s.eliminated.is_none()) {
+            let head = snake.body[0];
+            if self.hazards.contains(&head) && !self.food.contains(&head) {
+                snake.health = (snake.health - HAZARD_DAMAGE).max(0);
+                if snake.health <= 0 {
+                    snake.eliminated = Some((Cause::OutOfHealth, None));
+                }
+            }
+        }
+
+        let mut uneaten = Vec::new();
+        for &food in &self.food {
+            let mut eaten = false;
+            for snake in self.snakes.iter_mut().filter(|s| s.eliminated.is_none()) {
+                if snake.body[0] == food {
+                    let tail = *snake.body.last().unwrap();
+                    snake.body.push(tail);
+                    snake.health = 100;
+                    eaten = true;
+                }
+            }
+            if !eaten {
+                uneaten.push(food);
+            }
+        }
+        self.food = uneaten;
+
+        // Collisions are credited to the longest snake, ties in slot order
+        let mut by_length: Vec<usize> = (0..self.snakes.len()).collect();
+        by_length.sort_by_key(|&i| std::cmp::Reverse(self.snakes[i].body.len()));
+
+        for snake in self.snakes.iter_mut().filter(|s| s.eliminated.is_none()) {
+            let (x, y) = snake.body[0];
+            if snake.health <= 0 {
+                snake.eliminated = Some((Cause::OutOfHealth, None));
+            } else if x < 0 || y < 0 || x >= self.width || y >= self.height {
+                snake.eliminated = Some((Cause::OutOfBounds, None));
+            }
+        }
+
+        let mut collisions = Vec::new();
+        for (i, snake) in self.snakes.iter().enumerate().filter(|(_, s)| s.eliminated.is_none()) {
+            let head = snake.body[0];
+            let others = by_length.iter().map(|&j| &self.snakes[j]).filter(|o| o.eliminated.is_none() && o.id != snake.id);
+            if snake.body[1..].contains(&head) {
+                collisions.push((i, Cause::SelfCollision, snake.id));
+            } else if let Some(other) = others.clone().find(|o| o.body[1..].contains(&head)) {
+                collisions.push((i, Cause::Collision, other.id));
+            } else if let Some(other) = others.clone().find(|o| o.body[0] == head && snake.body.len() <= o.body.len()) {
+                collisions.push((i, Cause::HeadToHead, other.id));
+            }
+        }
+        for (i, cause, by) in collisions {
+            self.snakes[i].eliminated = Some((cause, Some(by)));
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        let alive = self.snakes.iter().filter(|s| s.eliminated.is_none()).count();
+        if self.snakes.len() == 1 {
+            alive == 0
+        } else {
+            alive <= 1
+        }
+    }
+}
+
+fn point(t: &Tile) -> Point {
+    (t.x as i64, t.y as i64)
+}
+
+// Our reasons folded onto the official causes. A self collision is credited to the snake
+// itself there and to nobody here
+fn our_cause(reason: DeathReason, killed_by: Option<u32>, id: u32) -> Option<(Cause, Option<u32>)> {
+    match reason {
+        DeathReason::None => None,
+        DeathReason::Wall => Some((Cause::OutOfBounds, None)),
+        DeathReason::Starvation | DeathReason::Hazard => Some((Cause::OutOfHealth, None)),
+        DeathReason::SelfCollision => Some((Cause::SelfCollision, Some(id))),
+        DeathReason::EnemyBody => Some((Cause::Collision, killed_by)),
+        DeathReason::HeadToHead => Some((Cause::HeadToHead, killed_by)),
+        DeathReason::Squad => panic!("squad eliminations are not part of the standard rules"),
+    }
+}
+
+fn setup(seed: u64) -> (GameInstance, Board, StdRng) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let num_players = rng.gen_range(1..=8);
+    let mut game = game_with(Parameters { num_players, hazard_damage: HAZARD_DAMAGE as usize, ..no_food() }, &mut rng);
+
+    // The port starts from the reset's spawn points and food, with its snakes stacked three
+    // deep on their spawn point like standard.go, so the game's own starting bodies are compared
+    let (_, players, food, _, _, _, _) = game.get_state();
+    let food: Vec<Tile> = food.iter().copied().collect();
+    let spawns: Vec<Tile> = game.get_player_ids().iter().map(|id| players[id].body[0]).collect();
+    let mut free: Vec<Tile> = (0..11).flat_map(|x| (0..11).map(move |y| Tile { x, y })).filter(|t| !spawns.contains(t) && !food.contains(t)).collect();
+    free.shuffle(&mut rng);
+    let hazards: HashSet<Tile> = free.drain(..rng.gen_range(0..16)).collect();
+    game.set_hazards(hazards.clone());
+
+    let board = Board {
+        width: 11,
+        height: 11,
+        snakes: game
+            .get_player_ids()
+            .iter()
+            .zip(&spawns)
+            .map(|(&id, spawn)| Snake { id, body: vec![point(spawn); 3], health: 100, eliminated: None })
+            .collect(),
+        food: food.iter().map(point).collect(),
+        hazards: hazards.iter().map(point).collect(),
+    };
+    (game, board, rng)
+}
+
+fn compare(game: &GameInstance, board: &Board, seed: u64) {
+    let (_, players, food, _, _, turn, _) = game.get_state();
+    let context = || format!("seed {} turn {}: {}", seed, turn, game.to_json());
+    for (id, snake) in game.get_player_ids().iter().zip(&board.snakes) {
+        let player = &players[id];
+        let ours = our_cause(player.death_reason, player.killed_by, *id).filter(|_| !player.alive);
+        assert_eq!(ours, snake.eliminated, "elimination of {}, {}", id, context());
+        // Off-board heads only exist in the official representation
+        if snake.eliminated.map(|(cause, _)| cause) != Some(Cause::OutOfBounds) {
+            let body: Vec<Point> = player.body.iter().map(point).collect();
+            assert_eq!(body, snake.body, "body of {}, {}", id, context());
+        }
+        if snake.eliminated.is_none() {
+            assert_eq!(player.health as i64, snake.health, "health of {}, {}", id, context());
+        }
+    }
+    let mut ours: Vec<Point> = food.iter().map(point).collect();
+    let mut theirs = board.food.clone();
+    ours.sort_unstable();
+    theirs.sort_unstable();
+    assert_eq!(ours, theirs, "food, {}", context());
+    assert_eq!(game.is_over(), board.is_over(), "game over, {}", context());
+}
+
+#[test]
+fn matches_the_official_standard_rules() {
+    for seed in 0..GAMES {
+        let (mut game, mut board, mut rng) = setup(seed);
+        while !game.is_over() && game.get_turn() < MAX_TURNS {
+            // Mostly survivable moves, so games last long enough to chase tails and eat
+            let ids = game.get_player_ids();
+            let moves: Vec<char> = ids
+                .iter()
+                .map(|&id| {
+                    let safe: Vec<char> = ['u', 'd', 'l', 'r'].into_iter().filter(|&m| !game.is_move_fatal(id, m)).collect();
+                    match safe.choose(&mut rng) {
+                        Some(&m) if rng.gen_bool(0.9) => m,
+                        _ => *['u', 'd', 'l', 'r'].choose(&mut rng).unwrap(),
+                    }
+                })
+                .collect();
+            for (&id, &m) in ids.iter().zip(&moves) {
+                game.set_player_move(id, m);
+            }
+            game.step();
+            board.step(&moves);
+            compare(&game, &board, seed);
+        }
+    }
+}
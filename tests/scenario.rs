@@ -0,0 +1,88 @@
+// Hand-made positions loaded over a running game, in the shape of GameInstance::to_json
+use rust::{GameInstance, Tile};
+use serde_json::json;
+
+mod common;
+use common::game;
+
+fn point(x: u32, y: u32) -> serde_json::Value {
+    json!({ "x": x, "y": y })
+}
+
+fn scenario() -> serde_json::Value {
+    json!({
+        "turn": 40,
+        "board": {
+            "width": 11,
+            "height": 11,
+            "food": [point(5, 4)],
+            "hazards": [point(0, 0), point(0, 0), point(1, 0)],
+            "snakes": [
+                { "id": "a", "health": 7, "body": [point(5, 5), point(5, 6), point(5, 7)] },
+                { "id": "b", "health": 90, "body": [point(8, 8), point(8, 9), point(9, 9), point(9, 9)] },
+            ],
+        },
+    })
+}
+
+#[test]
+fn loads_bodies_health_food_and_hazards() {
+    let mut game = game(2);
+    game.load_scenario(&scenario()).unwrap();
+    let ids = game.get_player_ids();
+    let (_, players, food, _, _, turn, hazards) = game.get_state();
+    assert_eq!(players[&ids[0]].health, 7);
+    assert_eq!(players[&ids[1]].body.len(), 4);
+    assert_eq!(*food, [Tile { x: 5, y: 4 }].into_iter().collect());
+    assert_eq!(hazards.len(), 2);
+    assert_eq!(turn, 40);
+    assert_eq!(game.get_tile_id(5, 6), ids[0]);
+    assert_eq!(game.get_tile_id(5, 4), 1);
+
+    // The loaded position plays on like any other
+    game.set_player_move(ids[0], 'u');
+    game.set_player_move(ids[1], 'u');
+    game.step();
+    let players = game.get_state().1;
+    assert_eq!(players[&ids[0]].health, 100);
+    assert_eq!(players[&ids[0]].body.len(), 4);
+}
+
+#[test]
+fn round_trips_through_to_json() {
+    let snakes = |game: &GameInstance| {
+        let players = game.get_state().1;
+        let mut snakes: Vec<_> = players.values().map(|p| (p.health, p.body.clone())).collect();
+        snakes.sort_by_key(|(_, body)| (body[0].x, body[0].y));
+        snakes
+    };
+    let mut game = game(2);
+    game.load_scenario(&scenario()).unwrap();
+    let mut copy = self::game(2);
+    copy.load_scenario(&game.to_json()).unwrap();
+    assert_eq!(snakes(&copy), snakes(&game));
+    assert_eq!(copy.get_state().2, game.get_state().2);
+    assert_eq!(copy.get_state().6, game.get_state().6);
+}
+
+#[test]
+fn rejects_scenarios_that_do_not_fit_and_keeps_the_game() {
+    let mut game = game(3);
+    assert!(game.load_scenario(&scenario()).unwrap_err().contains("2 snakes"));
+
+    let mut game = self::game(2);
+    let before = game.snapshot();
+    let mut wide = scenario();
+    wide["board"]["width"] = json!(19);
+    assert!(game.load_scenario(&wide).is_err());
+    let mut outside = scenario();
+    outside["board"]["food"] = json!([point(11, 0)]);
+    assert!(game.load_scenario(&outside).unwrap_err().contains("outside"));
+    let mut starving = scenario();
+    starving["board"]["snakes"][0]["health"] = json!(0);
+    assert!(game.load_scenario(&starving).is_err());
+    let mut short = scenario();
+    short["board"]["snakes"][1]["body"] = json!([point(8, 8)]);
+    assert!(game.load_scenario(&short).is_err());
+    assert_eq!(game.snapshot(), before);
+}
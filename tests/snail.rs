@@ -0,0 +1,74 @@
+// Snail mode trails, laid behind moving tails, stacked by length and worn off turn by turn, and
+// the healing pools sharing their damage path
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust::{GameInstance, Parameters, Tile};
+
+mod common;
+use common::{game_with, no_food};
+
+fn snail_game() -> GameInstance {
+    let mut game = game_with(Parameters { snail_mode: true, ..no_food() }, &mut StdRng::seed_from_u64(0));
+    let body = |tiles: &[(u32, u32)]| tiles.iter().map(|&(x, y)| Tile { x, y }).collect::<Vec<_>>();
+    game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)]), body(&[(7, 7), (8, 7), (9, 7)])], &[]);
+    game
+}
+
+fn step(game: &mut GameInstance, moves: &[char]) {
+    for (id, &m) in game.get_player_ids().into_iter().zip(moves) {
+        game.set_player_move(id, m);
+    }
+    game.step();
+}
+
+#[test]
+fn tails_leave_stacks_as_long_as_the_snake() {
+    let mut game = snail_game();
+    step(&mut game, &['u', 'l']);
+    let trails = game.get_trails();
+    assert_eq!(trails.len(), 2);
+    assert_eq!(trails[&Tile { x: 5, y: 7 }], 3);
+    assert_eq!(trails[&Tile { x: 9, y: 7 }], 3);
+    step(&mut game, &['u', 'u']);
+    let trails = game.get_trails();
+    assert_eq!(trails[&Tile { x: 5, y: 7 }], 2);
+    assert_eq!(trails[&Tile { x: 5, y: 6 }], 3);
+    for _ in 0..2 {
+        step(&mut game, &['u', 'u']);
+    }
+    assert!(!game.get_trails().contains_key(&Tile { x: 5, y: 7 }));
+}
+
+#[test]
+fn every_stack_deals_hazard_damage() {
+    let mut game = snail_game();
+    let id = game.get_player_ids()[1];
+    step(&mut game, &['u', 'l']);
+    step(&mut game, &['u', 'l']);
+    let player = &game.get_state().1[&id];
+    assert_eq!(player.body[0], Tile { x: 5, y: 7 });
+    assert_eq!(player.health, 100 - 2 - 3 * 14);
+}
+
+#[test]
+fn trails_survive_a_snapshot() {
+    let mut game = snail_game();
+    step(&mut game, &['u', 'l']);
+    let restored = GameInstance::from_snapshot(&game.snapshot()).unwrap();
+    assert_eq!(restored.get_trails(), game.get_trails());
+    assert_eq!(restored.hazard_stacks(Tile { x: 5, y: 7 }), 3);
+}
+
+#[test]
+fn healing_pools_restore_health() {
+    let mut game = snail_game();
+    let id = game.get_player_ids()[0];
+    game.set_pools([(Tile { x: 5, y: 4 }, 10)].into_iter().collect());
+    assert_eq!(game.tile_damage(Tile { x: 5, y: 4 }), -10);
+    step(&mut game, &['u', 'd']);
+    // Capped at full health
+    assert_eq!(game.get_state().1[&id].health, 100);
+    // A pool on a trail offsets its damage
+    game.set_pools([(Tile { x: 9, y: 7 }, 10)].into_iter().collect());
+    assert_eq!(game.tile_damage(Tile { x: 9, y: 7 }), 3 * 14 - 10);
+}
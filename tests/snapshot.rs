@@ -0,0 +1,62 @@
+// GameInstance snapshots restore the exact game, including model slot order
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rust::{GameInstance, Parameters, SquadRules};
+
+mod common;
+use common::{game_with, no_food};
+
+fn game(rng: &mut StdRng) -> GameInstance {
+    game_with(Parameters { num_players: 4, squads: SquadRules { size: 2, ..SquadRules::default() }, ..no_food() }, rng)
+}
+
+#[test]
+fn restores_the_same_state() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut game = game(&mut rng);
+    game.step();
+    let restored = GameInstance::from_snapshot(&game.snapshot()).unwrap();
+    assert_eq!(restored.get_player_ids(), game.get_player_ids());
+    assert_eq!(restored.get_state(), game.get_state());
+    assert_eq!(restored.parameters(), game.parameters());
+    assert_eq!(restored.snapshot(), game.snapshot());
+}
+
+#[test]
+fn restored_games_play_out_the_same() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut game = game(&mut rng);
+    let mut restored = GameInstance::from_snapshot(&game.snapshot()).unwrap();
+    while !game.is_over() {
+        for id in game.get_player_ids() {
+            let m = *['u', 'd', 'l', 'r'].choose(&mut rng).unwrap();
+            game.set_player_move(id, m);
+            restored.set_player_move(id, m);
+        }
+        game.step();
+        restored.step();
+        assert_eq!(restored.get_state(), game.get_state());
+    }
+    assert!(restored.is_over());
+}
+
+#[test]
+fn rejects_malformed_snapshots() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let mut snapshot = game(&mut rng).snapshot();
+    snapshot["food"] = serde_json::json!([[11, 0]]);
+    assert!(GameInstance::from_snapshot(&snapshot).is_err());
+    snapshot.as_object_mut().unwrap().remove("players");
+    assert!(GameInstance::from_snapshot(&snapshot).is_err());
+}
+
+#[test]
+fn rejects_living_snakes_without_health() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let mut snapshot = game(&mut rng).snapshot();
+    snapshot["players"][0]["health"] = serde_json::json!(0);
+    assert!(GameInstance::from_snapshot(&snapshot).is_err());
+    snapshot["players"][0]["alive"] = serde_json::json!(false);
+    assert!(GameInstance::from_snapshot(&snapshot).is_ok());
+}
@@ -0,0 +1,31 @@
+// Reachable area, dead ends and choke tiles around a snake's head
+use rust::{analyze, GameInstance, Tile};
+
+fn tiles(tiles: &[(u32, u32)]) -> Vec<Tile> {
+    tiles.iter().map(|&(x, y)| Tile { x, y }).collect()
+}
+
+#[test]
+fn a_gap_in_a_wall_and_its_approaches_are_chokes() {
+    let mut game = GameInstance::new(11, 11, 1, 0.0);
+    // Column 3 is walled off but for (3, 5)
+    game.set_walls((0..11).filter(|&y| y != 5).map(|y| Tile { x: 3, y }).collect());
+    game.set_layout(&[tiles(&[(1, 5), (1, 6), (1, 7)])], &[]);
+    let space = analyze(&game, game.get_player_ids()[0]).unwrap();
+    assert_eq!(space.reachable, 121 - 10 - 3);
+    assert!(!space.dead_end);
+    assert_eq!(space.chokes, tiles(&[(2, 5), (3, 5), (4, 5)]));
+}
+
+#[test]
+fn a_pocket_shorter_than_the_snake_is_a_dead_end() {
+    let mut game = GameInstance::new(11, 11, 1, 0.0);
+    game.set_walls(tiles(&[(1, 0), (1, 1), (1, 2)]).into_iter().collect());
+    game.set_layout(&[tiles(&[(0, 2), (0, 3), (0, 4), (0, 5), (0, 6)])], &[]);
+    let id = game.get_player_ids()[0];
+    let space = analyze(&game, id).unwrap();
+    assert_eq!(space.reachable, 2);
+    assert!(space.dead_end);
+    assert_eq!(space.chokes, tiles(&[(0, 1)]));
+    assert_eq!(analyze(&game, id + 1), None);
+}
@@ -0,0 +1,80 @@
+// Deltas applied to the board a viewer holds rebuild the game's own
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust::{Frame, GameInstance, Parameters, StateDelta, Tile};
+use std::collections::HashMap;
+
+mod common;
+use common::game_with;
+
+fn game(rng: &mut StdRng) -> GameInstance {
+    game_with(Parameters { num_players: 4, food_spawn_chance: 0.5, minimum_food: 2, snail_mode: true, ..Parameters::default() }, rng)
+}
+
+// The viewer's side: bodies by id, food and the ids it saw die
+#[derive(Default)]
+struct View {
+    bodies: HashMap<u32, Vec<Tile>>,
+    food: Vec<Tile>,
+    dead: Vec<u32>,
+}
+
+impl View {
+    fn apply(&mut self, delta: &StateDelta) {
+        if delta.full {
+            *self = View::default();
+        }
+        for snake in &delta.snakes {
+            let body = self.bodies.entry(snake.id).or_default();
+            body.truncate(body.len() - snake.tail_removed);
+            body.splice(0..0, snake.heads.iter().copied());
+        }
+        self.food.retain(|t| !delta.food_removed.contains(t));
+        self.food.extend(&delta.food_added);
+        self.dead.extend(delta.deaths.iter().map(|&(id, _)| id));
+    }
+
+    fn matches(&self, frame: &Frame) -> bool {
+        let mut food = self.food.clone();
+        food.sort_unstable_by_key(|t| (t.x, t.y));
+        let mut expected = frame.food.clone();
+        expected.sort_unstable_by_key(|t| (t.x, t.y));
+        food == expected && frame.snakes.iter().all(|s| self.bodies[&s.id] == s.body && self.dead.contains(&s.id) != s.alive)
+    }
+}
+
+#[test]
+fn deltas_rebuild_the_board_at_any_polling_rate() {
+    let mut rng = StdRng::seed_from_u64(11);
+    let mut game = game(&mut rng);
+    let mut view = View::default();
+    let mut seen: Option<(u32, Frame)> = None;
+    for _ in 0..2000 {
+        if game.is_over() {
+            game.reset_in_place(&mut rng);
+        }
+        // Viewers poll slower than the game steps, skipping turns
+        for _ in 0..rng.gen_range(0..3) {
+            for id in game.get_player_ids() {
+                let m = ['u', 'd', 'l', 'r'].into_iter().find(|&m| !game.is_move_fatal(id, m) && rng.gen_bool(0.7)).unwrap_or('u');
+                game.set_player_move(id, m);
+            }
+            game.step();
+        }
+        let frame = Frame::of(&game);
+        let delta = StateDelta::between(seen.as_ref().map(|(id, frame)| (*id, frame)), game.get_game_id(), &frame);
+        assert_eq!(delta.full, seen.as_ref().map_or(true, |(id, _)| *id != game.get_game_id()));
+        view.apply(&delta);
+        assert!(view.matches(&frame), "turn {} of game {}", frame.turn, game.get_game_id());
+        seen = Some((game.get_game_id(), frame));
+    }
+}
+
+#[test]
+fn unchanged_boards_give_empty_deltas() {
+    let game = game(&mut StdRng::seed_from_u64(2));
+    let frame = Frame::of(&game);
+    let delta = StateDelta::between(Some((game.get_game_id(), &frame)), game.get_game_id(), &frame);
+    assert!(!delta.full);
+    assert!(delta.snakes.is_empty() && delta.food_added.is_empty() && delta.food_removed.is_empty() && delta.deaths.is_empty());
+}
@@ -0,0 +1,333 @@
+// Turn resolution on scripted positions, and the board against a full rebuild in slot order
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rust::{DeathReason, GameInstance, Parameters, SquadRules, Tile};
+
+mod common;
+use common::{game_with, no_food};
+
+fn game(num_players: u32, squads: SquadRules) -> GameInstance {
+    game_with_growth(num_players, squads, false)
+}
+
+fn game_with_growth(num_players: u32, squads: SquadRules, legacy_growth: bool) -> GameInstance {
+    game_with(Parameters { num_players, squads, legacy_growth, ..no_food() }, &mut StdRng::seed_from_u64(0))
+}
+
+fn body(tiles: &[(u32, u32)]) -> Vec<Tile> {
+    tiles.iter().map(|&(x, y)| Tile { x, y }).collect()
+}
+
+// Steps with one move per snake, given in get_player_ids order
+fn step(game: &mut GameInstance, moves: &[char]) {
+    for (id, &m) in game.get_player_ids().into_iter().zip(moves) {
+        game.set_player_move(id, m);
+    }
+    game.step();
+}
+
+fn expected_board(game: &GameInstance) -> Vec<u32> {
+    let (_, players, food, width, length, _, _) = game.get_state();
+    let mut board = vec![0; (width * length) as usize];
+    for player in game.get_player_ids().iter().map(|id| &players[id]).filter(|p| p.alive) {
+        for t in &player.body {
+            board[(t.y * width + t.x) as usize] = player.id as u32;
+        }
+    }
+    for t in food {
+        board[(t.y * width + t.x) as usize] = 1;
+    }
+    board
+}
+
+#[test]
+fn eating_grows_the_snake_and_removes_the_food() {
+    let mut game = game(1, SquadRules::default());
+    game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)])], &[Tile { x: 5, y: 4 }]);
+    let id = game.get_player_ids()[0];
+    step(&mut game, &['u']);
+    let player = &game.get_state().1[&id];
+    // The tail is stacked and only moves on the turn after
+    assert_eq!(player.body, body(&[(5, 4), (5, 5), (5, 6), (5, 6)]));
+    assert_eq!(player.health, 100);
+    assert!(game.get_state().2.is_empty());
+    assert_eq!(game.get_tile_id(5, 4), id);
+    assert_eq!(game.get_tile_id(5, 7), 0);
+    assert_eq!(*game.get_state().0, expected_board(&game));
+    step(&mut game, &['u']);
+    assert_eq!(game.get_state().1[&id].body, body(&[(5, 3), (5, 4), (5, 5), (5, 6)]));
+}
+
+#[test]
+fn eating_is_recorded_per_turn_and_per_episode() {
+    let mut game = game(1, SquadRules::default());
+    game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)])], &[Tile { x: 5, y: 4 }, Tile { x: 5, y: 2 }]);
+    // A pool keeps health at 100 without any food
+    game.set_pools([(Tile { x: 5, y: 3 }, 10)].into_iter().collect());
+    let id = game.get_player_ids()[0];
+    let mut record = Vec::new();
+    for _ in 0..3 {
+        step(&mut game, &['u']);
+        let player = &game.get_state().1[&id];
+        assert_eq!(player.health, 100);
+        record.push((player.ate_this_turn, player.food_eaten));
+    }
+    assert_eq!(record, [(true, 1), (false, 1), (true, 2)]);
+}
+
+#[test]
+fn legacy_growth_keeps_the_old_tail() {
+    let mut game = game_with_growth(1, SquadRules::default(), true);
+    game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)])], &[Tile { x: 5, y: 4 }]);
+    let id = game.get_player_ids()[0];
+    step(&mut game, &['u']);
+    assert_eq!(game.get_state().1[&id].body, body(&[(5, 4), (5, 5), (5, 6), (5, 7)]));
+    assert_eq!(*game.get_state().0, expected_board(&game));
+    step(&mut game, &['u']);
+    assert_eq!(game.get_state().1[&id].body, body(&[(5, 3), (5, 4), (5, 5), (5, 6)]));
+}
+
+#[test]
+fn super_food_grows_by_its_value() {
+    let food = Tile { x: 5, y: 4 };
+    for (legacy_growth, growth, length) in [(false, 3, 6), (true, 3, 6), (false, 0, 3), (true, 0, 3)] {
+        let mut game = game_with_growth(1, SquadRules::default(), legacy_growth);
+        game.set_layout(&[body(&[(5, 5), (5, 6), (5, 7)])], &[food]);
+        game.set_food_value(food, growth);
+        let id = game.get_player_ids()[0];
+        step(&mut game, &['u']);
+        let player = &game.get_state().1[&id];
+        assert_eq!(player.body.len(), length, "legacy_growth {} growth {}", legacy_growth, growth);
+        assert_eq!(player.health, 100);
+        assert_eq!(player.body[0], food);
+        assert!(game.get_state().2.is_empty());
+    }
+}
+
+#[test]
+fn a_snake_can_follow_its_tail_unless_it_just_ate() {
+    let mut chasing = game(1, SquadRules::default());
+    chasing.set_layout(&[body(&[(5, 5), (5, 6), (4, 6), (4, 5)])], &[]);
+    let id = chasing.get_player_ids()[0];
+    assert!(!chasing.is_move_fatal(id, 'l'));
+    step(&mut chasing, &['l']);
+    assert!(chasing.get_state().1[&id].alive);
+
+    let mut fed = game(1, SquadRules::default());
+    fed.set_layout(&[body(&[(5, 5), (5, 6), (4, 6), (4, 5), (4, 4), (4, 3)])], &[Tile { x: 5, y: 4 }]);
+    let id = fed.get_player_ids()[0];
+    step(&mut fed, &['u']);
+    assert!(fed.is_move_fatal(id, 'l'));
+    step(&mut fed, &['l']);
+    let player = &fed.get_state().1[&id];
+    assert_eq!(player.death_reason, DeathReason::SelfCollision);
+}
+
+#[test]
+fn leaving_the_board_is_a_wall_death() {
+    let mut game = game(1, SquadRules::default());
+    game.set_layout(&[body(&[(0, 5), (1, 5), (2, 5)])], &[]);
+    let id = game.get_player_ids()[0];
+    step(&mut game, &['l']);
+    let player = &game.get_state().1[&id];
+    assert!(!player.alive);
+    assert_eq!(player.death_reason, DeathReason::Wall);
+    assert!(game.is_over());
+    assert!(game.get_state().0.iter().all(|&t| t == 0));
+}
+
+#[test]
+fn running_into_itself_is_a_self_collision() {
+    let mut game = game(1, SquadRules::default());
+    game.set_layout(&[body(&[(5, 5), (5, 6), (4, 6), (4, 5), (4, 4), (4, 3)])], &[]);
+    let id = game.get_player_ids()[0];
+    step(&mut game, &['l']);
+    let player = &game.get_state().1[&id];
+    assert_eq!(player.death_reason, DeathReason::SelfCollision);
+    assert_eq!(player.killed_by, None);
+}
+
+#[test]
+fn running_into_a_body_credits_its_owner() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(3, 5), (2, 5), (1, 5)]), body(&[(4, 4), (4, 5), (4, 6)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['r', 'u']);
+    let players = game.get_state().1;
+    assert_eq!(players[&ids[0]].death_reason, DeathReason::EnemyBody);
+    assert_eq!(players[&ids[0]].killed_by, Some(ids[1]));
+    assert_eq!(players[&ids[1]].kills, 1);
+    assert!(game.is_over());
+    assert_eq!(game.get_winner(), Some(ids[1]));
+    assert_eq!(*game.get_state().0, expected_board(&game));
+}
+
+#[test]
+fn the_longer_snake_wins_head_on_head() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(3, 5), (2, 5), (1, 5), (0, 5)]), body(&[(5, 5), (6, 5), (7, 5)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['r', 'l']);
+    let players = game.get_state().1;
+    assert!(players[&ids[0]].alive);
+    assert_eq!(players[&ids[0]].kills, 1);
+    assert_eq!(players[&ids[1]].death_reason, DeathReason::HeadToHead);
+    assert_eq!(players[&ids[1]].killed_by, Some(ids[0]));
+    assert_eq!(game.get_tile_id(4, 5), ids[0]);
+    assert_eq!(*game.get_state().0, expected_board(&game));
+}
+
+#[test]
+fn equal_lengths_head_on_head_is_a_draw() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(3, 5), (2, 5), (1, 5)]), body(&[(5, 5), (6, 5), (7, 5)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['r', 'l']);
+    let players = game.get_state().1;
+    for (&id, &other) in ids.iter().zip(ids.iter().rev()) {
+        assert_eq!(players[&id].death_reason, DeathReason::HeadToHead);
+        assert_eq!(players[&id].killed_by, Some(other));
+        assert_eq!(players[&id].kills, 1);
+    }
+    assert!(game.is_over());
+    assert!(game.is_draw());
+    assert!(game.get_state().0.iter().all(|&t| t == 0));
+}
+
+#[test]
+fn squadmates_pass_through_each_other() {
+    let mut game = game(2, SquadRules { size: 2, ..SquadRules::default() });
+    game.set_layout(&[body(&[(3, 5), (2, 5), (1, 5)]), body(&[(4, 4), (4, 5), (4, 6), (4, 7)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['r', 'u']);
+    let players = game.get_state().1;
+    assert!(players[&ids[0]].alive && players[&ids[1]].alive);
+    assert_eq!(*game.get_state().0, expected_board(&game));
+}
+
+#[test]
+fn board_matches_a_full_rebuild_every_turn() {
+    let mut rng = StdRng::seed_from_u64(5);
+    for squads in [SquadRules::default(), SquadRules { size: 2, shared_elimination: false, ..SquadRules::default() }] {
+        for episode in 0..200 {
+            let mut game = game(2 + episode % 7, squads);
+            game.reset_in_place(&mut rng);
+            while !game.is_over() {
+                let moves: Vec<char> = game.get_player_ids().iter().map(|_| *['u', 'd', 'l', 'r'].choose(&mut rng).unwrap()).collect();
+                step(&mut game, &moves);
+                assert_eq!(*game.get_state().0, expected_board(&game));
+            }
+        }
+    }
+}
+
+#[test]
+fn twelve_snakes_spawn_apart_on_a_large_board() {
+    let mut game = GameInstance::new(19, 19, 12, 0.0);
+    game.reset_in_place(&mut StdRng::seed_from_u64(0));
+    let players = game.get_state().1;
+    let mut heads: Vec<_> = players.values().filter(|p| p.alive).map(|p| (p.body[0].x, p.body[0].y)).collect();
+    heads.sort();
+    heads.dedup();
+    assert_eq!(heads.len(), 12);
+}
+
+#[test]
+fn players_are_walked_in_slot_order() {
+    let mut game = game(8, SquadRules::default());
+    game.reset_in_place(&mut StdRng::seed_from_u64(3));
+    let ids = game.get_player_ids();
+    let players = game.get_state().1;
+    assert_eq!(players.values().map(|p| p.id as u32).collect::<Vec<_>>(), ids);
+    assert_eq!(players.len(), 8);
+    assert!(players.get(&0).is_none());
+    let restored = GameInstance::from_snapshot(&game.snapshot()).unwrap();
+    assert_eq!(restored.get_state().1, players);
+}
+
+#[test]
+fn three_way_head_on_head_ends_alike_in_every_slot_order() {
+    let shorter = body(&[(4, 5), (3, 5), (2, 5)]);
+    let right = body(&[(6, 5), (7, 5), (8, 5), (9, 5)]);
+    let below = body(&[(5, 6), (5, 7), (5, 8), (5, 9)]);
+    let snakes = [(shorter, 'r'), (right, 'l'), (below, 'u')];
+    for order in [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]] {
+        let mut game = game(3, SquadRules::default());
+        let bodies: Vec<Vec<Tile>> = order.iter().map(|&i| snakes[i].0.clone()).collect();
+        game.set_layout(&bodies, &[]);
+        let moves: Vec<char> = order.iter().map(|&i| snakes[i].1).collect();
+        step(&mut game, &moves);
+        let players = game.get_state().1;
+        for id in game.get_player_ids() {
+            assert_eq!(players[&id].death_reason, DeathReason::HeadToHead, "order {:?}", order);
+        }
+        assert!(game.is_draw(), "order {:?}", order);
+    }
+}
+
+#[test]
+fn snakes_move_into_tails_left_the_same_turn() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(4, 4), (5, 4)]), body(&[(5, 5), (4, 5)])], &[]);
+    let ids = game.get_player_ids();
+    assert!(!game.is_move_fatal(ids[0], 'd') && !game.is_move_fatal(ids[1], 'u'));
+    step(&mut game, &['d', 'u']);
+    let players = game.get_state().1;
+    assert_eq!(players[&ids[0]].body, body(&[(4, 5), (4, 4)]));
+    assert_eq!(players[&ids[1]].body, body(&[(5, 4), (5, 5)]));
+    assert!(players[&ids[0]].alive && players[&ids[1]].alive);
+    assert_eq!(*game.get_state().0, expected_board(&game));
+}
+
+#[test]
+fn heads_swapping_tiles_run_into_each_others_necks() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(4, 5), (3, 5), (2, 5)]), body(&[(5, 5), (6, 5), (7, 5)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['r', 'l']);
+    let players = game.get_state().1;
+    for (&id, &other) in ids.iter().zip(ids.iter().rev()) {
+        assert_eq!(players[&id].death_reason, DeathReason::EnemyBody);
+        assert_eq!(players[&id].killed_by, Some(other));
+    }
+    assert!(game.is_draw());
+}
+
+#[test]
+fn a_snake_leaving_the_board_is_no_obstacle() {
+    let mut game = game(2, SquadRules::default());
+    game.set_layout(&[body(&[(0, 5), (0, 6), (0, 7)]), body(&[(1, 6), (2, 6), (3, 6)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['l', 'l']);
+    let players = game.get_state().1;
+    assert_eq!(players[&ids[0]].death_reason, DeathReason::Wall);
+    assert!(players[&ids[1]].alive);
+    assert_eq!(game.get_winner(), Some(ids[1]));
+    assert_eq!(*game.get_state().0, expected_board(&game));
+}
+
+#[test]
+fn a_single_segment_snake_keeps_its_body_at_the_wall() {
+    let mut game = game(1, SquadRules::default());
+    game.set_layout(&[body(&[(0, 5)])], &[]);
+    let id = game.get_player_ids()[0];
+    step(&mut game, &['l']);
+    let player = &game.get_state().1[&id];
+    assert_eq!(player.death_reason, DeathReason::Wall);
+    assert_eq!(player.body, body(&[(0, 5)]));
+    assert!(game.get_state().0.iter().all(|&t| t == 0));
+}
+
+#[test]
+fn own_segments_under_a_squadmate_are_a_self_collision() {
+    let mut game = game(2, SquadRules { size: 2, ..SquadRules::default() });
+    // The second snake runs through the tile the first one turns back into, and is written
+    // over it in the collision grid
+    game.set_layout(&[body(&[(5, 5), (5, 6), (4, 6), (4, 5), (4, 4)]), body(&[(3, 4), (3, 5), (4, 5), (4, 6)])], &[]);
+    let ids = game.get_player_ids();
+    step(&mut game, &['l', 'u']);
+    let players = game.get_state().1;
+    assert_eq!(players[&ids[0]].death_reason, DeathReason::SelfCollision);
+    assert_eq!(players[&ids[0]].killed_by, None);
+}
@@ -0,0 +1,113 @@
+// Seeded games replay the same episodes whichever thread runs them and in whatever order
+use rust::{GameInstance, Parameters, Tile};
+
+fn params() -> Parameters {
+    Parameters { num_players: 4, super_food_chance: 0.1, super_food_growth: 2, ..Parameters::default() }
+}
+
+// Turn, snake ids and bodies in slot order, and the sorted food of one position
+type Trace = (u32, Vec<(u32, Vec<Tile>)>, Vec<(u32, u32)>);
+
+fn trace(game: &GameInstance) -> Trace {
+    let players = game.get_state().1;
+    let snakes = game.get_player_ids().into_iter().map(|id| (id, players[&id].body.clone())).collect();
+    let mut food: Vec<_> = game.get_state().2.iter().map(|t| (t.x, t.y)).collect();
+    food.sort_unstable();
+    (game.get_turn(), snakes, food)
+}
+
+// Every snake takes its first move that isn't fatal, so only the game's draws vary
+fn play(game: &mut GameInstance, turns: usize) -> Vec<Trace> {
+    let mut traces = Vec::new();
+    for _ in 0..turns {
+        if game.is_over() {
+            game.reset_next_episode(params());
+        }
+        for id in game.get_player_ids() {
+            let m = ['u', 'd', 'l', 'r'].into_iter().find(|&m| !game.is_move_fatal(id, m)).unwrap_or('u');
+            game.set_player_move(id, m);
+        }
+        game.step();
+        traces.push(trace(game));
+    }
+    traces
+}
+
+fn seeded(seed: u64, stream: u64) -> GameInstance {
+    let mut game = GameInstance::new(11, 11, 4, 0.15);
+    game.seed_streams(seed, stream);
+    game.reset_next_episode(params());
+    game
+}
+
+#[test]
+fn streams_replay_on_any_thread_in_any_order() {
+    let serial: Vec<_> = (0..8).map(|stream| play(&mut seeded(42, stream), 400)).collect();
+    let threads: Vec<_> = (0..8u64)
+        .rev()
+        .map(|stream| std::thread::spawn(move || (stream, play(&mut seeded(42, stream), 400))))
+        .collect();
+    for handle in threads {
+        let (stream, traces) = handle.join().unwrap();
+        assert_eq!(traces, serial[stream as usize], "stream {}", stream);
+    }
+}
+
+#[test]
+fn streams_and_seeds_draw_apart() {
+    let first = play(&mut seeded(42, 0), 50);
+    assert_ne!(first, play(&mut seeded(42, 1), 50));
+    assert_ne!(first, play(&mut seeded(43, 0), 50));
+}
+
+#[test]
+fn reseeding_starts_the_streams_over() {
+    let mut game = seeded(7, 3);
+    let first = play(&mut game, 200);
+    game.seed_streams(7, 3);
+    game.reset_next_episode(params());
+    assert_eq!(play(&mut game, 200), first);
+}
+
+#[test]
+fn snapshots_keep_their_place_in_the_streams() {
+    let mut game = seeded(9, 1);
+    play(&mut game, 120);
+    let mut restored = GameInstance::from_snapshot(&game.snapshot()).unwrap();
+    assert_eq!(play(&mut restored, 300), play(&mut game, 300));
+}
+
+#[test]
+fn seeded_game_ids_replay_and_others_never_repeat() {
+    let ids = |game: &mut GameInstance| {
+        (0..5)
+            .map(|_| {
+                game.reset_next_episode(params());
+                game.get_game_id()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&mut seeded(5, 0)), ids(&mut seeded(5, 0)));
+
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..20 {
+        let mut game = GameInstance::new(11, 11, 2, 0.15);
+        assert!(seen.insert(game.get_game_id()));
+        for id in ids(&mut game) {
+            assert!(seen.insert(id));
+        }
+    }
+}
+
+#[test]
+fn restarting_from_another_start_keeps_the_games_own_streams() {
+    let start = seeded(11, 0);
+    let mut game = seeded(11, 1);
+    game.restart_from(&start);
+    assert_eq!(trace(&game), trace(&start));
+    // Later episodes still come from stream 1, not the start's stream 0
+    game.reset_next_episode(params());
+    let mut own = seeded(11, 1);
+    own.reset_next_episode(params());
+    assert_eq!(trace(&game), trace(&own));
+}
@@ -0,0 +1,85 @@
+// The canvas transforms move tiles and actions alike, and undo each other
+use rust::{SymmetryTransform, Tile, D4};
+
+const MOVES: [char; 4] = ['u', 'd', 'l', 'r'];
+
+fn step(t: Tile, m: char) -> Tile {
+    match m {
+        'u' => Tile { x: t.x, y: t.y - 1 },
+        'd' => Tile { x: t.x, y: t.y + 1 },
+        'l' => Tile { x: t.x - 1, y: t.y },
+        _ => Tile { x: t.x + 1, y: t.y },
+    }
+}
+
+fn canvas_step(p: (i32, i32), m: char) -> (i32, i32) {
+    match m {
+        'u' => (p.0, p.1 - 1),
+        'd' => (p.0, p.1 + 1),
+        'l' => (p.0 - 1, p.1),
+        _ => (p.0 + 1, p.1),
+    }
+}
+
+// Every transform the wrappers use: the D4 orientations and facing up from each direction
+fn transforms() -> Vec<SymmetryTransform> {
+    let head = Tile { x: 5, y: 5 };
+    let mut transforms: Vec<_> = D4::ALL.iter().map(|d4| d4.transform()).collect();
+    transforms.extend(MOVES.iter().map(|&m| SymmetryTransform::face_up(head, step(head, m))));
+    transforms
+}
+
+#[test]
+fn actions_move_points_the_same_way() {
+    let head = Tile { x: 5, y: 5 };
+    for transform in transforms() {
+        for t in [Tile { x: 5, y: 5 }, Tile { x: 2, y: 7 }, Tile { x: 9, y: 1 }] {
+            for m in MOVES {
+                let moved = transform.map_point(step(t, m), head, 23, 23);
+                assert_eq!(moved, canvas_step(transform.map_point(t, head, 23, 23), transform.map_action(m)), "{:?} {}", transform, m);
+            }
+        }
+    }
+}
+
+#[test]
+fn inverse_undoes_the_transform() {
+    let head = Tile { x: 5, y: 5 };
+    for transform in transforms() {
+        assert_eq!(transform.compose(&transform.inverse()), SymmetryTransform::IDENTITY);
+        for m in MOVES {
+            assert_eq!(transform.inverse().map_action(transform.map_action(m)), m);
+        }
+        assert_eq!(transform.map_point(head, head, 23, 23), (11, 11));
+    }
+}
+
+#[test]
+fn face_up_puts_the_neck_below_the_head() {
+    let head = Tile { x: 5, y: 5 };
+    for m in MOVES {
+        let neck = step(head, m);
+        let transform = SymmetryTransform::face_up(head, neck);
+        assert_eq!(transform.map_point(neck, head, 23, 23), (11, 12), "neck to the {}", m);
+        // The move back into the neck is always down on the canvas
+        assert_eq!(transform.map_action(m), 'd');
+    }
+    assert_eq!(SymmetryTransform::face_up(head, head), SymmetryTransform::IDENTITY);
+}
+
+#[test]
+fn d4_is_the_whole_group() {
+    let all: Vec<SymmetryTransform> = D4::ALL.iter().map(|d4| d4.transform()).collect();
+    for (i, a) in all.iter().enumerate() {
+        assert!(all[i + 1..].iter().all(|b| b != a));
+        for b in &all {
+            assert!(all.contains(&a.compose(b)));
+        }
+    }
+    assert_eq!(D4::Identity.transform(), SymmetryTransform::IDENTITY);
+    for (x, y) in [(false, false), (true, false), (false, true), (true, true)] {
+        assert_eq!(D4::from_flips(x, y).transform(), SymmetryTransform::flips(x, y));
+    }
+    // Clockwise on a canvas whose y axis points down
+    assert_eq!(D4::Rot90.transform().map_action('u'), 'r');
+}
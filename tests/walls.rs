@@ -0,0 +1,52 @@
+// Maze walls block movement like the board edge and stay clear of spawns and food
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rust::{DeathReason, GameInstance, Parameters, Tile};
+use std::collections::HashSet;
+
+fn params(num_players: u32, minimum_food: u32) -> Parameters {
+    Parameters { num_players, food_spawn_chance: 0.0, minimum_food, ..Parameters::default() }
+}
+
+// Every other column walled off, leaving the odd ones open
+fn columns() -> HashSet<Tile> {
+    (0..11).step_by(2).flat_map(|x| (0..11).map(move |y| Tile { x, y })).collect()
+}
+
+#[test]
+fn moving_into_a_wall_eliminates_the_snake() {
+    let mut game = GameInstance::new(11, 11, 1, 0.0);
+    game.reset_with_parameters(params(1, 0), &mut StdRng::seed_from_u64(0));
+    game.set_walls([Tile { x: 5, y: 4 }].into_iter().collect());
+    game.set_layout(&[vec![Tile { x: 5, y: 5 }, Tile { x: 5, y: 6 }, Tile { x: 5, y: 7 }]], &[]);
+    let id = game.get_player_ids()[0];
+    assert!(game.is_move_fatal(id, 'u'));
+    assert!(!game.is_move_fatal(id, 'l'));
+    game.set_player_move(id, 'u');
+    game.step();
+    let player = &game.get_state().1[&id];
+    assert!(!player.alive);
+    assert_eq!(player.death_reason, DeathReason::Wall);
+}
+
+#[test]
+fn spawns_and_food_avoid_walls() {
+    for seed in 0..20 {
+        let mut game = GameInstance::new(11, 11, 4, 0.0);
+        game.set_walls(columns());
+        game.reset_with_parameters(params(4, 0), &mut StdRng::seed_from_u64(seed));
+        let (_, players, food, _, _, _, _) = game.get_state();
+        assert!(players.values().flat_map(|p| &p.body).all(|t| t.x % 2 == 1));
+        assert_eq!(food.len(), 4);
+        assert!(food.iter().all(|t| t.x % 2 == 1));
+    }
+}
+
+#[test]
+fn walls_outside_a_smaller_board_are_dropped() {
+    let mut game = GameInstance::new(11, 11, 1, 0.0);
+    game.set_walls([Tile { x: 1, y: 1 }, Tile { x: 9, y: 9 }].into_iter().collect());
+    let small = Parameters { board_width: 7, board_length: 7, ..params(1, 0) };
+    game.reset_with_parameters(small, &mut StdRng::seed_from_u64(0));
+    assert_eq!(game.get_walls(), &[Tile { x: 1, y: 1 }].into_iter().collect());
+}